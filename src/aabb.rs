@@ -1,6 +1,6 @@
 use crate::{cuboid::Cuboid, matrix::Matrix4, point::Point3};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Aabb {
     pub min: Point3,
     pub max: Point3,
@@ -0,0 +1,658 @@
+/*! Keyframe animation of objects, lights and the camera.
+
+An [`Animation`] is a list of [`Track`]s, each of which animates one property of the scene with a
+[`Curve`]. Animations are part of the scene file:
+
+```ron
+animation: (
+    tracks: [
+        Object(
+            name: "monkey",
+            property: Rotation,
+            curve: (
+                interpolation: Linear,
+                keyframes: [
+                    (time: 0.0, value: (x: 0.0, y: 0.0, z: 0.0)),
+                    (time: 8.0, value: (x: 0.0, y: 360.0, z: 0.0)),
+                ],
+            ),
+        ),
+        LuminousPower(light: 0, curve: (keyframes: [(time: 0.0, value: 0.0), (time: 4.0, value: 600000.0)])),
+    ],
+),
+```
+
+Evaluating an animation only depends on the time, so it can be scrubbed back and forth.
+*/
+
+use cgmath::InnerSpace;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    camera::Camera,
+    point::Point3,
+    scene::{SceneDescription, Transform},
+    vector::{Vec3, Vec4},
+};
+
+/// Values that keyframes can hold.
+pub trait Interpolate: Copy {
+    fn add(self, other: Self) -> Self;
+    fn scale(self, factor: f32) -> Self;
+}
+
+impl Interpolate for f32 {
+    fn add(self, other: Self) -> Self {
+        self + other
+    }
+
+    fn scale(self, factor: f32) -> Self {
+        self * factor
+    }
+}
+
+impl Interpolate for Vec3 {
+    fn add(self, other: Self) -> Self {
+        self + other
+    }
+
+    fn scale(self, factor: f32) -> Self {
+        factor * self
+    }
+}
+
+/// Quaternions are interpolated component-wise, so they need to be normalised after sampling (see
+/// [`JointTransform::rotation`](crate::skin::JointTransform::rotation)).
+impl Interpolate for Vec4 {
+    fn add(self, other: Self) -> Self {
+        Vec4 {
+            x: self.x + other.x,
+            y: self.y + other.y,
+            z: self.z + other.z,
+            w: self.w + other.w,
+        }
+    }
+
+    fn scale(self, factor: f32) -> Self {
+        Vec4 {
+            x: self.x * factor,
+            y: self.y * factor,
+            z: self.z * factor,
+            w: self.w * factor,
+        }
+    }
+}
+
+impl Interpolate for Point3 {
+    fn add(self, other: Self) -> Self {
+        self + other
+    }
+
+    fn scale(self, factor: f32) -> Self {
+        Point3 {
+            x: self.x * factor,
+            y: self.y * factor,
+            z: self.z * factor,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Interpolation {
+    /// Keep each keyframe's value until the next keyframe.
+    Step,
+
+    #[default]
+    Linear,
+
+    /// A Catmull-Rom spline, which passes through the keyframes with tangents that point from the
+    /// previous keyframe to the next one.
+    Cubic,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Keyframe<T> {
+    /// In seconds since the start of the animation.
+    pub time: f32,
+    pub value: T,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Curve<T> {
+    #[serde(default)]
+    pub interpolation: Interpolation,
+
+    /// Sorted by time, with no two at the same time.
+    pub keyframes: Vec<Keyframe<T>>,
+}
+
+impl<T: Interpolate> Curve<T> {
+    /**
+    The curve's value at `time`.
+
+    Before the first keyframe the value is the first keyframe's, and after the last keyframe it's
+    the last keyframe's. A curve without keyframes doesn't have a value.
+    */
+    pub fn sample(&self, time: f32) -> Option<T> {
+        let keyframes = &self.keyframes;
+        let next = keyframes.partition_point(|keyframe| keyframe.time <= time);
+        if next == 0 {
+            return keyframes.first().map(|keyframe| keyframe.value);
+        }
+        if next == keyframes.len() {
+            return Some(keyframes[next - 1].value);
+        }
+
+        let from = &keyframes[next - 1];
+        let to = &keyframes[next];
+        let duration = to.time - from.time;
+        let t = (time - from.time) / duration;
+        Some(match self.interpolation {
+            Interpolation::Step => from.value,
+            Interpolation::Linear => from.value.scale(1.0 - t).add(to.value.scale(t)),
+            Interpolation::Cubic => {
+                // The first and last keyframes' tangents only use the keyframe next to them.
+                let tangent = |index: usize| {
+                    let before = &keyframes[index.saturating_sub(1)];
+                    let after = &keyframes[(index + 1).min(keyframes.len() - 1)];
+                    after
+                        .value
+                        .add(before.value.scale(-1.0))
+                        .scale(1.0 / (after.time - before.time))
+                };
+
+                // <https://en.wikipedia.org/wiki/Cubic_Hermite_spline#Interpolation_on_an_arbitrary_interval>
+                let t2 = t * t;
+                let t3 = t2 * t;
+                from.value
+                    .scale(2.0 * t3 - 3.0 * t2 + 1.0)
+                    .add(tangent(next - 1).scale(duration * (t3 - 2.0 * t2 + t)))
+                    .add(to.value.scale(-2.0 * t3 + 3.0 * t2))
+                    .add(tangent(next).scale(duration * (t3 - t2)))
+            }
+        })
+    }
+
+    /// The time of the last keyframe.
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |keyframe| keyframe.time)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        match self
+            .keyframes
+            .windows(2)
+            .find(|pair| pair[0].time >= pair[1].time)
+        {
+            Some(pair) => Err(format!(
+                "keyframe at {}s comes after the keyframe at {}s",
+                pair[1].time, pair[0].time
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransformProperty {
+    Translation,
+
+    /// In degrees, like [`Transform::rotation`].
+    Rotation,
+
+    Scale,
+}
+
+impl TransformProperty {
+    fn of(self, transform: &mut Transform) -> &mut Vec3 {
+        match self {
+            TransformProperty::Translation => &mut transform.translation,
+            TransformProperty::Rotation => &mut transform.rotation,
+            TransformProperty::Scale => &mut transform.scale,
+        }
+    }
+}
+
+/// One animated property.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Track {
+    /// Part of the transform of the object with this name.
+    Object {
+        name: String,
+        property: TransformProperty,
+        curve: Curve<Vec3>,
+    },
+
+    /// The luminous power (in lumens) of the point light at this index.
+    LuminousPower {
+        light: usize,
+        curve: Curve<f32>,
+    },
+
+    /// The illuminance (in lux) of the directional light at this index.
+    Illuminance {
+        light: usize,
+        curve: Curve<f32>,
+    },
+
+    CameraEye(Curve<Point3>),
+
+    /// The direction the camera looks in. It's normalized after it's interpolated.
+    CameraDirection(Curve<Vec3>),
+}
+
+impl Track {
+    fn duration(&self) -> f32 {
+        match self {
+            Track::Object { curve, .. } | Track::CameraDirection(curve) => curve.duration(),
+            Track::LuminousPower { curve, .. } | Track::Illuminance { curve, .. } => {
+                curve.duration()
+            }
+            Track::CameraEye(curve) => curve.duration(),
+        }
+    }
+}
+
+/**
+The values of an animation's tracks at one time, from [`Animation::apply`].
+
+They're kept apart from the [`SceneDescription`] that they animate, so that saving the scene saves
+what was in its file (and any edits), rather than wherever the animation was up to.
+*/
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Animated {
+    /// Animated properties of [`SceneDescription::objects`], by index, in the order of their tracks.
+    pub transforms: Vec<(usize, TransformProperty, Vec3)>,
+
+    /// Luminous powers of [`SceneDescription::point_lights`], by index.
+    pub luminous_powers: Vec<(usize, f32)>,
+
+    /// Illuminances of [`SceneDescription::directional_lights`], by index.
+    pub illuminances: Vec<(usize, f32)>,
+
+    pub camera: bool,
+}
+
+impl Animated {
+    /// Indices in [`SceneDescription::objects`] of the animated objects, without duplicates.
+    pub fn objects(&self) -> Vec<usize> {
+        let mut objects: Vec<usize> = self.transforms.iter().map(|(index, ..)| *index).collect();
+        objects.sort_unstable();
+        objects.dedup();
+        objects
+    }
+
+    pub fn lights(&self) -> bool {
+        !self.luminous_powers.is_empty() || !self.illuminances.is_empty()
+    }
+
+    /// `transform` (of the object at `index`) with the object's animated properties.
+    pub fn transform(&self, index: usize, mut transform: Transform) -> Transform {
+        for (_, property, value) in self.transforms.iter().filter(|(i, ..)| *i == index) {
+            *property.of(&mut transform) = *value;
+        }
+        transform
+    }
+
+    /// `transform` (of the object at `index`) with its animated properties taken from
+    /// `unanimated`, so that an edit only changes the properties that the animation doesn't.
+    pub fn unanimated(
+        &self,
+        index: usize,
+        mut transform: Transform,
+        mut unanimated: Transform,
+    ) -> Transform {
+        for (_, property, _) in self.transforms.iter().filter(|(i, ..)| *i == index) {
+            *property.of(&mut transform) = *property.of(&mut unanimated);
+        }
+        transform
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Animation {
+    pub tracks: Vec<Track>,
+}
+
+impl Animation {
+    pub fn is_empty(&self) -> bool {
+        self.tracks.is_empty()
+    }
+
+    /// The time of the last keyframe of any track.
+    pub fn duration(&self) -> f32 {
+        self.tracks.iter().map(Track::duration).fold(0.0, f32::max)
+    }
+
+    /// Check that the tracks' objects and lights are in the scene, and that their keyframes are in
+    /// order.
+    pub fn validate(&self, description: &SceneDescription) -> Result<(), String> {
+        let objects = description.objects();
+        for track in &self.tracks {
+            let result = match track {
+                Track::Object { name, curve, .. } => {
+                    if objects.iter().all(|(_, object)| &object.name != name) {
+                        return Err(format!("animated object {:?} isn't in the scene", name));
+                    }
+                    curve.validate()
+                }
+                Track::LuminousPower { light, curve } => {
+                    if *light >= description.point_lights.len() {
+                        return Err(format!("animated point light {} isn't in the scene", light));
+                    }
+                    curve.validate()
+                }
+                Track::Illuminance { light, curve } => {
+                    if *light >= description.directional_lights.len() {
+                        return Err(format!(
+                            "animated directional light {} isn't in the scene",
+                            light
+                        ));
+                    }
+                    curve.validate()
+                }
+                Track::CameraEye(curve) => curve.validate(),
+                Track::CameraDirection(curve) => curve.validate(),
+            };
+            result?;
+        }
+        Ok(())
+    }
+
+    /**
+    Sample the animated properties of `description` at `time`, and set the animated properties of
+    `camera`.
+
+    Objects and lights that aren't in `description` are skipped (see [`Animation::validate`]).
+    */
+    pub fn apply(
+        &self,
+        time: f32,
+        description: &SceneDescription,
+        camera: &mut Camera,
+    ) -> Animated {
+        let mut animated = Animated::default();
+        let object_names: Vec<&str> = description
+            .objects()
+            .into_iter()
+            .map(|(_, object)| object.name.as_str())
+            .collect();
+
+        for track in &self.tracks {
+            match track {
+                Track::Object {
+                    name,
+                    property,
+                    curve,
+                } => {
+                    let Some(index) = object_names.iter().position(|object| object == name) else {
+                        continue;
+                    };
+                    if let Some(value) = curve.sample(time) {
+                        animated.transforms.push((index, *property, value));
+                    }
+                }
+                Track::LuminousPower { light, curve } => {
+                    if let Some(value) = curve.sample(time) {
+                        if *light < description.point_lights.len() {
+                            animated.luminous_powers.push((*light, value));
+                        }
+                    }
+                }
+                Track::Illuminance { light, curve } => {
+                    if let Some(value) = curve.sample(time) {
+                        if *light < description.directional_lights.len() {
+                            animated.illuminances.push((*light, value));
+                        }
+                    }
+                }
+                Track::CameraEye(curve) => {
+                    if let Some(value) = curve.sample(time) {
+                        camera.eye = value;
+                        animated.camera = true;
+                    }
+                }
+                Track::CameraDirection(curve) => {
+                    if let Some(value) = curve.sample(time) {
+                        camera.direction = cgmath::Vector3::from(value).normalize();
+                        animated.camera = true;
+                    }
+                }
+            }
+        }
+
+        animated
+    }
+}
+
+/// Where an animation is up to, and whether it's playing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Playback {
+    /// In seconds since the start of the animation.
+    pub time: f32,
+    pub playing: bool,
+
+    /// Start again from the beginning after the end, instead of stopping.
+    pub looping: bool,
+}
+
+impl Default for Playback {
+    fn default() -> Self {
+        Self {
+            time: 0.0,
+            playing: true,
+            looping: true,
+        }
+    }
+}
+
+impl Playback {
+    /// Move `elapsed` seconds through an animation that's `duration` seconds long, if it's playing.
+    /// Returns whether the time changed.
+    pub fn advance(&mut self, elapsed: f32, duration: f32) -> bool {
+        if !self.playing {
+            return false;
+        }
+
+        self.time += elapsed;
+        if self.time >= duration {
+            if self.looping && duration > 0.0 {
+                self.time %= duration;
+            } else {
+                self.time = duration;
+                self.playing = false;
+            }
+        }
+        true
+    }
+}
+
+#[test]
+fn test_curve_sample() {
+    let curve = |interpolation| Curve {
+        interpolation,
+        keyframes: vec![
+            Keyframe {
+                time: 1.0,
+                value: 0.0,
+            },
+            Keyframe {
+                time: 2.0,
+                value: 1.0,
+            },
+            Keyframe {
+                time: 3.0,
+                value: 0.0,
+            },
+        ],
+    };
+    let close = |a: Option<f32>, b: f32| a.is_some_and(|a| (a - b).abs() < 1e-6);
+
+    for interpolation in [
+        Interpolation::Step,
+        Interpolation::Linear,
+        Interpolation::Cubic,
+    ] {
+        let curve = curve(interpolation);
+
+        // The curve passes through the keyframes, and is flat outside them.
+        assert!(close(curve.sample(0.0), 0.0), "{:?}", interpolation);
+        assert!(close(curve.sample(1.0), 0.0), "{:?}", interpolation);
+        assert!(close(curve.sample(2.0), 1.0), "{:?}", interpolation);
+        assert!(close(curve.sample(3.0), 0.0), "{:?}", interpolation);
+        assert!(close(curve.sample(4.0), 0.0), "{:?}", interpolation);
+    }
+
+    assert!(close(curve(Interpolation::Step).sample(1.5), 0.0));
+    assert!(close(curve(Interpolation::Step).sample(2.9), 1.0));
+    assert!(close(curve(Interpolation::Linear).sample(1.5), 0.5));
+    assert!(close(curve(Interpolation::Linear).sample(2.25), 0.75));
+
+    /* The middle keyframe's tangent is flat, and the first keyframe's points at the middle one:
+    h00(0.5) * 0 + h10(0.5) * 1 + h01(0.5) * 1 + h11(0.5) * 0 = 0.125 + 0.5
+    */
+    assert!(close(curve(Interpolation::Cubic).sample(1.5), 0.625));
+    assert!(close(curve(Interpolation::Cubic).sample(2.5), 0.625));
+
+    let empty: Curve<f32> = Curve {
+        interpolation: Interpolation::Linear,
+        keyframes: Vec::new(),
+    };
+    assert_eq!(empty.sample(1.0), None);
+    assert_eq!(empty.duration(), 0.0);
+}
+
+#[test]
+fn test_animation_apply() {
+    let description = SceneDescription::read("scenes/default.ron").unwrap();
+    let mut camera = description.camera.camera(1.0);
+    let keyframes = |from: Vec3, to: Vec3| Curve {
+        interpolation: Interpolation::Linear,
+        keyframes: vec![
+            Keyframe {
+                time: 0.0,
+                value: from,
+            },
+            Keyframe {
+                time: 2.0,
+                value: to,
+            },
+        ],
+    };
+    let animation = Animation {
+        tracks: vec![
+            Track::Object {
+                name: String::from("square"),
+                property: TransformProperty::Translation,
+                curve: keyframes(Vec3::ZERO, 2.0 * Vec3::Y),
+            },
+            Track::Object {
+                name: String::from("square"),
+                property: TransformProperty::Scale,
+                curve: keyframes(Vec3::X, 3.0 * Vec3::X),
+            },
+            Track::Illuminance {
+                light: 0,
+                curve: Curve {
+                    interpolation: Interpolation::Step,
+                    keyframes: vec![
+                        Keyframe {
+                            time: 0.0,
+                            value: 1.0,
+                        },
+                        Keyframe {
+                            time: 3.0,
+                            value: 2.0,
+                        },
+                    ],
+                },
+            },
+            Track::CameraDirection(keyframes(Vec3::X, -Vec3::Z)),
+        ],
+    };
+    assert_eq!(animation.validate(&description), Ok(()));
+    assert_eq!(animation.duration(), 3.0);
+
+    let square = description
+        .objects()
+        .iter()
+        .position(|(_, object)| object.name == "square")
+        .unwrap();
+    let saved = description.clone();
+    let animated = animation.apply(1.0, &description, &mut camera);
+    assert_eq!(animated.objects(), [square]);
+    assert!(animated.lights());
+    assert!(animated.camera);
+    assert_eq!(description, saved, "the description isn't animated");
+
+    let unanimated = description.objects()[square].1.transform;
+    let transform = animated.transform(square, unanimated);
+    assert_eq!(transform.translation, Vec3::Y);
+    assert_eq!(transform.scale, 2.0 * Vec3::X);
+    assert_eq!(transform.rotation, unanimated.rotation);
+    assert_eq!(animated.illuminances, [(0, 1.0)]);
+    let direction = cgmath::Vector3::new(1.0, 0.0, -1.0).normalize();
+    assert!((camera.direction - direction).magnitude() < 1e-6);
+
+    // Editing the animated transform only keeps the change to the property that isn't animated.
+    let edited = Transform {
+        rotation: 45.0 * Vec3::Y,
+        ..transform
+    };
+    assert_eq!(
+        animated.unanimated(square, edited, unanimated),
+        Transform {
+            rotation: 45.0 * Vec3::Y,
+            ..unanimated
+        }
+    );
+
+    // The same time gives the same result, whatever was applied before.
+    animation.apply(2.5, &description, &mut camera);
+    assert_eq!(animation.apply(1.0, &description, &mut camera), animated);
+
+    let mut invalid = animation.clone();
+    invalid.tracks.push(Track::LuminousPower {
+        light: description.point_lights.len(),
+        curve: Curve {
+            interpolation: Interpolation::Linear,
+            keyframes: Vec::new(),
+        },
+    });
+    assert!(invalid.validate(&description).is_err());
+
+    let mut unordered = animation;
+    unordered.tracks.push(Track::CameraEye(Curve {
+        interpolation: Interpolation::Linear,
+        keyframes: vec![
+            Keyframe {
+                time: 1.0,
+                value: Point3::ZERO,
+            },
+            Keyframe {
+                time: 1.0,
+                value: Point3::ZERO,
+            },
+        ],
+    }));
+    assert!(unordered.validate(&description).is_err());
+}
+
+#[test]
+fn test_playback() {
+    let mut playback = Playback::default();
+    assert!(playback.advance(1.5, 2.0));
+    assert_eq!(playback.time, 1.5);
+
+    // Looping wraps around to the start.
+    assert!(playback.advance(1.0, 2.0));
+    assert_eq!(playback.time, 0.5);
+
+    // Otherwise it stops at the end.
+    playback.looping = false;
+    assert!(playback.advance(2.0, 2.0));
+    assert_eq!(playback.time, 2.0);
+    assert!(!playback.playing);
+    assert!(!playback.advance(1.0, 2.0));
+    assert_eq!(playback.time, 2.0);
+}
@@ -0,0 +1,203 @@
+/*! Loading assets on background threads.
+
+Parsing a model (and generating its [levels of detail](crate::lod)) or decoding an HDRI can take
+seconds, so an [`AssetLoader`] does it on a pool of worker threads. The render thread requests
+assets, keeps rendering, and picks up each finished asset with [`AssetLoader::poll`] so that it can
+upload it with [`insert_model`](crate::load::insert_model),
+[`insert_gltf`](crate::load_gltf::insert_gltf) or `queue.write_texture`.
+
+Only CPU work happens on the worker threads. Everything that touches the GPU stays on the render
+thread.
+*/
+
+use std::{
+    sync::{mpsc, Arc, Mutex},
+    thread,
+};
+
+use crate::{
+    error::LoadError,
+    hdri::Hdri,
+    load::{read_model, LoadOptions, ModelFile},
+    load_gltf::{self, GltfScene},
+};
+
+/// Identifies a request made to an [`AssetLoader`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AssetId(pub u32);
+
+/// An asset that's been read from a file, but not uploaded to the GPU.
+pub enum Asset {
+    Model(ModelFile),
+
+    /// A model file that's a glTF scene, which is inserted with its node hierarchy and skins.
+    Gltf(GltfScene),
+
+    Hdri(Hdri),
+}
+
+/// The outcome of a request.
+pub struct LoadedAsset {
+    pub id: AssetId,
+    pub file_name: String,
+    pub result: Result<Asset, LoadError>,
+}
+
+type Job = Box<dyn FnOnce() -> LoadedAsset + Send>;
+
+pub struct AssetLoader {
+    next_id: u32,
+
+    /// `None` once the loader is being dropped, which tells the workers to stop.
+    jobs: Option<mpsc::Sender<Job>>,
+    loaded: mpsc::Receiver<LoadedAsset>,
+    workers: Vec<thread::JoinHandle<()>>,
+}
+
+impl AssetLoader {
+    pub fn new(worker_count: usize) -> Self {
+        let (jobs, job_receiver) = mpsc::channel::<Job>();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+        let (loaded_sender, loaded) = mpsc::channel();
+
+        let workers = (0..worker_count.max(1))
+            .map(|index| {
+                let job_receiver = job_receiver.clone();
+                let loaded_sender = loaded_sender.clone();
+                thread::Builder::new()
+                    .name(format!("asset_loader_{}", index))
+                    .spawn(move || loop {
+                        /* The lock is released before the job runs, so that the other workers can
+                        take jobs in the meantime.
+                        */
+                        let job = match job_receiver.lock().unwrap().recv() {
+                            Ok(job) => job,
+                            Err(mpsc::RecvError) => return,
+                        };
+                        if loaded_sender.send(job()).is_err() {
+                            return;
+                        }
+                    })
+                    .unwrap()
+            })
+            .collect();
+
+        AssetLoader {
+            next_id: 0,
+            jobs: Some(jobs),
+            loaded,
+            workers,
+        }
+    }
+
+    fn request(
+        &mut self,
+        file_name: &str,
+        load: impl FnOnce(&str) -> Result<Asset, LoadError> + Send + 'static,
+    ) -> AssetId {
+        let id = AssetId(self.next_id);
+        self.next_id += 1;
+
+        let file_name = String::from(file_name);
+        let job: Job = Box::new(move || {
+            let result = load(&file_name);
+            LoadedAsset {
+                id,
+                file_name,
+                result,
+            }
+        });
+        self.jobs
+            .as_ref()
+            .unwrap()
+            .send(job)
+            .expect("asset loader workers stopped");
+        id
+    }
+
+    /**
+    Read a model file and generate its levels of detail. See [`read_model`] and
+    [`ModelFile::generate_lods`].
+
+    glTF files are read with [`GltfScene::read`] instead, and their levels of detail are generated
+    when they're inserted.
+    */
+    pub fn load_model(&mut self, file_name: &str, options: &LoadOptions) -> AssetId {
+        let normal_style = options.normal_style;
+        let lod_levels = options.lod_levels;
+        self.request(file_name, move |file_name| {
+            if load_gltf::is_gltf(file_name) {
+                return GltfScene::read(file_name).map(Asset::Gltf);
+            }
+            let mut model_file = read_model(file_name, normal_style)?;
+            model_file.generate_lods(lod_levels);
+            Ok(Asset::Model(model_file))
+        })
+    }
+
+    /// Read an HDRI. See [`Hdri::load`].
+    pub fn load_hdri(&mut self, file_name: &str) -> AssetId {
+        self.request(file_name, |file_name| {
+            Hdri::load(file_name).map(Asset::Hdri)
+        })
+    }
+
+    /// The assets that have finished loading since the last call. Doesn't block.
+    pub fn poll(&self) -> impl Iterator<Item = LoadedAsset> + '_ {
+        self.loaded.try_iter()
+    }
+
+    /// Block until the next asset finishes loading.
+    pub fn wait(&self) -> LoadedAsset {
+        self.loaded.recv().expect("asset loader workers stopped")
+    }
+}
+
+impl Drop for AssetLoader {
+    fn drop(&mut self) {
+        /* Closing the job channel makes each worker return once it's finished its current job.
+        Queued jobs are still run, so dropping the loader while a large asset is queued blocks
+        until it's read.
+        */
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[test]
+fn test_asset_loader() {
+    let mut asset_loader = AssetLoader::new(2);
+    let monkey = asset_loader.load_model("models/monkey.obj", &LoadOptions::default());
+    let missing = asset_loader.load_hdri("hdris/missing.hdr");
+    let gltf = asset_loader.load_model("models/test_skin.gltf", &LoadOptions::default());
+
+    let mut loaded: Vec<LoadedAsset> = (0..3).map(|_| asset_loader.wait()).collect();
+    loaded.sort_by_key(|asset| asset.id.0);
+
+    assert_eq!(loaded[0].id, monkey);
+    assert_eq!(loaded[0].file_name, "models/monkey.obj");
+    match &loaded[0].result {
+        Ok(Asset::Model(model_file)) => {
+            let model = model_file.model();
+            assert_eq!(model.objects.len(), 1);
+            assert_eq!(
+                model.objects[0].lods.len(),
+                LoadOptions::default().lod_levels + 1
+            );
+        }
+        _ => panic!("expected a model"),
+    }
+
+    assert_eq!(loaded[1].id, missing);
+    assert!(matches!(loaded[1].result, Err(LoadError::Io { .. })));
+
+    assert_eq!(loaded[2].id, gltf);
+    match &loaded[2].result {
+        Ok(Asset::Gltf(scene)) => assert_eq!(scene.skins.len(), 1),
+        _ => panic!("expected a glTF scene"),
+    }
+
+    assert!(asset_loader.poll().next().is_none());
+}
@@ -0,0 +1,127 @@
+/*! Convert models to and from the [mesh file](it::mesh_file) format.
+
+```
+cargo run --bin convert_model -- models/teapot.obj teapot.mesh
+cargo run --bin convert_model -- teapot.mesh teapot.obj
+```
+
+The direction is chosen from the input file's extension. Any format that
+[`read_model`](it::load::read_model) supports can be converted to a mesh file, and mesh files are
+converted to `.obj`. Levels of detail are generated when writing a mesh file. When writing an
+`.obj` file, its materials are written to an `.mtl` file next to it.
+*/
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use it::{
+    load::{self, Model, NormalStyle},
+    lod,
+    mesh_file::{self, MeshFile},
+};
+
+fn main() {
+    env_logger::init();
+
+    let args: Vec<String> = std::env::args().collect();
+    let [_, input, output] = args.as_slice() else {
+        eprintln!("usage: convert_model INPUT OUTPUT");
+        std::process::exit(1);
+    };
+
+    let result = if Path::new(input).extension() == Some(mesh_file::EXTENSION.as_ref()) {
+        convert_mesh_to_obj(input, Path::new(output))
+    } else {
+        convert_model_to_mesh(input, Path::new(output))
+    };
+
+    if let Err(err) = result {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
+}
+
+fn convert_model_to_mesh(input: &str, output: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let mut model_file = load::read_model(input, NormalStyle::default())?;
+    model_file.generate_lods(lod::DEFAULT_LOD_LEVELS);
+    mesh_file::write(output, &model_file.model())?;
+    Ok(())
+}
+
+fn convert_mesh_to_obj(input: &str, output: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let mesh_file = MeshFile::open(input)?;
+    let model = mesh_file.model();
+
+    let mtl_path = output.with_extension("mtl");
+    write_mtl(&mtl_path, &model)?;
+    write_obj(output, &mtl_path, &model)?;
+    Ok(())
+}
+
+fn material_name(material: u32) -> String {
+    format!("material_{}", material)
+}
+
+fn write_mtl(path: &Path, model: &Model) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    for (index, material) in model.materials.iter().enumerate() {
+        writeln!(writer, "newmtl {}", material_name(index as u32))?;
+        writeln!(
+            writer,
+            "Kd {} {} {}",
+            material.color.r, material.color.g, material.color.b
+        )?;
+        writeln!(writer, "d {}", material.color.a)?;
+        writeln!(writer, "Pr {}", material.roughness)?;
+        writeln!(writer, "Pm {}", material.metallic)?;
+        writeln!(writer)?;
+    }
+    writer.flush()
+}
+
+fn write_obj(path: &Path, mtl_path: &Path, model: &Model) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    if let Some(mtl_file_name) = mtl_path.file_name() {
+        writeln!(writer, "mtllib {}", mtl_file_name.to_string_lossy())?;
+    }
+
+    for vertex in model.vertices.iter() {
+        let position = vertex.position;
+        writeln!(writer, "v {} {} {}", position.x, position.y, position.z)?;
+    }
+    for vertex in model.vertices.iter() {
+        let normal = vertex.normal;
+        writeln!(writer, "vn {} {} {}", normal.x, normal.y, normal.z)?;
+    }
+    for vertex in model.vertices.iter() {
+        // `.obj` texture coordinates start at the bottom left.
+        let uv = vertex.uv;
+        writeln!(writer, "vt {} {}", uv.x, 1.0 - uv.y)?;
+    }
+
+    for object in model.objects.iter() {
+        writeln!(writer, "o {}", object.name)?;
+
+        // `.obj` files can't store levels of detail, so only the full-detail level is written.
+        let indices = &model.indices[object.lods[0].as_range()];
+
+        let mut current_material = None;
+        for triangle in indices.chunks(3) {
+            let material = model.vertices[triangle[0] as usize].material_id.0;
+            if current_material != Some(material) {
+                writeln!(writer, "usemtl {}", material_name(material))?;
+                current_material = Some(material);
+            }
+
+            // `.obj` indices start at 1.
+            let [a, b, c] = [triangle[0] + 1, triangle[1] + 1, triangle[2] + 1];
+            writeln!(writer, "f {a}/{a}/{a} {b}/{b}/{b} {c}/{c}/{c}")?;
+        }
+    }
+
+    writer.flush()
+}
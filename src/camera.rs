@@ -1,6 +1,7 @@
 use winit::window::Window;
 
 use crate::{
+    aabb::Aabb,
     cuboid::Cuboid,
     matrix::Matrix4,
     point::{Point3, Point4},
@@ -113,34 +114,45 @@ impl Camera {
         }
     }
 
-    pub fn frustum_world_space(&self) -> Cuboid {
-        let clip_to_world = self.clip_coordinates_matrix().inverse();
-
-        Cuboid {
-            near_top_left: Point3::from(clip_to_world * CLIP_NEAR_TOP_LEFT),
-            near_top_right: Point3::from(clip_to_world * CLIP_NEAR_TOP_RIGHT),
-            near_bottom_left: Point3::from(clip_to_world * CLIP_NEAR_BOTTOM_LEFT),
-            near_bottom_right: Point3::from(clip_to_world * CLIP_NEAR_BOTTOM_RIGHT),
-            far_top_left: Point3::from(clip_to_world * CLIP_FAR_TOP_LEFT),
-            far_top_right: Point3::from(clip_to_world * CLIP_FAR_TOP_RIGHT),
-            far_bottom_left: Point3::from(clip_to_world * CLIP_FAR_BOTTOM_LEFT),
-            far_bottom_right: Point3::from(clip_to_world * CLIP_FAR_BOTTOM_RIGHT),
+    /**
+    Approximately how much of the viewport's height `aabb` covers, where `1.0` fills it.
+
+    The box is approximated by its bounding sphere. The result is infinite when the camera is
+    inside the sphere.
+    */
+    pub fn projected_size(&self, aabb: &Aabb) -> f32 {
+        let radius = (aabb.max - aabb.min).length() / 2.0;
+        let distance = (aabb.center() - self.eye).length();
+        if distance <= radius {
+            return f32::INFINITY;
         }
+
+        // The tangent of the sphere's angular radius, relative to that of half the field of view.
+        let angular_radius = (radius / distance).asin();
+        angular_radius.tan() / (self.fovy / 2.0).to_radians().tan()
+    }
+
+    pub fn frustum_world_space(&self) -> Cuboid {
+        clip_volume(self.clip_coordinates_matrix().inverse())
     }
 
     pub fn frustum_camera_space(&self) -> Cuboid {
-        let clip_to_camera = self.perspective_matrix().inverse();
-
-        Cuboid {
-            near_top_left: Point3::from(clip_to_camera * CLIP_NEAR_TOP_LEFT),
-            near_top_right: Point3::from(clip_to_camera * CLIP_NEAR_TOP_RIGHT),
-            near_bottom_left: Point3::from(clip_to_camera * CLIP_NEAR_BOTTOM_LEFT),
-            near_bottom_right: Point3::from(clip_to_camera * CLIP_NEAR_BOTTOM_RIGHT),
-            far_top_left: Point3::from(clip_to_camera * CLIP_FAR_TOP_LEFT),
-            far_top_right: Point3::from(clip_to_camera * CLIP_FAR_TOP_RIGHT),
-            far_bottom_left: Point3::from(clip_to_camera * CLIP_FAR_BOTTOM_LEFT),
-            far_bottom_right: Point3::from(clip_to_camera * CLIP_FAR_BOTTOM_RIGHT),
-        }
+        clip_volume(self.perspective_matrix().inverse())
+    }
+}
+
+/// The corners of the WGPU clip volume, taken out of clip space by `clip_to_space` (the inverse of
+/// a matrix like [`Camera::clip_coordinates_matrix`]).
+pub fn clip_volume(clip_to_space: Matrix4) -> Cuboid {
+    Cuboid {
+        near_top_left: Point3::from(clip_to_space * CLIP_NEAR_TOP_LEFT),
+        near_top_right: Point3::from(clip_to_space * CLIP_NEAR_TOP_RIGHT),
+        near_bottom_left: Point3::from(clip_to_space * CLIP_NEAR_BOTTOM_LEFT),
+        near_bottom_right: Point3::from(clip_to_space * CLIP_NEAR_BOTTOM_RIGHT),
+        far_top_left: Point3::from(clip_to_space * CLIP_FAR_TOP_LEFT),
+        far_top_right: Point3::from(clip_to_space * CLIP_FAR_TOP_RIGHT),
+        far_bottom_left: Point3::from(clip_to_space * CLIP_FAR_BOTTOM_LEFT),
+        far_bottom_right: Point3::from(clip_to_space * CLIP_FAR_BOTTOM_RIGHT),
     }
 }
 
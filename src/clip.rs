@@ -1,6 +1,7 @@
 use crate::{point::Point3, vector::Vec3};
 
 /// `plane.normal.dot(Vec3{x, y, z} - plane.point) == 0`
+#[derive(Debug, Clone, Copy)]
 pub struct Plane {
     normal: Vec3,
     point: Point3,
@@ -14,7 +15,13 @@ impl Plane {
         }
     }
 
-    fn distance_to_point(&self, point: Point3) -> f32 {
+    /// A unit vector.
+    pub fn normal(&self) -> Vec3 {
+        self.normal
+    }
+
+    /// The signed distance, which is positive on the side that the normal points towards.
+    pub fn distance_to_point(&self, point: Point3) -> f32 {
         /*
         Projection of `a` in the direction of `b` is `|a| cos theta = |a| * dot(a, b) / |a||b| = dot(a, b) / |b|`.
 
@@ -1,5 +1,14 @@
 #[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    bytemuck::Pod,
+    bytemuck::Zeroable,
+    PartialEq,
+    serde::Serialize,
+    serde::Deserialize,
+)]
 pub struct Color {
     pub r: f32,
     pub g: f32,
@@ -36,3 +45,28 @@ impl Color {
         a: 1.0,
     };
 }
+
+impl Color {
+    /**
+    Create a [`Color`] from linear RGB components.
+
+    [`Color`]s are sRGB-encoded, and are converted back to linear RGB when they're used for shading
+    (see `render_hdr.wgsl:srgb_to_linear`).
+    */
+    pub fn from_linear(r: f32, g: f32, b: f32, a: f32) -> Self {
+        fn linear_to_srgb(linear: f32) -> f32 {
+            if linear <= 0.0031308 {
+                linear * 12.92
+            } else {
+                1.055 * linear.powf(1.0 / 2.4) - 0.055
+            }
+        }
+
+        Color {
+            r: linear_to_srgb(r),
+            g: linear_to_srgb(g),
+            b: linear_to_srgb(b),
+            a,
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use crate::mesh_file::FormatError;
+
+/// A GPU buffer doesn't have room for the data that was inserted into it, and the device doesn't
+/// allow it to grow any bigger.
+#[derive(Debug, Clone)]
+pub struct CapacityError {
+    /// The buffer's label.
+    pub buffer: String,
+
+    /// The number of elements that were inserted.
+    pub requested: u64,
+
+    /// The number of elements that the buffer had room for.
+    pub available: u64,
+}
+
+impl fmt::Display for CapacityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is full: requested {} elements, but only {} are available",
+            self.buffer, self.requested, self.available
+        )
+    }
+}
+
+impl std::error::Error for CapacityError {}
+
+/// An asset couldn't be loaded.
+#[derive(Debug)]
+pub enum LoadError {
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    Obj {
+        path: PathBuf,
+        error: tobj::LoadError,
+    },
+    Gltf {
+        path: PathBuf,
+        error: gltf::Error,
+    },
+    Image {
+        path: PathBuf,
+        error: image::ImageError,
+    },
+    MeshFile {
+        path: PathBuf,
+        error: FormatError,
+    },
+    Scene {
+        path: PathBuf,
+        error: ron::error::SpannedError,
+    },
+
+    /// The file was read, but its contents aren't valid.
+    Invalid {
+        path: PathBuf,
+        reason: String,
+    },
+
+    /// The asset was read, but there wasn't enough room for it on the GPU.
+    Capacity {
+        path: PathBuf,
+        error: CapacityError,
+    },
+}
+
+impl LoadError {
+    /// The file that couldn't be loaded.
+    pub fn path(&self) -> &Path {
+        match self {
+            LoadError::Io { path, .. }
+            | LoadError::Obj { path, .. }
+            | LoadError::Gltf { path, .. }
+            | LoadError::Image { path, .. }
+            | LoadError::MeshFile { path, .. }
+            | LoadError::Scene { path, .. }
+            | LoadError::Invalid { path, .. }
+            | LoadError::Capacity { path, .. } => path,
+        }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = self.path().display();
+        match self {
+            LoadError::Io { error, .. } => write!(f, "failed to read {}: {}", path, error),
+            LoadError::Obj { error, .. } => write!(f, "failed to load OBJ {}: {}", path, error),
+            LoadError::Gltf { error, .. } => write!(f, "failed to load glTF {}: {}", path, error),
+            LoadError::Image { error, .. } => write!(f, "failed to load image {}: {}", path, error),
+            LoadError::MeshFile { error, .. } => {
+                write!(f, "failed to load mesh file {}: {}", path, error)
+            }
+            LoadError::Scene { error, .. } => write!(f, "failed to load scene {}: {}", path, error),
+            LoadError::Invalid { reason, .. } => write!(f, "failed to load {}: {}", path, reason),
+            LoadError::Capacity { error, .. } => write!(f, "failed to load {}: {}", path, error),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoadError::Io { error, .. } => Some(error),
+            LoadError::Obj { error, .. } => Some(error),
+            LoadError::Gltf { error, .. } => Some(error),
+            LoadError::Image { error, .. } => Some(error),
+            LoadError::MeshFile { error, .. } => Some(error),
+            LoadError::Scene { error, .. } => Some(error),
+            LoadError::Invalid { .. } => None,
+            LoadError::Capacity { error, .. } => Some(error),
+        }
+    }
+}
+
+/// An asset couldn't be saved.
+#[derive(Debug)]
+pub enum SaveError {
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    Scene {
+        path: PathBuf,
+        error: ron::Error,
+    },
+}
+
+impl SaveError {
+    /// The file that couldn't be saved.
+    pub fn path(&self) -> &Path {
+        match self {
+            SaveError::Io { path, .. } | SaveError::Scene { path, .. } => path,
+        }
+    }
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = self.path().display();
+        match self {
+            SaveError::Io { error, .. } => write!(f, "failed to write {}: {}", path, error),
+            SaveError::Scene { error, .. } => write!(f, "failed to save scene {}: {}", path, error),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SaveError::Io { error, .. } => Some(error),
+            SaveError::Scene { error, .. } => Some(error),
+        }
+    }
+}
@@ -0,0 +1,118 @@
+/*! Noticing when files change on disk.
+
+Files are watched by polling their modification times, which is cheap for the handful of assets
+that the viewer loads and doesn't need any platform-specific APIs.
+*/
+
+use std::{
+    fs,
+    time::{Duration, Instant, SystemTime},
+};
+
+struct WatchedFile {
+    file_name: String,
+
+    /// `None` when the file couldn't be read, e.g. while it's being replaced.
+    modified: Option<SystemTime>,
+}
+
+pub struct FileWatcher {
+    files: Vec<WatchedFile>,
+
+    /// The minimum time between checks.
+    interval: Duration,
+    last_checked: Instant,
+}
+
+impl FileWatcher {
+    pub fn new(interval: Duration) -> Self {
+        FileWatcher {
+            files: Vec::new(),
+            interval,
+            last_checked: Instant::now(),
+        }
+    }
+
+    /// Start watching a file. Watching a file twice has no effect.
+    pub fn watch(&mut self, file_name: &str) {
+        if self.files.iter().any(|file| file.file_name == file_name) {
+            return;
+        }
+        self.files.push(WatchedFile {
+            file_name: String::from(file_name),
+            modified: modified(file_name),
+        });
+    }
+
+    /**
+    The watched files that have been modified since the last check.
+
+    Returns nothing until the watcher's interval has passed since the last check, so this can be
+    called every frame. A file that's deleted and then recreated counts as modified once it's
+    recreated.
+    */
+    pub fn changed(&mut self) -> Vec<String> {
+        if self.last_checked.elapsed() < self.interval {
+            return Vec::new();
+        }
+        self.last_checked = Instant::now();
+
+        self.files
+            .iter_mut()
+            .filter_map(|file| {
+                let modified = modified(&file.file_name);
+                let changed = modified.is_some() && modified != file.modified;
+                file.modified = modified;
+                if changed {
+                    Some(file.file_name.clone())
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+fn modified(file_name: &str) -> Option<SystemTime> {
+    fs::metadata(file_name)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+#[test]
+fn test_file_watcher() {
+    let directory = std::env::temp_dir().join(format!("file_watcher_{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    let path = directory.join("model.obj");
+    let file_name = path.to_str().unwrap();
+    fs::write(&path, "").unwrap();
+
+    let set_modified = |seconds: u64| {
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds))
+            .unwrap();
+    };
+    set_modified(1);
+
+    let mut file_watcher = FileWatcher::new(Duration::ZERO);
+    file_watcher.watch(file_name);
+    file_watcher.watch(file_name);
+    assert_eq!(file_watcher.changed(), Vec::<String>::new());
+
+    set_modified(2);
+    assert_eq!(file_watcher.changed(), vec![String::from(file_name)]);
+    assert_eq!(file_watcher.changed(), Vec::<String>::new());
+
+    // Nothing is reported while the file is missing.
+    fs::remove_file(&path).unwrap();
+    assert_eq!(file_watcher.changed(), Vec::<String>::new());
+
+    fs::write(&path, "").unwrap();
+    set_modified(1);
+    assert_eq!(file_watcher.changed(), vec![String::from(file_name)]);
+
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,159 @@
+/*! Culling objects that a camera or a shadow map can't see.
+
+A [`Frustum`] is the volume that a view and projection can see, as 6 planes that face inwards. An
+object is only drawn for a view when its world-space bounds intersect the view's frustum.
+*/
+
+use crate::{aabb::Aabb, camera, clip::Plane, cuboid::Cuboid, matrix::Matrix4, point::Point3};
+
+#[derive(Debug, Clone, Copy)]
+pub struct Frustum {
+    /// Near, far, left, right, top and bottom, with their normals pointing into the frustum.
+    planes: [Plane; 6],
+}
+
+impl Frustum {
+    /// The frustum that `clip_coordinates_matrix` (a projection times a view) takes to the WGPU
+    /// clip volume.
+    pub fn new(clip_coordinates_matrix: Matrix4) -> Self {
+        Self::from_cuboid(&camera::clip_volume(clip_coordinates_matrix.inverse()))
+    }
+
+    /// The frustum with `cuboid`'s faces, like [`camera::Camera::frustum_world_space`].
+    pub fn from_cuboid(cuboid: &Cuboid) -> Self {
+        let center = cuboid.center();
+        let plane = |a: Point3, b: Point3, c: Point3| {
+            let normal = (b - a).cross(c - a);
+            let plane = Plane::new(normal, a);
+            if plane.distance_to_point(center) < 0.0 {
+                Plane::new(-normal, a)
+            } else {
+                plane
+            }
+        };
+
+        Frustum {
+            planes: [
+                plane(
+                    cuboid.near_top_left,
+                    cuboid.near_top_right,
+                    cuboid.near_bottom_left,
+                ),
+                plane(
+                    cuboid.far_top_left,
+                    cuboid.far_top_right,
+                    cuboid.far_bottom_left,
+                ),
+                plane(
+                    cuboid.near_top_left,
+                    cuboid.near_bottom_left,
+                    cuboid.far_top_left,
+                ),
+                plane(
+                    cuboid.near_top_right,
+                    cuboid.near_bottom_right,
+                    cuboid.far_top_right,
+                ),
+                plane(
+                    cuboid.near_top_left,
+                    cuboid.near_top_right,
+                    cuboid.far_top_left,
+                ),
+                plane(
+                    cuboid.near_bottom_left,
+                    cuboid.near_bottom_right,
+                    cuboid.far_bottom_left,
+                ),
+            ],
+        }
+    }
+
+    /// The planes as `[normal.x, normal.y, normal.z, d]`, where a point `p` is on the inside of a
+    /// plane when `dot(normal, p) + d >= 0`. The GPU culling shader tests bounds against these.
+    pub fn plane_equations(&self) -> [[f32; 4]; 6] {
+        self.planes.map(|plane| {
+            let normal = plane.normal();
+            [
+                normal.x,
+                normal.y,
+                normal.z,
+                plane.distance_to_point(Point3::ZERO),
+            ]
+        })
+    }
+
+    /**
+    Whether any of `aabb` might be inside the frustum.
+
+    A box is outside when it's entirely behind one of the planes, which is the case when its corner
+    furthest along the plane's normal is behind it. This is conservative: a box near one of the
+    frustum's edges can be outside without being behind any single plane, and is still drawn.
+    */
+    pub fn intersects(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            let normal = plane.normal();
+            let along =
+                |component: f32, min: f32, max: f32| if component >= 0.0 { max } else { min };
+            let furthest = Point3 {
+                x: along(normal.x, aabb.min.x, aabb.max.x),
+                y: along(normal.y, aabb.min.y, aabb.max.y),
+                z: along(normal.z, aabb.min.z, aabb.max.z),
+            };
+            plane.distance_to_point(furthest) >= 0.0
+        })
+    }
+}
+
+#[test]
+fn test_intersects() {
+    use crate::vector::Vec3;
+
+    // Looking down -Z from the origin, with a 90° field of view and far plane at 10.
+    let frustum = Frustum::new(
+        Matrix4::perspective(90.0, 1.0, 0.1, 10.0)
+            * Matrix4::look_to(Point3::ZERO, -Vec3::Z, Vec3::Y),
+    );
+    let cube = |x: f32, y: f32, z: f32| Aabb {
+        min: Point3 {
+            x: x - 0.5,
+            y: y - 0.5,
+            z: z - 0.5,
+        },
+        max: Point3 {
+            x: x + 0.5,
+            y: y + 0.5,
+            z: z + 0.5,
+        },
+    };
+
+    assert!(frustum.intersects(&cube(0.0, 0.0, -5.0)), "in front");
+    assert!(
+        frustum.intersects(&cube(5.0, 0.0, -5.0)),
+        "on the right edge"
+    );
+    assert!(
+        !frustum.intersects(&cube(0.0, 0.0, 5.0)),
+        "behind the camera"
+    );
+    assert!(
+        !frustum.intersects(&cube(0.0, 0.0, -12.0)),
+        "past the far plane"
+    );
+    assert!(!frustum.intersects(&cube(8.0, 0.0, -5.0)), "to the right");
+    assert!(!frustum.intersects(&cube(0.0, -8.0, -5.0)), "below");
+
+    // A box that contains the whole frustum.
+    let everything = Aabb {
+        min: Point3 {
+            x: -100.0,
+            y: -100.0,
+            z: -100.0,
+        },
+        max: Point3 {
+            x: 100.0,
+            y: 100.0,
+            z: 100.0,
+        },
+    };
+    assert!(frustum.intersects(&everything));
+}
@@ -0,0 +1,443 @@
+/*! Handles for moving, rotating and scaling the picked object.
+
+The handles are drawn with [`RenderWireframe`](crate::render_wireframe::RenderWireframe) at the
+object's origin, along the world's axes, and stay the same size on screen. Dragging one of them
+changes the object's [`Transform`] along or around that axis.
+*/
+
+use cgmath::{EuclideanSpace, InnerSpace, Vector2, Vector3};
+
+use crate::{
+    aabb::Aabb, camera::Camera, matrix::Matrix4, point::Point3, scene::Transform, vector::Vec3,
+};
+
+/// How much of the viewport's height the handles cover.
+const SCREEN_SIZE: f32 = 0.15;
+
+/// How far (in pixels) the cursor can be from a handle to grab it.
+const GRAB_DISTANCE: f32 = 8.0;
+
+const RING_SEGMENTS: usize = 48;
+
+/// Half the size of the arrow heads and of the scale handles' boxes, relative to the handles.
+const TIP_SIZE: f32 = 0.06;
+
+/// The smallest scale that a scale handle can be dragged to.
+const MIN_SCALE: f32 = 0.01;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Translate,
+    Rotate,
+    Scale,
+}
+
+impl Mode {
+    pub const ALL: [Mode; 3] = [Mode::Translate, Mode::Rotate, Mode::Scale];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Translate => "Translate",
+            Mode::Rotate => "Rotate",
+            Mode::Scale => "Scale",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+impl Axis {
+    pub const ALL: [Axis; 3] = [Axis::X, Axis::Y, Axis::Z];
+
+    fn unit(self) -> Vector3<f32> {
+        match self {
+            Axis::X => Vector3::unit_x(),
+            Axis::Y => Vector3::unit_y(),
+            Axis::Z => Vector3::unit_z(),
+        }
+    }
+
+    /// Two unit vectors that are perpendicular to the axis and to each other.
+    fn perpendicular(self) -> (Vector3<f32>, Vector3<f32>) {
+        match self {
+            Axis::X => (Vector3::unit_y(), Vector3::unit_z()),
+            Axis::Y => (Vector3::unit_z(), Vector3::unit_x()),
+            Axis::Z => (Vector3::unit_x(), Vector3::unit_y()),
+        }
+    }
+
+    fn component(self, vector: &mut Vec3) -> &mut f32 {
+        match self {
+            Axis::X => &mut vector.x,
+            Axis::Y => &mut vector.y,
+            Axis::Z => &mut vector.z,
+        }
+    }
+}
+
+/// The lines of a mode's handles, for handles that are one unit long. See [`Gizmo::model_matrix`].
+pub fn lines(mode: Mode) -> Vec<(Point3, Point3)> {
+    Axis::ALL
+        .into_iter()
+        .flat_map(|axis| handle_lines(mode, axis))
+        .collect()
+}
+
+fn handle_lines(mode: Mode, axis: Axis) -> Vec<(Point3, Point3)> {
+    let point = |vector: Vector3<f32>| Point3::from(cgmath::Point3::from_vec(vector));
+    let unit = axis.unit();
+    let (u, v) = axis.perpendicular();
+    match mode {
+        Mode::Translate => {
+            let arrow_base = (1.0 - 3.0 * TIP_SIZE) * unit;
+            std::iter::once((point(Vector3::new(0.0, 0.0, 0.0)), point(unit)))
+                .chain(
+                    [u, -u, v, -v]
+                        .into_iter()
+                        .map(|side| (point(unit), point(arrow_base + TIP_SIZE * side))),
+                )
+                .collect()
+        }
+        Mode::Rotate => {
+            let ring_point = |segment: usize| {
+                let angle = segment as f32 / RING_SEGMENTS as f32 * std::f32::consts::TAU;
+                point(angle.cos() * u + angle.sin() * v)
+            };
+            (0..RING_SEGMENTS)
+                .map(|segment| (ring_point(segment), ring_point(segment + 1)))
+                .collect()
+        }
+        Mode::Scale => {
+            let tip = Aabb {
+                min: point(unit - TIP_SIZE * Vector3::new(1.0, 1.0, 1.0)),
+                max: point(unit + TIP_SIZE * Vector3::new(1.0, 1.0, 1.0)),
+            };
+            std::iter::once((
+                point(Vector3::new(0.0, 0.0, 0.0)),
+                point((1.0 - TIP_SIZE) * unit),
+            ))
+            .chain(tip.as_cuboid().wireframe_mesh())
+            .collect()
+        }
+    }
+}
+
+/// Where a mode's handles are drawn.
+#[derive(Debug, Clone, Copy)]
+pub struct Gizmo {
+    pub mode: Mode,
+    pub origin: Point3,
+
+    /// The length of the handles in world space.
+    pub size: f32,
+}
+
+impl Gizmo {
+    /// Handles at `origin`, sized so that they cover the same part of the viewport wherever they are.
+    pub fn new(mode: Mode, camera: &Camera, origin: Point3) -> Self {
+        let distance = (origin - camera.eye).length();
+        Self {
+            mode,
+            origin,
+            size: 2.0 * distance * (camera.fovy / 2.0).to_radians().tan() * SCREEN_SIZE,
+        }
+    }
+
+    /// Takes [`lines`] to world space.
+    pub fn model_matrix(&self) -> Matrix4 {
+        (cgmath::Matrix4::from_translation(cgmath::Point3::from(self.origin).to_vec())
+            * cgmath::Matrix4::from_scale(self.size))
+        .into()
+    }
+
+    /**
+    The axis of the handle under the cursor, if there is one.
+
+    `viewport` is the size of the viewport in pixels, and `cursor` is measured in pixels from its
+    top left corner.
+    */
+    pub fn grab(&self, camera: &Camera, viewport: [f32; 2], cursor: [f32; 2]) -> Option<Axis> {
+        let origin = cgmath::Point3::from(self.origin).to_vec();
+        let cursor = Vector2::from(cursor);
+        Axis::ALL
+            .into_iter()
+            .filter_map(|axis| {
+                let distance = handle_lines(self.mode, axis)
+                    .into_iter()
+                    .filter_map(|(from, to)| {
+                        let to_screen = |point: Point3| {
+                            to_screen(
+                                camera,
+                                viewport,
+                                origin + self.size * cgmath::Point3::from(point).to_vec(),
+                            )
+                        };
+                        Some(distance_to_segment(
+                            cursor,
+                            to_screen(from)?,
+                            to_screen(to)?,
+                        ))
+                    })
+                    .fold(f32::INFINITY, f32::min);
+                (distance <= GRAB_DISTANCE).then_some((axis, distance))
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(axis, _)| axis)
+    }
+}
+
+/// A handle that's being dragged.
+pub struct Drag {
+    pub axis: Axis,
+
+    /// The handles as they were when the drag started.
+    gizmo: Gizmo,
+
+    /// The world transform of the object's parent. Moves along the world's axes are made in the
+    /// parent's space, where the object's translation is.
+    parent_transform: Matrix4,
+
+    start_transform: Transform,
+    start_cursor: [f32; 2],
+
+    /// How far a rotate handle has been dragged around, in degrees, as of `last_cursor`.
+    rotation: f32,
+    last_cursor: [f32; 2],
+}
+
+impl Drag {
+    pub fn new(
+        gizmo: Gizmo,
+        axis: Axis,
+        parent_transform: Matrix4,
+        start_transform: Transform,
+        start_cursor: [f32; 2],
+    ) -> Self {
+        Self {
+            axis,
+            gizmo,
+            parent_transform,
+            start_transform,
+            start_cursor,
+            rotation: 0.0,
+            last_cursor: start_cursor,
+        }
+    }
+
+    /**
+    The object's transform once the handle has been dragged to `cursor`.
+
+    Translate and scale handles follow the cursor along the handle's axis on screen. Rotate handles
+    follow the cursor's angle around the handles' origin, and change the rotation around that axis.
+    */
+    pub fn drag_to(&mut self, camera: &Camera, viewport: [f32; 2], cursor: [f32; 2]) -> Transform {
+        let mut transform = self.start_transform;
+        let origin = cgmath::Point3::from(self.gizmo.origin).to_vec();
+        let Some(screen_origin) = to_screen(camera, viewport, origin) else {
+            return transform;
+        };
+
+        match self.gizmo.mode {
+            Mode::Translate | Mode::Scale => {
+                let Some(screen_end) = to_screen(
+                    camera,
+                    viewport,
+                    origin + self.gizmo.size * self.axis.unit(),
+                ) else {
+                    return transform;
+                };
+                let screen_axis = screen_end - screen_origin;
+                if screen_axis.magnitude2() < 1e-6 {
+                    // The axis points at the camera, so the cursor can't move along it.
+                    return transform;
+                }
+
+                // How far the cursor moved along the handle, in handle lengths.
+                let amount = (Vector2::from(cursor) - Vector2::from(self.start_cursor))
+                    .dot(screen_axis)
+                    / screen_axis.magnitude2();
+
+                if self.gizmo.mode == Mode::Translate {
+                    let movement = cgmath::Transform::transform_vector(
+                        &cgmath::Matrix4::from(self.parent_transform.inverse()),
+                        amount * self.gizmo.size * self.axis.unit(),
+                    );
+                    transform.translation += Vec3::from(movement);
+                } else {
+                    let scale = self.axis.component(&mut transform.scale);
+                    *scale = (*scale * (1.0 + amount)).max(MIN_SCALE);
+                }
+            }
+            Mode::Rotate => {
+                // Measured counterclockwise, with Y going up the screen.
+                let angle = |cursor: [f32; 2]| {
+                    let offset = Vector2::from(cursor) - screen_origin;
+                    (-offset.y).atan2(offset.x).to_degrees()
+                };
+                let turn =
+                    (angle(cursor) - angle(self.last_cursor) + 180.0).rem_euclid(360.0) - 180.0;
+                self.rotation += turn;
+                self.last_cursor = cursor;
+
+                // Turning counterclockwise is a positive rotation when the axis points at the camera.
+                let towards_camera = self
+                    .axis
+                    .unit()
+                    .dot(cgmath::Point3::from(camera.eye).to_vec() - origin)
+                    >= 0.0;
+                *self.axis.component(&mut transform.rotation) += if towards_camera {
+                    self.rotation
+                } else {
+                    -self.rotation
+                };
+            }
+        }
+        transform
+    }
+}
+
+/// Where a world space point is in the viewport, in pixels from its top left corner. `None` when
+/// the point is behind the camera.
+fn to_screen(camera: &Camera, viewport: [f32; 2], point: Vector3<f32>) -> Option<Vector2<f32>> {
+    let clip = cgmath::Matrix4::from(camera.clip_coordinates_matrix()) * point.extend(1.0);
+    if clip.w <= 0.0 {
+        return None;
+    }
+    Some(Vector2::new(
+        (clip.x / clip.w + 1.0) / 2.0 * viewport[0],
+        (1.0 - clip.y / clip.w) / 2.0 * viewport[1],
+    ))
+}
+
+fn distance_to_segment(point: Vector2<f32>, from: Vector2<f32>, to: Vector2<f32>) -> f32 {
+    let segment = to - from;
+    let along = if segment.magnitude2() > 0.0 {
+        ((point - from).dot(segment) / segment.magnitude2()).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (from + along * segment - point).magnitude()
+}
+
+#[test]
+fn test_gizmo_grab() {
+    let camera = Camera {
+        eye: Point3 {
+            x: 0.0,
+            y: 0.0,
+            z: 5.0,
+        },
+        direction: -Vector3::unit_z(),
+        up: Vector3::unit_y(),
+        aspect: 1.0,
+        fovy: 90.0,
+        near: 0.1,
+        far: 100.0,
+    };
+    let viewport = [100.0, 100.0];
+    let gizmo = Gizmo::new(Mode::Translate, &camera, Point3::ZERO);
+
+    // The handles cover 15% of the viewport's height, so the X handle's tip is at (65, 50).
+    assert!((gizmo.size - 1.5).abs() < 1e-5, "{}", gizmo.size);
+    assert_eq!(gizmo.grab(&camera, viewport, [60.0, 51.0]), Some(Axis::X));
+    assert_eq!(gizmo.grab(&camera, viewport, [49.0, 40.0]), Some(Axis::Y));
+    assert_eq!(gizmo.grab(&camera, viewport, [90.0, 90.0]), None);
+
+    // The Z axis points at the camera, so its ring is the one that's seen face on.
+    let gizmo = Gizmo::new(Mode::Rotate, &camera, Point3::ZERO);
+    assert_eq!(gizmo.grab(&camera, viewport, [61.0, 39.0]), Some(Axis::Z));
+    assert_eq!(gizmo.grab(&camera, viewport, [90.0, 90.0]), None);
+}
+
+#[test]
+fn test_gizmo_drag() {
+    let camera = Camera {
+        eye: Point3 {
+            x: 0.0,
+            y: 0.0,
+            z: 5.0,
+        },
+        direction: -Vector3::unit_z(),
+        up: Vector3::unit_y(),
+        aspect: 1.0,
+        fovy: 90.0,
+        near: 0.1,
+        far: 100.0,
+    };
+    let viewport = [100.0, 100.0];
+    let drag = |mode, axis, parent_transform, from, to| {
+        let gizmo = Gizmo::new(mode, &camera, Point3::ZERO);
+        Drag::new(gizmo, axis, parent_transform, Transform::default(), from)
+            .drag_to(&camera, viewport, to)
+    };
+    let close = |a: f32, b: f32| (a - b).abs() < 1e-4;
+
+    // The X handle is 15 pixels long, and 1.5 units long in the world.
+    let transform = drag(
+        Mode::Translate,
+        Axis::X,
+        Matrix4::IDENTITY,
+        [60.0, 50.0],
+        [65.0, 45.0],
+    );
+    assert!(close(transform.translation.x, 0.5), "{:?}", transform);
+    assert!(close(transform.translation.y, 0.0), "{:?}", transform);
+
+    // Translations are in the parent's space.
+    let transform = drag(
+        Mode::Translate,
+        Axis::X,
+        cgmath::Matrix4::from_scale(2.0).into(),
+        [60.0, 50.0],
+        [65.0, 50.0],
+    );
+    assert!(close(transform.translation.x, 0.25), "{:?}", transform);
+
+    let transform = drag(
+        Mode::Scale,
+        Axis::X,
+        Matrix4::IDENTITY,
+        [65.0, 50.0],
+        [80.0, 50.0],
+    );
+    assert!(close(transform.scale.x, 2.0), "{:?}", transform);
+    assert!(close(transform.scale.y, 1.0), "{:?}", transform);
+    let transform = drag(
+        Mode::Scale,
+        Axis::Y,
+        Matrix4::IDENTITY,
+        [50.0, 35.0],
+        [50.0, 80.0],
+    );
+    assert!(close(transform.scale.y, MIN_SCALE), "{:?}", transform);
+
+    // A quarter turn counterclockwise around the Z axis, which points at the camera.
+    let transform = drag(
+        Mode::Rotate,
+        Axis::Z,
+        Matrix4::IDENTITY,
+        [65.0, 50.0],
+        [50.0, 35.0],
+    );
+    assert!(close(transform.rotation.z, 90.0), "{:?}", transform);
+
+    // Turning further than half way round keeps adding up.
+    let gizmo = Gizmo::new(Mode::Rotate, &camera, Point3::ZERO);
+    let mut drag = Drag::new(
+        gizmo,
+        Axis::Z,
+        Matrix4::IDENTITY,
+        Transform::default(),
+        [65.0, 50.0],
+    );
+    for cursor in [[50.0, 35.0], [35.0, 50.0], [50.0, 65.0]] {
+        drag.drag_to(&camera, viewport, cursor);
+    }
+    let transform = drag.drag_to(&camera, viewport, [65.0, 50.0]);
+    assert!(close(transform.rotation.z, 360.0), "{:?}", transform);
+}
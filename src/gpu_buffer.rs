@@ -1,9 +1,375 @@
+use std::ops::Range;
+
 use wgpu::util::DeviceExt;
 
+use crate::error::CapacityError;
+
+/**
+The number of bits of a generational id that hold an element's index. The remaining (high) bits hold
+the generation of the element's slot.
+
+Ids like [`ModelMatrixId`](crate::model_matrices::ModelMatrixId) are packed into a single `u32`
+so that they can be stored in vertices, which means that shaders have to mask off the generation
+(`id & 0xffffffu`) before using an id as an index.
+*/
+pub const INDEX_BITS: u32 = 24;
+
+const INDEX_MASK: u32 = (1 << INDEX_BITS) - 1;
+
+/// The largest index is reserved, so that `u32::MAX` is never a valid id.
+pub fn pack_id(index: u32, generation: u8) -> u32 {
+    assert!(index < INDEX_MASK, "index {} doesn't fit in an id", index);
+    (generation as u32) << INDEX_BITS | index
+}
+
+/// The index and generation of a packed id.
+pub fn unpack_id(id: u32) -> (u32, u8) {
+    (id & INDEX_MASK, (id >> INDEX_BITS) as u8)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Slot {
+    /**
+    Increases every time the slot is reused, so that ids of removed elements can be told apart from
+    ids of the elements that reuse the slot. A slot is retired when its generation can't increase
+    any more, instead of wrapping around, so that an old id never refers to a new element.
+    */
+    generation: u8,
+    removed: bool,
+}
+
+/// Tracks which of a buffer's slots are in use.
+#[derive(Debug, Default)]
+struct Slots {
+    /// Every slot that's been used, including the removed ones.
+    slots: Vec<Slot>,
+
+    /// The removed slots, which are reused (most recently removed first) before new slots are used.
+    free: Vec<u32>,
+}
+
+impl Slots {
+    fn full(len: u32) -> Self {
+        Slots {
+            slots: vec![
+                Slot {
+                    generation: 0,
+                    removed: false,
+                };
+                len as usize
+            ],
+            free: Vec::new(),
+        }
+    }
+
+    fn len(&self) -> u32 {
+        self.slots.len() as u32
+    }
+
+    /// Returns the index of a removed slot, or `None` when none are free.
+    fn reuse(&mut self) -> Option<u32> {
+        let index = self.free.pop()?;
+        let slot = &mut self.slots[index as usize];
+        slot.removed = false;
+        slot.generation += 1;
+        Some(index)
+    }
+
+    fn push(&mut self) -> u32 {
+        self.slots.push(Slot {
+            generation: 0,
+            removed: false,
+        });
+        self.len() - 1
+    }
+
+    /**
+    Returns the first of `len` consecutive removed slots that have the same generation, or `None`
+    when there aren't any.
+    */
+    fn reuse_run(&mut self, len: u32) -> Option<u32> {
+        let mut first = 0;
+        let mut run = None;
+        for index in 0..self.len() {
+            let slot = self.slots[index as usize];
+            if !slot.removed || slot.generation == u8::MAX {
+                first = index + 1;
+                continue;
+            }
+            if slot.generation != self.slots[first as usize].generation {
+                first = index;
+            }
+            if index + 1 - first == len {
+                run = Some(first);
+                break;
+            }
+        }
+
+        let first = run?;
+        let run = first..first + len;
+        for index in run.clone() {
+            let slot = &mut self.slots[index as usize];
+            slot.removed = false;
+            slot.generation += 1;
+        }
+        self.free.retain(|index| !run.contains(index));
+        Some(first)
+    }
+
+    /// Returns the index of the first of `len` new slots.
+    fn push_run(&mut self, len: u32) -> u32 {
+        let first = self.len();
+        self.slots.resize(
+            (first + len) as usize,
+            Slot {
+                generation: 0,
+                removed: false,
+            },
+        );
+        first
+    }
+
+    fn contains(&self, index: u32) -> bool {
+        self.slots
+            .get(index as usize)
+            .is_some_and(|slot| !slot.removed)
+    }
+
+    fn generation(&self, index: u32) -> u8 {
+        self.slots[index as usize].generation
+    }
+
+    fn remove(&mut self, index: u32) {
+        assert!(
+            self.contains(index),
+            "slot {} isn't in use, so it can't be removed",
+            index
+        );
+        let slot = &mut self.slots[index as usize];
+        slot.removed = true;
+        if slot.generation < u8::MAX {
+            self.free.push(index);
+        }
+    }
+}
+
+/// A range of elements that a buffer's `compact` moved to `to..to + (from.end - from.start)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Relocation {
+    pub from: Range<u64>,
+    pub to: u64,
+}
+
+impl Relocation {
+    /// Where the element at `position` is after `relocations` (which are sorted, like the ones
+    /// that `compact` returns). Elements that aren't in any of them didn't move.
+    pub fn apply(relocations: &[Relocation], position: u64) -> u64 {
+        let index = relocations.partition_point(|relocation| relocation.from.end <= position);
+        match relocations.get(index) {
+            Some(relocation) if relocation.from.start <= position => {
+                position - relocation.from.start + relocation.to
+            }
+            _ => position,
+        }
+    }
+}
+
+/**
+Tracks which elements of a buffer are in use, for buffers whose elements are inserted and removed in
+ranges, like the [`VertexBuffer`](crate::vertex_buffer::VertexBuffer) and
+[`IndexBuffer`](crate::index_buffer::IndexBuffer).
+*/
+#[derive(Debug, Default)]
+pub(crate) struct Ranges {
+    /// One past the last element that's in use.
+    len: u64,
+
+    /// Ranges below `len` whose elements were removed, sorted and non-adjacent.
+    free: Vec<Range<u64>>,
+}
+
+impl Ranges {
+    pub(crate) fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Take `len` elements from the first removed range that's big enough, and return where they
+    /// start, or `None` when none are.
+    pub(crate) fn reuse(&mut self, len: u64) -> Option<u64> {
+        let index = self
+            .free
+            .iter()
+            .position(|range| range.end - range.start >= len)?;
+        let start = self.free[index].start;
+        self.free[index].start += len;
+        if self.free[index].is_empty() {
+            self.free.remove(index);
+        }
+        Some(start)
+    }
+
+    /// Use `len` elements after the last one that's in use, and return where they start.
+    pub(crate) fn push(&mut self, len: u64) -> u64 {
+        self.len += len;
+        self.len - len
+    }
+
+    /// Free a range, merging it with its neighbours. A range at the end goes back to being unused.
+    pub(crate) fn remove(&mut self, range: Range<u64>) {
+        assert!(
+            range.end <= self.len,
+            "elements {:?} are past the end of the buffer ({})",
+            range,
+            self.len
+        );
+        if range.is_empty() {
+            return;
+        }
+
+        let free = &mut self.free;
+        let index = free.partition_point(|free_range| free_range.end <= range.start);
+        assert!(
+            free.get(index).is_none_or(|next| range.end <= next.start),
+            "elements {:?} were already removed",
+            range
+        );
+
+        let merges_previous = index > 0 && free[index - 1].end == range.start;
+        let merges_next = free.get(index).is_some_and(|next| next.start == range.end);
+        match (merges_previous, merges_next) {
+            (true, true) => {
+                free[index - 1].end = free[index].end;
+                free.remove(index);
+            }
+            (true, false) => free[index - 1].end = range.end,
+            (false, true) => free[index].start = range.start,
+            (false, false) => free.insert(index, range),
+        }
+
+        if let Some(last) = self.free.last() {
+            if last.end == self.len {
+                self.len = last.start;
+                self.free.pop();
+            }
+        }
+    }
+
+    /// Whether any elements before [`Ranges::len`] were removed.
+    pub(crate) fn has_removed(&self) -> bool {
+        !self.free.is_empty()
+    }
+
+    /**
+    Pack the ranges that are in use together at the start of the buffer, forgetting the removed
+    ones. Returns where each range that's in use moves to, including the ones that don't move.
+    */
+    pub(crate) fn compact(&mut self) -> Vec<Relocation> {
+        let mut relocations = Vec::new();
+        let mut from = 0;
+        let mut to = 0;
+        for free_range in self.free.iter().chain([&(self.len..self.len)]) {
+            if from < free_range.start {
+                relocations.push(Relocation {
+                    from: from..free_range.start,
+                    to,
+                });
+                to += free_range.start - from;
+            }
+            from = free_range.end;
+        }
+
+        self.len = to;
+        self.free.clear();
+        relocations
+    }
+}
+
+/**
+The capacity that a buffer needs to grow to so that it can hold `required` elements: at least double
+its current capacity, but no more than `max`. Returns `None` when `required` is more than `max`.
+*/
+pub(crate) fn grown_capacity(capacity: u64, required: u64, max: u64) -> Option<u64> {
+    if required > max {
+        return None;
+    }
+    let mut grown = capacity.max(1);
+    while grown < required {
+        grown *= 2;
+    }
+    Some(grown.max(capacity * 2).min(max))
+}
+
+/// The largest number of elements of `element_size` bytes that the device allows in a buffer that's
+/// used with `usage`.
+pub(crate) fn max_capacity(
+    device: &wgpu::Device,
+    usage: wgpu::BufferUsages,
+    element_size: u64,
+) -> u64 {
+    let limits = device.limits();
+    let max_size = if usage.contains(wgpu::BufferUsages::STORAGE) {
+        limits
+            .max_buffer_size
+            .min(limits.max_storage_buffer_binding_size as u64)
+    } else {
+        limits.max_buffer_size
+    };
+    max_size / element_size
+}
+
+/**
+Create a copy of `buffer` with room for `capacity` elements of `element_size` bytes, and copy its
+first `len` elements into the copy.
+
+The copy is submitted straight away. Writes to `buffer` that were queued before the reallocation are
+submitted before the copy, so they're included.
+*/
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn reallocate(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    label: &str,
+    usage: wgpu::BufferUsages,
+    buffer: &wgpu::Buffer,
+    element_size: u64,
+    len: u64,
+    capacity: u64,
+) -> wgpu::Buffer {
+    let reallocated = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size: capacity * element_size,
+        usage: usage | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let mut command_encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("reallocate_command_encoder"),
+    });
+    command_encoder.copy_buffer_to_buffer(buffer, 0, &reallocated, 0, len * element_size);
+    queue.submit(std::iter::once(command_encoder.finish()));
+    reallocated
+}
+
+/**
+An array of `T`s on the GPU.
+
+Elements are inserted into the first free slot: a slot whose element was removed, or the slot after
+the last one that's been used. Removed elements are zeroed, like the buffer's unused capacity, so
+shaders that loop over the whole buffer have to skip zeroed elements either way.
+
+When there are no free slots, an insert replaces the underlying [`wgpu::Buffer`] with one that's
+twice as big. Bind groups that use the old buffer need to be recreated; see
+[`GpuBuffer::take_reallocated`].
+*/
 pub struct GpuBuffer<T> {
+    /// Used to identify the buffer in [`CapacityError`]s.
+    label: String,
+    usage: wgpu::BufferUsages,
     buffer: wgpu::Buffer,
     capacity: u32,
-    size: u32,
+    slots: Slots,
+
+    /// The underlying buffer was replaced since the last [`GpuBuffer::take_reallocated`].
+    reallocated: bool,
     _phantom_data: std::marker::PhantomData<T>,
 }
 
@@ -14,6 +380,8 @@ impl<T: Sized + bytemuck::Pod> GpuBuffer<T> {
         usage: wgpu::BufferUsages,
         capacity: u32,
     ) -> Self {
+        // The buffer is copied from when it's reallocated.
+        let usage = usage | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST;
         let buffer = device.create_buffer(&wgpu::BufferDescriptor {
             label,
             size: capacity as u64 * std::mem::size_of::<T>() as u64,
@@ -21,9 +389,12 @@ impl<T: Sized + bytemuck::Pod> GpuBuffer<T> {
             mapped_at_creation: false,
         });
         Self {
+            label: label.unwrap_or("GpuBuffer").to_string(),
+            usage,
             buffer,
             capacity,
-            size: 0,
+            slots: Slots::default(),
+            reallocated: false,
             _phantom_data: std::marker::PhantomData,
         }
     }
@@ -38,38 +409,91 @@ impl<T: Sized + bytemuck::Pod> GpuBuffer<T> {
         let size = contents.len() as u32;
         assert!(size <= capacity);
 
+        let usage = usage | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST;
         let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label,
             usage,
             contents: bytemuck::cast_slice(contents),
         });
         Self {
+            label: label.unwrap_or("GpuBuffer").to_string(),
+            usage,
             buffer,
-            capacity,
-            size,
+            capacity: size,
+            slots: Slots::full(size),
+            reallocated: false,
             _phantom_data: std::marker::PhantomData,
         }
     }
 
-    pub fn insert(&mut self, queue: &wgpu::Queue, data: T) -> u32 {
-        assert!(self.size < self.capacity);
-
-        let next_free_index = self.size;
+    /**
+    Insert an element into the first free slot, and return the slot's index.
 
-        let next_size = self.size + 1;
-        self.size = next_size;
+    Grows the buffer when it's full. Fails when the device doesn't allow a buffer that big.
+    */
+    pub fn insert(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        data: T,
+    ) -> Result<u32, CapacityError> {
+        let index = match self.slots.reuse() {
+            Some(index) => index,
+            None => {
+                if self.slots.len() >= self.capacity {
+                    self.grow(device, queue, self.slots.len() + 1)?;
+                }
+                self.slots.push()
+            }
+        };
 
         queue.write_buffer(
             &self.buffer,
-            next_free_index as u64 * std::mem::size_of::<T>() as u64,
+            index as u64 * std::mem::size_of::<T>() as u64,
             bytemuck::cast_slice(&[data]),
         );
 
-        next_free_index
+        Ok(index)
+    }
+
+    /**
+    Insert elements into consecutive slots that have the same generation, and return the first
+    slot's index. Removed slots are only reused when enough of them are next to each other.
+
+    Grows the buffer when there isn't room at the end. Fails when the device doesn't allow a buffer
+    that big.
+    */
+    pub fn insert_many(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        data: &[T],
+    ) -> Result<u32, CapacityError> {
+        let len = data.len() as u32;
+        let index = match self.slots.reuse_run(len) {
+            Some(index) => index,
+            None => {
+                let required = self.slots.len() + len;
+                if required > self.capacity {
+                    self.grow(device, queue, required)?;
+                }
+                self.slots.push_run(len)
+            }
+        };
+
+        if !data.is_empty() {
+            queue.write_buffer(
+                &self.buffer,
+                index as u64 * std::mem::size_of::<T>() as u64,
+                bytemuck::cast_slice(data),
+            );
+        }
+
+        Ok(index)
     }
 
     pub fn update(&mut self, queue: &wgpu::Queue, index: u32, data: T) {
-        assert!(index < self.size);
+        assert!(self.slots.contains(index));
 
         queue.write_buffer(
             &self.buffer,
@@ -79,7 +503,7 @@ impl<T: Sized + bytemuck::Pod> GpuBuffer<T> {
     }
 
     pub fn update_slice(&mut self, queue: &wgpu::Queue, index: u32, data: &[T]) {
-        assert!(index + (data.len() as u32) <= self.size);
+        assert!((index..index + data.len() as u32).all(|index| self.slots.contains(index)));
         queue.write_buffer(
             &self.buffer,
             index as u64 * std::mem::size_of::<T>() as u64,
@@ -87,19 +511,290 @@ impl<T: Sized + bytemuck::Pod> GpuBuffer<T> {
         );
     }
 
-    pub fn remove(&mut self, _index: u32) {
-        todo!()
+    /// Replace the underlying buffer with one that can hold at least `required` elements.
+    fn grow(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        required: u32,
+    ) -> Result<(), CapacityError> {
+        let element_size = std::mem::size_of::<T>() as u64;
+        let max = max_capacity(device, self.usage, element_size).min(u32::MAX as u64);
+        let capacity =
+            grown_capacity(self.capacity as u64, required as u64, max).ok_or(CapacityError {
+                buffer: self.label.clone(),
+                requested: (required - self.slots.len()) as u64,
+                available: max.saturating_sub(self.slots.len() as u64),
+            })?;
+        log::debug!(
+            "Growing {} from {} to {} elements",
+            self.label,
+            self.capacity,
+            capacity
+        );
+
+        self.buffer = reallocate(
+            device,
+            queue,
+            &self.label,
+            self.usage,
+            &self.buffer,
+            element_size,
+            self.slots.len() as u64,
+            capacity,
+        );
+        self.capacity = capacity as u32;
+        self.reallocated = true;
+        Ok(())
+    }
+
+    /**
+    Whether the underlying buffer was replaced since the last call, e.g. because the buffer grew.
+
+    Bind groups that use the buffer need to be recreated when it's replaced, because they still refer
+    to the old buffer.
+    */
+    pub fn take_reallocated(&mut self) -> bool {
+        std::mem::take(&mut self.reallocated)
+    }
+
+    /// Zero the element, and free its slot so that it can be reused by a later insert.
+    pub fn remove(&mut self, queue: &wgpu::Queue, index: u32) {
+        self.slots.remove(index);
+        queue.write_buffer(
+            &self.buffer,
+            index as u64 * std::mem::size_of::<T>() as u64,
+            bytemuck::cast_slice(&[T::zeroed()]),
+        );
+    }
+
+    /// Whether the slot holds an element, i.e. it's been inserted into and not removed since.
+    pub fn contains(&self, index: u32) -> bool {
+        self.slots.contains(index)
+    }
+
+    /// The slot's generation, which changes every time the slot is reused. See [`pack_id`].
+    pub fn generation(&self, index: u32) -> u8 {
+        self.slots.generation(index)
     }
 
     pub fn as_raw_buffer(&self) -> &wgpu::Buffer {
         &self.buffer
     }
 
+    /// The number of slots that have been used, including the ones whose elements were removed.
     pub fn len(&self) -> u32 {
-        self.size
+        self.slots.len()
     }
 
     pub fn is_empty(&self) -> bool {
-        self.size == 0
+        self.slots.len() == 0
+    }
+}
+
+#[test]
+fn test_slots() {
+    let mut slots = Slots::default();
+    assert_eq!([slots.push(), slots.push(), slots.push()], [0, 1, 2]);
+    assert_eq!(slots.reuse(), None);
+
+    slots.remove(1);
+    slots.remove(0);
+    assert!(!slots.contains(1));
+    assert_eq!(slots.len(), 3);
+
+    // The most recently removed slot is reused first, with a new generation.
+    assert_eq!(slots.reuse(), Some(0));
+    assert_eq!(slots.reuse(), Some(1));
+    assert_eq!(slots.reuse(), None);
+    assert!(slots.contains(1));
+    assert_eq!(slots.generation(1), 1);
+    assert_eq!(slots.generation(2), 0);
+}
+
+#[test]
+fn test_slot_runs() {
+    let mut slots = Slots::default();
+    assert_eq!(slots.push_run(3), 0);
+    assert_eq!(slots.push_run(2), 3);
+    assert_eq!(slots.len(), 5);
+
+    // Slots 0 and 2 aren't next to each other.
+    slots.remove(0);
+    slots.remove(2);
+    assert_eq!(slots.reuse_run(2), None);
+
+    slots.remove(1);
+    assert_eq!(slots.reuse_run(3), Some(0));
+    assert!((0..3).all(|index| slots.contains(index) && slots.generation(index) == 1));
+    assert_eq!(
+        slots.reuse(),
+        None,
+        "the reused slots should no longer be free"
+    );
+
+    // Slots 3 and 4 are next to each other, but have different generations.
+    slots.remove(3);
+    slots.reuse();
+    slots.remove(3);
+    slots.remove(4);
+    assert_eq!(slots.reuse_run(2), None);
+    assert_eq!(slots.reuse_run(1), Some(3));
+    assert_eq!(slots.reuse(), Some(4));
+}
+
+#[test]
+fn test_slot_retirement() {
+    let mut slots = Slots::default();
+    let index = slots.push();
+    for generation in 1..=u8::MAX {
+        slots.remove(index);
+        assert_eq!(slots.reuse(), Some(index));
+        assert_eq!(slots.generation(index), generation);
+    }
+
+    // The slot's generation can't increase any more, so it isn't reused.
+    slots.remove(index);
+    assert_eq!(slots.reuse(), None);
+    assert_eq!(slots.reuse_run(1), None);
+    assert_eq!(slots.push(), index + 1);
+}
+
+#[test]
+fn test_ranges() {
+    let mut ranges = Ranges::default();
+    assert_eq!(ranges.push(50), 0);
+    ranges.remove(10..20);
+    ranges.remove(30..40);
+    ranges.remove(0..5);
+    assert_eq!(ranges.free, [0..5, 10..20, 30..40]);
+
+    // Adjacent ranges are merged.
+    ranges.remove(20..30);
+    assert_eq!(ranges.free, [0..5, 10..40]);
+    ranges.remove(5..7);
+    assert_eq!(ranges.free, [0..7, 10..40]);
+
+    // The first range that's big enough is used.
+    assert_eq!(ranges.reuse(8), Some(10));
+    assert_eq!(ranges.free, [0..7, 18..40]);
+    assert_eq!(ranges.reuse(5), Some(0));
+    assert_eq!(ranges.free, [5..7, 18..40]);
+    assert_eq!(ranges.reuse(100), None);
+
+    // A removed range at the end is no longer counted.
+    ranges.remove(40..50);
+    assert_eq!(ranges.len(), 18);
+    assert_eq!(ranges.free, vec![5..7]);
+}
+
+#[test]
+fn test_ranges_compact() {
+    let mut ranges = Ranges::default();
+    ranges.push(50);
+    for range in [0..5, 10..20, 30..40] {
+        ranges.remove(range);
     }
+    assert_eq!(
+        ranges.compact(),
+        [
+            Relocation { from: 5..10, to: 0 },
+            Relocation {
+                from: 20..30,
+                to: 5
+            },
+            Relocation {
+                from: 40..50,
+                to: 15
+            },
+        ]
+    );
+    assert_eq!(ranges.len(), 25);
+    assert!(!ranges.has_removed());
+
+    let mut ranges = Ranges::default();
+    ranges.push(40);
+    ranges.remove(10..20);
+    ranges.remove(30..40);
+    assert_eq!(
+        ranges.compact(),
+        [
+            Relocation { from: 0..10, to: 0 },
+            Relocation {
+                from: 20..30,
+                to: 10
+            }
+        ]
+    );
+    assert_eq!(Ranges::default().compact(), []);
+
+    let relocations = [
+        Relocation { from: 5..10, to: 0 },
+        Relocation {
+            from: 20..30,
+            to: 5,
+        },
+    ];
+    assert_eq!(Relocation::apply(&relocations, 7), 2);
+    assert_eq!(Relocation::apply(&relocations, 29), 14);
+    assert_eq!(Relocation::apply(&relocations, 3), 3);
+    assert_eq!(Relocation::apply(&relocations, 30), 30);
+}
+
+#[test]
+fn test_reload_keeps_len() {
+    // What `load::replace_model` does to the buffers' bookkeeping when a model's file is saved
+    // without changes, with another model loaded after it.
+    let mut vertices = Ranges::default();
+    let mut indices = Ranges::default();
+    let mut materials = Slots::default();
+    let insert = |ranges: &mut Ranges, len| ranges.reuse(len).unwrap_or_else(|| ranges.push(len));
+
+    let model_vertices = insert(&mut vertices, 500);
+    let model_indices = insert(&mut indices, 1500);
+    let model_materials = materials.push_run(3);
+    insert(&mut vertices, 200);
+    insert(&mut indices, 600);
+    materials.push_run(2);
+    let lens = (vertices.len(), indices.len(), materials.len());
+
+    for _ in 0..3 {
+        vertices.remove(model_vertices..model_vertices + 500);
+        indices.remove(model_indices..model_indices + 1500);
+        for index in model_materials..model_materials + 3 {
+            materials.remove(index);
+        }
+
+        assert_eq!(insert(&mut vertices, 500), model_vertices);
+        assert_eq!(insert(&mut indices, 1500), model_indices);
+        assert_eq!(materials.reuse_run(3), Some(model_materials));
+        assert_eq!((vertices.len(), indices.len(), materials.len()), lens);
+    }
+}
+
+#[test]
+fn test_grown_capacity() {
+    assert_eq!(grown_capacity(10, 11, 1000), Some(20));
+    assert_eq!(grown_capacity(10, 50, 1000), Some(80));
+    assert_eq!(grown_capacity(0, 1, 1000), Some(1));
+
+    // Buffers don't grow past the maximum...
+    assert_eq!(grown_capacity(600, 601, 1000), Some(1000));
+
+    // ...so they can't hold more than it.
+    assert_eq!(grown_capacity(1000, 1001, 1000), None);
+}
+
+#[test]
+fn test_pack_id() {
+    let id = pack_id(1000, 3);
+    assert_eq!(id & INDEX_MASK, 1000);
+    assert_eq!(unpack_id(id), (1000, 3));
+    assert_eq!(
+        unpack_id(pack_id(INDEX_MASK - 1, 255)),
+        (INDEX_MASK - 1, 255)
+    );
+
+    // Ids of generation 0 are plain indices.
+    assert_eq!(pack_id(7, 0), 7);
 }
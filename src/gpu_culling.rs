@@ -0,0 +1,568 @@
+/*! Frustum culling on the GPU.
+
+Each pass's instances are batched on the CPU as usual (see [`instance::batch`]), but aren't culled
+there. Instead, each instance goes in a [`CullInstance`] with its world-space bounds, and a compute
+shader tests them against their pass's frustum. The visible instances of each draw are written to
+the front of the draw's range of the [`InstanceBuffer`], and counted in the draw's
+[`DrawIndexedIndirect`] arguments. The render passes draw with those arguments (see
+[`Draws::Indirect`]), so the CPU never finds out what was culled.
+
+Indirect draws with a first instance need [`wgpu::Features::INDIRECT_FIRST_INSTANCE`] (see
+[`GpuCulling::FEATURES`]). Without it, objects are culled on the CPU.
+*/
+
+use std::{ops::Range, sync::mpsc};
+
+use wgpu::include_wgsl;
+
+use crate::{
+    aabb::Aabb,
+    error::CapacityError,
+    frustum::Frustum,
+    gpu_buffer,
+    index_buffer::IndexRange,
+    instance::{self, Draws, Instance, InstanceBuffer},
+    point::Point3,
+};
+
+const WORKGROUP_SIZE: u32 = 64;
+
+/// The arguments of one indexed indirect draw, as `draw_indexed_indirect` reads them.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DrawIndexedIndirect {
+    pub index_count: u32,
+
+    /// Zero until the culling shader counts the draw's visible instances.
+    pub instance_count: u32,
+
+    pub first_index: u32,
+    pub base_vertex: i32,
+    pub first_instance: u32,
+}
+
+/// An instance that might be drawn, with the bounds that it's culled with.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct CullInstance {
+    pub aabb_min: Point3,
+
+    /// The index of the instance's draw in [`CullingPasses`].
+    pub draw: u32,
+
+    pub aabb_max: Point3,
+
+    /// The index of the pass whose frustum the instance is culled against.
+    pub frustum: u32,
+
+    pub instance: Instance,
+    pub _padding: u32,
+}
+
+/// The passes of one frame, with the instances that each of them might draw. They're uploaded
+/// with [`GpuCulling::write`].
+#[derive(Debug, Default)]
+pub struct CullingPasses {
+    cull_instances: Vec<CullInstance>,
+    frustums: Vec<[[f32; 4]; 6]>,
+    draws: Vec<DrawIndexedIndirect>,
+}
+
+impl CullingPasses {
+    /**
+    Add a pass that draws the instances whose bounds intersect `frustum`. Returns the pass's range
+    of draws, for [`GpuCulling::draws`].
+
+    The first pass's visible instances are the ones counted by [`GpuCulling::read_objects_drawn`].
+    */
+    pub fn add(
+        &mut self,
+        frustum: &Frustum,
+        instances: impl IntoIterator<Item = (Aabb, IndexRange, Instance)>,
+    ) -> Range<u32> {
+        let pass = self.frustums.len() as u32;
+        self.frustums.push(frustum.plane_equations());
+
+        let draws = instance::batch(
+            instances.into_iter().map(|(aabb, indices, instance)| {
+                (
+                    indices,
+                    CullInstance {
+                        aabb_min: aabb.min,
+                        draw: 0,
+                        aabb_max: aabb.max,
+                        frustum: pass,
+                        instance,
+                        _padding: 0,
+                    },
+                )
+            }),
+            &mut self.cull_instances,
+        );
+        let first_draw = self.draws.len() as u32;
+        for (index, draw) in draws.iter().enumerate() {
+            let range = draw.instances.start as usize..draw.instances.end as usize;
+            for cull_instance in &mut self.cull_instances[range] {
+                cull_instance.draw = first_draw + index as u32;
+            }
+            self.draws.push(DrawIndexedIndirect {
+                index_count: draw.indices.index_count,
+                instance_count: 0,
+                first_index: draw.indices.first_index,
+                base_vertex: 0,
+                first_instance: draw.instances.start,
+            });
+        }
+        first_draw..self.draws.len() as u32
+    }
+}
+
+/// Where the readback of [`GpuCulling::read_objects_drawn`] is up to.
+enum Readback {
+    /// Nothing has been copied to the readback buffer yet, so there's nothing to map. The next
+    /// culling pass copies its count to it.
+    NotCopied,
+
+    /// The readback buffer has a culling pass's count, and the next culling pass copies its count
+    /// to it.
+    Idle,
+
+    /// The readback buffer is being mapped.
+    Mapping(mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>),
+}
+
+pub struct GpuCulling {
+    bind_group_layout_0: wgpu::BindGroupLayout,
+    bind_group_0: wgpu::BindGroup,
+    pipeline: wgpu::ComputePipeline,
+
+    cull_instances: FrameBuffer,
+    frustums: FrameBuffer,
+    draws: FrameBuffer,
+
+    /// The number of [`CullInstance`]s in the last write.
+    cull_instance_count: wgpu::Buffer,
+    len: u32,
+
+    /// The number of the first pass's instances that were visible.
+    objects_drawn: wgpu::Buffer,
+    objects_drawn_readback: wgpu::Buffer,
+    readback: Readback,
+    last_objects_drawn: Option<u32>,
+
+    /// Whether the device has [`wgpu::Features::MULTI_DRAW_INDIRECT`].
+    multi_draw: bool,
+}
+
+impl GpuCulling {
+    /// The features that GPU culling needs. [`wgpu::Features::MULTI_DRAW_INDIRECT`] is used too,
+    /// when it's available.
+    pub const FEATURES: wgpu::Features = wgpu::Features::INDIRECT_FIRST_INSTANCE;
+
+    pub fn new(device: &wgpu::Device, instance_buffer: &InstanceBuffer) -> Self {
+        let storage = |binding, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let bind_group_layout_0 =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("gpu_culling_bind_group_layout_0"),
+                entries: &[
+                    // var<storage, read> cull_instances: array<CullInstance>;
+                    storage(0, true),
+                    // var<storage, read> frustums: array<Frustum>;
+                    storage(1, true),
+                    // var<storage, read_write> draws: array<DrawIndexedIndirect>;
+                    storage(2, false),
+                    // var<storage, read_write> instances: array<Instance>;
+                    storage(3, false),
+                    // var<storage, read_write> objects_drawn: atomic<u32>;
+                    storage(4, false),
+                    // var<uniform> cull_instance_count: u32;
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 5,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("gpu_culling_pipeline_layout"),
+            bind_group_layouts: &[&bind_group_layout_0],
+            push_constant_ranges: &[],
+        });
+        let shader_module = device.create_shader_module(include_wgsl!("gpu_culling.wgsl"));
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("gpu_culling_pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader_module,
+            entry_point: "cull",
+        });
+
+        let cull_instances = FrameBuffer::new::<CullInstance>(
+            device,
+            "gpu_culling_cull_instances",
+            wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            1000,
+        );
+        let frustums = FrameBuffer::new::<[[f32; 4]; 6]>(
+            device,
+            "gpu_culling_frustums",
+            wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            16,
+        );
+        let draws = FrameBuffer::new::<DrawIndexedIndirect>(
+            device,
+            "gpu_culling_draws",
+            wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::INDIRECT
+                | wgpu::BufferUsages::COPY_DST,
+            100,
+        );
+        let cull_instance_count = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("gpu_culling_cull_instance_count"),
+            size: std::mem::size_of::<u32>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let objects_drawn = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("gpu_culling_objects_drawn"),
+            size: std::mem::size_of::<u32>() as u64,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let objects_drawn_readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("gpu_culling_objects_drawn_readback"),
+            size: std::mem::size_of::<u32>() as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group_0 = create_bind_group_0(
+            device,
+            &bind_group_layout_0,
+            [
+                &cull_instances.buffer,
+                &frustums.buffer,
+                &draws.buffer,
+                instance_buffer.as_raw_buffer(),
+                &objects_drawn,
+                &cull_instance_count,
+            ],
+        );
+
+        Self {
+            bind_group_layout_0,
+            bind_group_0,
+            pipeline,
+            cull_instances,
+            frustums,
+            draws,
+            cull_instance_count,
+            len: 0,
+            objects_drawn,
+            objects_drawn_readback,
+            readback: Readback::NotCopied,
+            last_objects_drawn: None,
+            multi_draw: device
+                .features()
+                .contains(wgpu::Features::MULTI_DRAW_INDIRECT),
+        }
+    }
+
+    /**
+    Upload a frame's passes, replacing the last frame's.
+
+    The passes' instances are written to `instance_buffer` unculled, in the order that
+    [`GpuCulling::record`] culls them in. The buffers grow when they're too small, and this fails
+    when the device doesn't allow a buffer that big.
+    */
+    pub fn write(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        instance_buffer: &mut InstanceBuffer,
+        passes: &CullingPasses,
+    ) -> Result<(), CapacityError> {
+        let instances: Vec<Instance> = passes
+            .cull_instances
+            .iter()
+            .map(|cull_instance| cull_instance.instance)
+            .collect();
+        instance_buffer.write(device, queue, &instances)?;
+
+        let mut reallocated = instance_buffer.take_reallocated();
+        reallocated |= self
+            .cull_instances
+            .write(device, queue, &passes.cull_instances)?;
+        reallocated |= self.frustums.write(device, queue, &passes.frustums)?;
+        reallocated |= self.draws.write(device, queue, &passes.draws)?;
+        if reallocated {
+            self.bind_group_0 = create_bind_group_0(
+                device,
+                &self.bind_group_layout_0,
+                [
+                    &self.cull_instances.buffer,
+                    &self.frustums.buffer,
+                    &self.draws.buffer,
+                    instance_buffer.as_raw_buffer(),
+                    &self.objects_drawn,
+                    &self.cull_instance_count,
+                ],
+            );
+        }
+
+        self.len = passes.cull_instances.len() as u32;
+        queue.write_buffer(&self.cull_instance_count, 0, bytemuck::bytes_of(&self.len));
+        queue.write_buffer(&self.objects_drawn, 0, bytemuck::bytes_of(&0u32));
+        Ok(())
+    }
+
+    /// Cull the last write's instances. Record this before the passes that use
+    /// [`GpuCulling::draws`].
+    pub fn record(&self, command_encoder: &mut wgpu::CommandEncoder) {
+        if self.len > 0 {
+            let mut compute_pass =
+                command_encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some("gpu_culling_pass"),
+                });
+            compute_pass.set_pipeline(&self.pipeline);
+            compute_pass.set_bind_group(0, &self.bind_group_0, &[]);
+            compute_pass.dispatch_workgroups(self.len.div_ceil(WORKGROUP_SIZE), 1, 1);
+        }
+
+        if let Readback::NotCopied | Readback::Idle = self.readback {
+            command_encoder.copy_buffer_to_buffer(
+                &self.objects_drawn,
+                0,
+                &self.objects_drawn_readback,
+                0,
+                std::mem::size_of::<u32>() as u64,
+            );
+        }
+    }
+
+    /// A pass's draws (see [`CullingPasses::add`]), as culled by the last [`GpuCulling::record`].
+    pub fn draws(&self, range: Range<u32>) -> Draws<'_> {
+        Draws::Indirect {
+            buffer: &self.draws.buffer,
+            range,
+            multi_draw: self.multi_draw,
+        }
+    }
+
+    /**
+    The number of the first pass's instances that were visible. Call this once a frame, before
+    [`GpuCulling::record`].
+
+    The count is read back without waiting for the GPU, so it's from a recent frame rather than the
+    last one, and it's `None` until the first count has been read.
+    */
+    pub fn read_objects_drawn(&mut self, device: &wgpu::Device) -> Option<u32> {
+        match &self.readback {
+            // The culling pass that's recorded after this copies the first count.
+            Readback::NotCopied => self.readback = Readback::Idle,
+            Readback::Idle => {
+                let (sender, receiver) = mpsc::channel();
+                self.objects_drawn_readback.slice(..).map_async(
+                    wgpu::MapMode::Read,
+                    move |result| {
+                        let _ = sender.send(result);
+                    },
+                );
+                self.readback = Readback::Mapping(receiver);
+            }
+            Readback::Mapping(receiver) => {
+                device.poll(wgpu::Maintain::Poll);
+                match receiver.try_recv() {
+                    Ok(Ok(())) => {
+                        let objects_drawn = *bytemuck::from_bytes::<u32>(
+                            &self.objects_drawn_readback.slice(..).get_mapped_range(),
+                        );
+                        self.objects_drawn_readback.unmap();
+                        self.last_objects_drawn = Some(objects_drawn);
+                        self.readback = Readback::Idle;
+                    }
+                    Ok(Err(error)) => {
+                        log::error!("failed to read the number of objects drawn: {}", error);
+                        self.readback = Readback::Idle;
+                    }
+                    Err(mpsc::TryRecvError::Empty) => {}
+                    Err(mpsc::TryRecvError::Disconnected) => {
+                        panic!("map_async callback was dropped without being called")
+                    }
+                }
+            }
+        }
+        self.last_objects_drawn
+    }
+}
+
+fn create_bind_group_0(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    buffers: [&wgpu::Buffer; 6],
+) -> wgpu::BindGroup {
+    let entries: Vec<wgpu::BindGroupEntry> = buffers
+        .iter()
+        .enumerate()
+        .map(|(binding, buffer)| wgpu::BindGroupEntry {
+            binding: binding as u32,
+            resource: buffer.as_entire_binding(),
+        })
+        .collect();
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("gpu_culling_bind_group_0"),
+        layout,
+        entries: &entries,
+    })
+}
+
+/// A buffer whose contents are replaced every frame. Like [`InstanceBuffer`], it grows without
+/// copying its old contents.
+struct FrameBuffer {
+    label: &'static str,
+    usage: wgpu::BufferUsages,
+    element_size: u64,
+    buffer: wgpu::Buffer,
+
+    /// Maximum number of elements that can be stored before the buffer grows.
+    capacity: u64,
+}
+
+impl FrameBuffer {
+    fn new<T>(
+        device: &wgpu::Device,
+        label: &'static str,
+        usage: wgpu::BufferUsages,
+        capacity: u64,
+    ) -> Self {
+        let element_size = std::mem::size_of::<T>() as u64;
+        FrameBuffer {
+            label,
+            usage,
+            element_size,
+            buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size: capacity * element_size,
+                usage,
+                mapped_at_creation: false,
+            }),
+            capacity,
+        }
+    }
+
+    /// Replace the buffer's contents, and return whether the buffer had to grow.
+    fn write<T: bytemuck::Pod>(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        data: &[T],
+    ) -> Result<bool, CapacityError> {
+        let required = data.len() as u64;
+        let reallocated = required > self.capacity;
+        if reallocated {
+            let max = gpu_buffer::max_capacity(device, self.usage, self.element_size);
+            let capacity =
+                gpu_buffer::grown_capacity(self.capacity, required, max).ok_or(CapacityError {
+                    buffer: String::from(self.label),
+                    requested: required,
+                    available: max,
+                })?;
+            log::debug!(
+                "Growing {} from {} to {} elements",
+                self.label,
+                self.capacity,
+                capacity
+            );
+
+            self.buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(self.label),
+                size: capacity * self.element_size,
+                usage: self.usage,
+                mapped_at_creation: false,
+            });
+            self.capacity = capacity;
+        }
+
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(data));
+        Ok(reallocated)
+    }
+}
+
+#[test]
+fn test_culling_passes() {
+    use crate::{material::MaterialId, model_matrices::ModelMatrixId};
+
+    let sphere = IndexRange {
+        first_index: 0,
+        index_count: 300,
+    };
+    let cube = IndexRange {
+        first_index: 300,
+        index_count: 36,
+    };
+    let instance = |index| Instance {
+        model_matrix_id: ModelMatrixId::new(index, 0),
+        material_id: MaterialId::new(index, 0),
+        material_count: 1,
+    };
+    let frustum = Frustum::new(crate::matrix::Matrix4::IDENTITY);
+    let objects = [
+        (Aabb::EMPTY, cube, instance(0)),
+        (Aabb::EMPTY, sphere, instance(1)),
+        (Aabb::EMPTY, cube, instance(2)),
+    ];
+
+    let mut passes = CullingPasses::default();
+    assert_eq!(passes.add(&frustum, objects), 0..2);
+    assert_eq!(passes.add(&frustum, objects), 2..4);
+    assert_eq!(passes.add(&frustum, []), 4..4);
+
+    assert_eq!(passes.frustums.len(), 3);
+    assert_eq!(
+        passes.draws,
+        [(sphere, 0), (cube, 1), (sphere, 3), (cube, 4)].map(|(indices, first_instance)| {
+            DrawIndexedIndirect {
+                index_count: indices.index_count,
+                instance_count: 0,
+                first_index: indices.first_index,
+                base_vertex: 0,
+                first_instance,
+            }
+        })
+    );
+    assert_eq!(
+        passes
+            .cull_instances
+            .iter()
+            .map(|cull_instance| (
+                cull_instance.instance,
+                cull_instance.draw,
+                cull_instance.frustum
+            ))
+            .collect::<Vec<_>>(),
+        [
+            (instance(1), 0, 0),
+            (instance(0), 1, 0),
+            (instance(2), 1, 0),
+            (instance(1), 2, 1),
+            (instance(0), 3, 1),
+            (instance(2), 3, 1),
+        ]
+    );
+}
@@ -0,0 +1,91 @@
+struct CullInstance {
+  aabb_min: vec3<f32>,
+  draw: u32,
+  aabb_max: vec3<f32>,
+  frustum: u32,
+  model_matrix_id: u32,
+  material_id: u32,
+  material_count: u32,
+}
+
+/* The planes as `(normal, d)`: a point `p` is on the inside of a plane when
+`dot(normal, p) + d >= 0`.
+*/
+struct Frustum {
+  planes: array<vec4<f32>, 6>,
+}
+
+struct DrawIndexedIndirect {
+  index_count: u32,
+  instance_count: atomic<u32>,
+  first_index: u32,
+  base_vertex: i32,
+  first_instance: u32,
+}
+
+struct Instance {
+  model_matrix_id: u32,
+  material_id: u32,
+  material_count: u32,
+}
+
+@group(0) @binding(0)
+var<storage, read> cull_instances: array<CullInstance>;
+
+@group(0) @binding(1)
+var<storage, read> frustums: array<Frustum>;
+
+@group(0) @binding(2)
+var<storage, read_write> draws: array<DrawIndexedIndirect>;
+
+@group(0) @binding(3)
+var<storage, read_write> instances: array<Instance>;
+
+// The number of the first pass's instances that are visible.
+@group(0) @binding(4)
+var<storage, read_write> objects_drawn: atomic<u32>;
+
+@group(0) @binding(5)
+var<uniform> cull_instance_count: u32;
+
+/* Whether any of the box might be inside one of `frustums`. The box is outside when the corner that's
+furthest along one of the planes' normals is behind that plane (see `Frustum::intersects`).
+*/
+fn intersects(frustum: u32, aabb_min: vec3<f32>, aabb_max: vec3<f32>) -> bool {
+  for (var i = 0u; i < 6u; i++) {
+    let plane = frustums[frustum].planes[i];
+    let furthest = select(aabb_min, aabb_max, plane.xyz >= vec3<f32>(0.0));
+    if dot(plane.xyz, furthest) + plane.w < 0.0 {
+      return false;
+    }
+  }
+  return true;
+}
+
+/* Each invocation culls one instance. A visible instance takes the next slot of its draw's range
+of `instances`, so the visible instances end up at the front of the range, in no particular order.
+*/
+@compute @workgroup_size(64)
+fn cull(@builtin(global_invocation_id) global_id: vec3<u32>) {
+  let index = global_id.x;
+  if index >= cull_instance_count {
+    return;
+  }
+
+  let cull_instance = cull_instances[index];
+  if !intersects(cull_instance.frustum, cull_instance.aabb_min, cull_instance.aabb_max) {
+    return;
+  }
+
+  let slot = atomicAdd(&draws[cull_instance.draw].instance_count, 1u);
+  instances[draws[cull_instance.draw].first_instance + slot] =
+    Instance(
+      cull_instance.model_matrix_id,
+      cull_instance.material_id,
+      cull_instance.material_count
+    );
+
+  if cull_instance.frustum == 0u {
+    atomicAdd(&objects_drawn, 1u);
+  }
+}
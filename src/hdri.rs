@@ -0,0 +1,55 @@
+use std::{fs::File, io::BufReader, path::PathBuf};
+
+use image::codecs::hdr::HdrDecoder;
+
+use crate::error::LoadError;
+
+/// A high dynamic range image, read from a [Radiance HDR](https://en.wikipedia.org/wiki/RGBE_image_format)
+/// file.
+pub struct Hdri {
+    pub width: u32,
+    pub height: u32,
+
+    /// Linear RGBA, row by row. Alpha is always 1.
+    pub pixels: Vec<[f32; 4]>,
+}
+
+impl Hdri {
+    pub fn load(file_name: &str) -> Result<Self, LoadError> {
+        let file = File::open(file_name).map_err(|error| LoadError::Io {
+            path: PathBuf::from(file_name),
+            error,
+        })?;
+
+        let image_error = |error| LoadError::Image {
+            path: PathBuf::from(file_name),
+            error,
+        };
+        let decoder = HdrDecoder::new(BufReader::new(file)).map_err(image_error)?;
+
+        let metadata = decoder.metadata();
+        log::debug!("hdri exposure: {:?}", metadata.exposure);
+
+        let pixels = decoder
+            .read_image_hdr()
+            .map_err(image_error)?
+            .into_iter()
+            .map(|pixel| [pixel.0[0], pixel.0[1], pixel.0[2], 1.0])
+            .collect();
+
+        Ok(Hdri {
+            width: metadata.width,
+            height: metadata.height,
+            pixels,
+        })
+    }
+
+    /// A single black pixel, for when there's no sky to show.
+    pub fn black() -> Self {
+        Hdri {
+            width: 1,
+            height: 1,
+            pixels: vec![[0.0, 0.0, 0.0, 1.0]],
+        }
+    }
+}
@@ -0,0 +1,277 @@
+use std::ops::Range;
+
+use wgpu::{include_wgsl, util::DeviceExt};
+
+use crate::{
+    error::CapacityError,
+    gpu_buffer::{self, Ranges, Relocation},
+};
+
+// Indices are moved to their vertices' new positions by a compute shader (see `IndexBuffer::compact`).
+const USAGE: wgpu::BufferUsages = wgpu::BufferUsages::INDEX
+    .union(wgpu::BufferUsages::STORAGE)
+    .union(wgpu::BufferUsages::COPY_DST)
+    .union(wgpu::BufferUsages::COPY_SRC);
+
+/// The workgroup size of `remap_indices.wgsl`.
+const WORKGROUP_SIZE: u32 = 64;
+
+/// The most workgroups that a dispatch can have in each dimension.
+const MAX_WORKGROUPS: u32 = 65535;
+
+/// A range of indices that's drawn with a single draw call: `first_index..first_index + index_count`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct IndexRange {
+    pub first_index: u32,
+    pub index_count: u32,
+}
+
+impl IndexRange {
+    pub fn as_range(&self) -> Range<usize> {
+        self.first_index as usize..(self.first_index + self.index_count) as usize
+    }
+
+    pub fn as_draw_range(&self) -> Range<u32> {
+        self.first_index..self.first_index + self.index_count
+    }
+}
+
+pub struct IndexBuffer {
+    /// Handle to the underling GPU buffer.
+    buffer: wgpu::Buffer,
+
+    /// Maximum number of indices that can be stored before the buffer grows.
+    capacity: u64,
+
+    /// Which indices are stored.
+    ranges: Ranges,
+}
+
+impl IndexBuffer {
+    pub const FORMAT: wgpu::IndexFormat = wgpu::IndexFormat::Uint32;
+
+    pub fn new(device: &wgpu::Device, capacity: u64) -> Self {
+        Self {
+            buffer: create_buffer(device, capacity),
+            capacity,
+            ranges: Ranges::default(),
+        }
+    }
+
+    /// Grow the buffer if it doesn't have room for `additional` more indices after the last stored
+    /// index. Fails when the device doesn't allow a buffer that big.
+    pub fn reserve(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        additional: u64,
+    ) -> Result<(), CapacityError> {
+        let required = self.ranges.len() + additional;
+        if required <= self.capacity {
+            return Ok(());
+        }
+
+        let element_size = std::mem::size_of::<u32>() as u64;
+        let max = gpu_buffer::max_capacity(device, USAGE, element_size);
+        let capacity =
+            gpu_buffer::grown_capacity(self.capacity, required, max).ok_or(CapacityError {
+                buffer: String::from("IndexBuffer"),
+                requested: additional,
+                available: max.saturating_sub(self.ranges.len()),
+            })?;
+        log::debug!(
+            "Growing IndexBuffer from {} to {} indices",
+            self.capacity,
+            capacity
+        );
+
+        self.buffer = gpu_buffer::reallocate(
+            device,
+            queue,
+            "IndexBuffer",
+            USAGE,
+            &self.buffer,
+            element_size,
+            self.ranges.len(),
+            capacity,
+        );
+        self.capacity = capacity;
+        Ok(())
+    }
+
+    /**
+    Insert indices next to each other, and return the position of the first one.
+
+    Like [`VertexBuffer::insert_many`](crate::vertex_buffer::VertexBuffer::insert_many), the indices
+    go in the first removed range that they fit in, or after the last stored index.
+    */
+    pub fn insert_many(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        indices: &[u32],
+    ) -> Result<u64, CapacityError> {
+        let indices_len = indices.len() as u64;
+        let first_index = match self.ranges.reuse(indices_len) {
+            Some(first_index) => first_index,
+            None => {
+                self.reserve(device, queue, indices_len)?;
+                self.ranges.push(indices_len)
+            }
+        };
+        queue.write_buffer(
+            &self.buffer,
+            first_index * std::mem::size_of::<u32>() as u64,
+            bytemuck::cast_slice(indices),
+        );
+        Ok(first_index)
+    }
+
+    /// Remove a range of indices, so that later inserts can reuse it. See
+    /// [`VertexBuffer::remove`](crate::vertex_buffer::VertexBuffer::remove).
+    pub fn remove(&mut self, range: Range<u64>) {
+        self.ranges.remove(range);
+    }
+
+    /**
+    Move the stored indices to the start of a new buffer, like
+    [`VertexBuffer::compact`](crate::vertex_buffer::VertexBuffer::compact), and point them at their
+    vertices' new positions after the vertex buffer's compaction moved `vertex_relocations`.
+
+    Returns the ranges that moved. An [`IndexRange`] in a moved range needs to be offset by
+    `to - from.start` (see [`Relocation::apply`]).
+    */
+    pub fn compact(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        vertex_relocations: &[Relocation],
+    ) -> Vec<Relocation> {
+        if !self.ranges.has_removed() && vertex_relocations.is_empty() {
+            return Vec::new();
+        }
+
+        let index_size = std::mem::size_of::<u32>() as u64;
+        let buffer = create_buffer(device, self.capacity);
+        let relocations = self.ranges.compact();
+        for relocation in &relocations {
+            encoder.copy_buffer_to_buffer(
+                &self.buffer,
+                relocation.from.start * index_size,
+                &buffer,
+                relocation.to * index_size,
+                (relocation.from.end - relocation.from.start) * index_size,
+            );
+        }
+        if !vertex_relocations.is_empty() && self.ranges.len() > 0 {
+            remap_indices(
+                device,
+                encoder,
+                &buffer,
+                self.ranges.len() as u32,
+                vertex_relocations,
+            );
+        }
+
+        self.buffer = buffer;
+        relocations
+            .into_iter()
+            .filter(|relocation| relocation.from.start != relocation.to)
+            .collect()
+    }
+
+    pub fn as_raw_slice(&self) -> wgpu::BufferSlice<'_> {
+        self.buffer.slice(..)
+    }
+
+    /// One past the last stored index. Removed indices before it are counted.
+    pub fn len(&self) -> u64 {
+        self.ranges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+fn create_buffer(device: &wgpu::Device, capacity: u64) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("IndexBuffer"),
+        size: capacity * std::mem::size_of::<u32>() as u64,
+        usage: USAGE,
+        mapped_at_creation: false,
+    })
+}
+
+/**
+Record a compute pass that moves the first `index_count` indices in `buffer` to their vertices'
+positions after `vertex_relocations`.
+
+The pipeline is created each time, because compaction is rare (see
+[`Scene::compact`](crate::scene::Scene::compact)).
+*/
+fn remap_indices(
+    device: &wgpu::Device,
+    encoder: &mut wgpu::CommandEncoder,
+    buffer: &wgpu::Buffer,
+    index_count: u32,
+    vertex_relocations: &[Relocation],
+) {
+    // `Relocation` in `remap_indices.wgsl`.
+    let relocations: Vec<[u32; 3]> = vertex_relocations
+        .iter()
+        .map(|relocation| {
+            [
+                relocation.from.start as u32,
+                relocation.from.end as u32,
+                relocation.to as u32,
+            ]
+        })
+        .collect();
+    let relocations = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("remap_indices_relocations"),
+        contents: bytemuck::cast_slice(&relocations),
+        usage: wgpu::BufferUsages::STORAGE,
+    });
+    let index_count_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("remap_indices_index_count"),
+        contents: bytemuck::bytes_of(&index_count),
+        usage: wgpu::BufferUsages::UNIFORM,
+    });
+
+    let shader_module = device.create_shader_module(include_wgsl!("remap_indices.wgsl"));
+    let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some("remap_indices_pipeline"),
+        layout: None,
+        module: &shader_module,
+        entry_point: "remap",
+    });
+    let bind_group_0 = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("remap_indices_bind_group_0"),
+        layout: &pipeline.get_bind_group_layout(0),
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: relocations.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: index_count_buffer.as_entire_binding(),
+            },
+        ],
+    });
+
+    let workgroups = index_count.div_ceil(WORKGROUP_SIZE);
+    let columns = workgroups.min(MAX_WORKGROUPS);
+    let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+        label: Some("remap_indices_pass"),
+    });
+    compute_pass.set_pipeline(&pipeline);
+    compute_pass.set_bind_group(0, &bind_group_0, &[]);
+    compute_pass.dispatch_workgroups(columns, workgroups.div_ceil(columns), 1);
+}
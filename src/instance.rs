@@ -0,0 +1,338 @@
+/*! Drawing a mesh many times with one draw call.
+
+A mesh is uploaded once, and each copy of it that's drawn is an [`Instance`]: the model matrix and
+material that the copy is drawn with. Instances go in an [`InstanceBuffer`], which is bound as an
+instance-rate vertex buffer next to the [`VertexBuffer`](crate::vertex_buffer::VertexBuffer), and
+each [`Draw`] draws a mesh once for each of a range of instances.
+
+The instances are rewritten every frame (see [`batch`]), so that each frame only draws the levels of
+detail it chose, and only the objects that each pass can see (see [`frustum`](crate::frustum)).
+Objects are either culled on the CPU, and drawn with [`Draws::Direct`], or culled on the GPU (see
+[`gpu_culling`](crate::gpu_culling)), and drawn with [`Draws::Indirect`].
+*/
+
+use std::ops::Range;
+
+use crate::{
+    error::CapacityError, gpu_buffer, gpu_culling::DrawIndexedIndirect, index_buffer::IndexRange,
+    material::MaterialId, model_matrices::ModelMatrixId,
+};
+
+// GPU culling writes the visible instances (see `gpu_culling`).
+const USAGE: wgpu::BufferUsages = wgpu::BufferUsages::VERTEX
+    .union(wgpu::BufferUsages::STORAGE)
+    .union(wgpu::BufferUsages::COPY_DST)
+    .union(wgpu::BufferUsages::COPY_SRC);
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Instance {
+    pub model_matrix_id: ModelMatrixId,
+
+    /// The material of vertices whose [`Vertex::material_id`](crate::vertex::Vertex::material_id)
+    /// is 0.
+    pub material_id: MaterialId,
+
+    /**
+    The number of materials that the mesh's vertices choose from: a vertex whose material is `n`
+    uses the `n`th material after `material_id`, which is why a model's materials are inserted with
+    [`Materials::insert_many`](crate::material::Materials::insert_many). Meshes with a single
+    material (and models that are loaded with one) use 1, which makes every vertex use
+    `material_id`.
+    */
+    pub material_count: u32,
+}
+
+impl Instance {
+    /// The vertex shaders' instance attributes come after [`Vertex::LAYOUT`](crate::vertex::Vertex::LAYOUT)'s.
+    pub const LAYOUT: wgpu::VertexBufferLayout<'static> = wgpu::VertexBufferLayout {
+        array_stride: std::mem::size_of::<Instance>() as u64,
+        step_mode: wgpu::VertexStepMode::Instance,
+        attributes: &[
+            // model_matrix_id: ModelMatrixId
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Uint32,
+                offset: 0,
+                shader_location: 8,
+            },
+            // material_id: MaterialId
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Uint32,
+                offset: std::mem::size_of::<ModelMatrixId>() as u64,
+                shader_location: 9,
+            },
+            // material_count: u32
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Uint32,
+                offset: std::mem::size_of::<ModelMatrixId>() as u64
+                    + std::mem::size_of::<MaterialId>() as u64,
+                shader_location: 10,
+            },
+        ],
+    };
+}
+
+/// Draw the triangles in `indices` once for each instance in `instances`, which is a range of the
+/// [`InstanceBuffer`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Draw {
+    pub indices: IndexRange,
+    pub instances: Range<u32>,
+}
+
+/// How a pass draws its instances.
+pub enum Draws<'a> {
+    /// Instances that were culled on the CPU.
+    Direct(Vec<Draw>),
+
+    /**
+    Draws whose instance counts are written by GPU culling: the draws in `range` of `buffer` (see
+    [`GpuCulling::draws`](crate::gpu_culling::GpuCulling::draws)). They're drawn with a single
+    `multi_draw_indexed_indirect` when `multi_draw` is true, which needs
+    [`wgpu::Features::MULTI_DRAW_INDIRECT`], and one at a time otherwise.
+    */
+    Indirect {
+        buffer: &'a wgpu::Buffer,
+        range: Range<u32>,
+        multi_draw: bool,
+    },
+}
+
+impl<'a> Draws<'a> {
+    /// Record the draws into a render pass that has the vertex, index and instance buffers bound.
+    pub fn record(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        match self {
+            Draws::Direct(draws) => {
+                for draw in draws {
+                    render_pass.draw_indexed(
+                        draw.indices.as_draw_range(),
+                        0,
+                        draw.instances.clone(),
+                    );
+                }
+            }
+            Draws::Indirect {
+                buffer,
+                range,
+                multi_draw,
+            } => {
+                let stride = std::mem::size_of::<DrawIndexedIndirect>() as u64;
+                if *multi_draw {
+                    render_pass.multi_draw_indexed_indirect(
+                        buffer,
+                        range.start as u64 * stride,
+                        range.len() as u32,
+                    );
+                } else {
+                    for draw in range.clone() {
+                        render_pass.draw_indexed_indirect(buffer, draw as u64 * stride);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/**
+Group instances of the same mesh into single draws.
+
+The instances are appended to `buffer_instances`, in the order that they need to be written to the
+[`InstanceBuffer`], and the draws that use them are returned. Each mesh is drawn once, however many
+of its instances there are. Each pass batches its own instances into the same `buffer_instances`.
+
+The instances are usually [`Instance`]s, but can be anything that ends up in the same order, like
+[`CullInstance`](crate::gpu_culling::CullInstance)s.
+*/
+pub fn batch<T>(
+    instances: impl IntoIterator<Item = (IndexRange, T)>,
+    buffer_instances: &mut Vec<T>,
+) -> Vec<Draw> {
+    let offset = buffer_instances.len() as u32;
+    let mut instances: Vec<(IndexRange, T)> = instances.into_iter().collect();
+    // The sort is stable, so a mesh's instances keep their order.
+    instances.sort_by_key(|(indices, _)| (indices.first_index, indices.index_count));
+
+    let mut draws: Vec<Draw> = Vec::new();
+    for (index, (indices, _)) in instances.iter().enumerate() {
+        let index = offset + index as u32;
+        match draws.last_mut() {
+            Some(draw) if draw.indices == *indices => draw.instances.end = index + 1,
+            _ => draws.push(Draw {
+                indices: *indices,
+                instances: index..index + 1,
+            }),
+        }
+    }
+
+    buffer_instances.extend(instances.into_iter().map(|(_, instance)| instance));
+    draws
+}
+
+/// The instances that are drawn each frame. Unlike [`gpu_buffer::GpuBuffer`], the whole buffer is
+/// replaced by each write.
+pub struct InstanceBuffer {
+    buffer: wgpu::Buffer,
+
+    /// Maximum number of instances that can be stored before the buffer grows.
+    capacity: u64,
+
+    /// Number of instances in the last write.
+    len: u64,
+
+    /// Whether the buffer has been replaced since the last call to
+    /// [`InstanceBuffer::take_reallocated`].
+    reallocated: bool,
+}
+
+impl InstanceBuffer {
+    pub fn new(device: &wgpu::Device, capacity: u64) -> Self {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("InstanceBuffer"),
+            size: capacity * std::mem::size_of::<Instance>() as u64,
+            usage: USAGE,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            buffer,
+            capacity,
+            len: 0,
+            reallocated: false,
+        }
+    }
+
+    /// Replace the buffer's instances. The buffer grows when it's too small, and fails when the
+    /// device doesn't allow a buffer that big.
+    pub fn write(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        instances: &[Instance],
+    ) -> Result<(), CapacityError> {
+        let required = instances.len() as u64;
+        if required > self.capacity {
+            let element_size = std::mem::size_of::<Instance>() as u64;
+            let max = gpu_buffer::max_capacity(device, USAGE, element_size);
+            let capacity =
+                gpu_buffer::grown_capacity(self.capacity, required, max).ok_or(CapacityError {
+                    buffer: String::from("InstanceBuffer"),
+                    requested: required,
+                    available: max,
+                })?;
+            log::debug!(
+                "Growing InstanceBuffer from {} to {} instances",
+                self.capacity,
+                capacity
+            );
+
+            // The previous instances are about to be replaced, so they aren't copied.
+            self.buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("InstanceBuffer"),
+                size: capacity * element_size,
+                usage: USAGE,
+                mapped_at_creation: false,
+            });
+            self.capacity = capacity;
+            self.reallocated = true;
+        }
+
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(instances));
+        self.len = required;
+        Ok(())
+    }
+
+    /// The instances from the last write.
+    pub fn as_raw_slice(&self) -> wgpu::BufferSlice<'_> {
+        self.buffer
+            .slice(0..self.len * std::mem::size_of::<Instance>() as u64)
+    }
+
+    /// The whole buffer, for binding it to GPU culling's compute shader.
+    pub fn as_raw_buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    /// Whether the buffer has grown since the last call, so that bind groups that use it need to
+    /// be recreated.
+    pub fn take_reallocated(&mut self) -> bool {
+        std::mem::take(&mut self.reallocated)
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+#[test]
+fn test_batch() {
+    let sphere = IndexRange {
+        first_index: 0,
+        index_count: 300,
+    };
+    let cube = IndexRange {
+        first_index: 300,
+        index_count: 36,
+    };
+    let sphere_lod = IndexRange {
+        first_index: 336,
+        index_count: 60,
+    };
+    let instance = |index| Instance {
+        model_matrix_id: ModelMatrixId::new(index, 0),
+        material_id: MaterialId::new(index, 0),
+        material_count: 1,
+    };
+
+    let mut instances = Vec::new();
+    let draws = batch(
+        [
+            (cube, instance(0)),
+            (sphere, instance(1)),
+            (sphere_lod, instance(2)),
+            (sphere, instance(3)),
+            (cube, instance(4)),
+        ],
+        &mut instances,
+    );
+
+    assert_eq!(
+        instances,
+        [1, 3, 0, 4, 2].map(instance),
+        "each mesh's instances should be together, in their original order"
+    );
+    assert_eq!(
+        draws,
+        [
+            Draw {
+                indices: sphere,
+                instances: 0..2,
+            },
+            Draw {
+                indices: cube,
+                instances: 2..4,
+            },
+            Draw {
+                indices: sphere_lod,
+                instances: 4..5,
+            },
+        ]
+    );
+
+    // A second pass's draws use the instances after the first pass's.
+    let draws = batch([(cube, instance(5))], &mut instances);
+    assert_eq!(
+        draws,
+        [Draw {
+            indices: cube,
+            instances: 5..6,
+        }]
+    );
+    assert_eq!(instances[5], instance(5));
+
+    assert_eq!(batch([], &mut instances), []);
+    assert_eq!(instances.len(), 6);
+}
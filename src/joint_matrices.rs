@@ -0,0 +1,78 @@
+use crate::{
+    error::CapacityError,
+    gpu_buffer::{self, GpuBuffer},
+    matrix::Matrix4,
+};
+
+/**
+A generational handle to a joint matrix: the matrix's index in [`JointMatrices`], and the generation
+of its slot (see [`gpu_buffer::pack_id`]). Skinned vertices refer to their joints with these (see
+[`Vertex::joints`](crate::vertex::Vertex::joints)).
+*/
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct JointMatrixId(pub u32);
+
+impl JointMatrixId {
+    pub fn new(index: u32, generation: u8) -> Self {
+        JointMatrixId(gpu_buffer::pack_id(index, generation))
+    }
+
+    pub fn index(self) -> u32 {
+        gpu_buffer::unpack_id(self.0).0
+    }
+
+    pub fn generation(self) -> u8 {
+        gpu_buffer::unpack_id(self.0).1
+    }
+}
+
+/// The skinning matrices of every skeleton's joints (see [`skin::joint_palette`](crate::skin::joint_palette)).
+/// They take a skinned vertex to its skeleton's coordinates, and its instance's model matrix takes
+/// it the rest of the way to world space.
+pub struct JointMatrices(GpuBuffer<Matrix4>);
+
+impl JointMatrices {
+    pub fn new(device: &wgpu::Device, capacity: u32) -> Self {
+        JointMatrices(GpuBuffer::new(
+            device,
+            Some("joint_matrices"),
+            wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            capacity,
+        ))
+    }
+
+    pub fn insert(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        data: Matrix4,
+    ) -> Result<JointMatrixId, CapacityError> {
+        let index = self.0.insert(device, queue, data)?;
+        Ok(JointMatrixId::new(index, self.0.generation(index)))
+    }
+
+    /// Whether the id refers to a joint matrix that hasn't been removed.
+    pub fn contains(&self, id: JointMatrixId) -> bool {
+        self.0.contains(id.index()) && self.0.generation(id.index()) == id.generation()
+    }
+
+    pub fn update(&mut self, queue: &wgpu::Queue, id: JointMatrixId, value: Matrix4) {
+        assert!(self.contains(id), "stale joint matrix id: {:?}", id);
+        self.0.update(queue, id.index(), value)
+    }
+
+    pub fn remove(&mut self, queue: &wgpu::Queue, id: JointMatrixId) {
+        assert!(self.contains(id), "stale joint matrix id: {:?}", id);
+        self.0.remove(queue, id.index())
+    }
+
+    /// See [`GpuBuffer::take_reallocated`].
+    pub fn take_reallocated(&mut self) -> bool {
+        self.0.take_reallocated()
+    }
+
+    pub fn as_raw_buffer(&self) -> &wgpu::Buffer {
+        self.0.as_raw_buffer()
+    }
+}
@@ -1,28 +1,52 @@
 pub mod aabb;
+pub mod animation;
+pub mod asset_loader;
 pub mod camera;
 pub mod clip;
 pub mod color;
 pub mod cuboid;
+pub mod error;
+pub mod file_watcher;
+pub mod frustum;
+pub mod gizmo;
 pub mod gpu_buffer;
+pub mod gpu_culling;
 pub mod gpu_flag;
 pub mod gpu_variable;
+pub mod hdri;
+pub mod index_buffer;
+pub mod instance;
+pub mod joint_matrices;
 pub mod light;
 pub mod load;
+pub mod load_gltf;
+pub mod load_ply;
+pub mod load_stl;
+pub mod lod;
 pub mod luminance;
 pub mod material;
 pub mod matrix;
+pub mod mesh;
+pub mod mesh_file;
 pub mod model_matrices;
+pub mod picking;
 pub mod point;
 pub mod reactive;
 pub mod render_egui;
 pub mod render_hdr;
 pub mod render_sky;
 pub mod render_wireframe;
+pub mod scene;
+pub mod scene_graph;
 pub mod shadow_map_atlas;
 pub mod shadow_maps;
 pub mod shape;
+pub mod simplify;
+pub mod skin;
 pub mod sphere;
+pub mod tangent;
 pub mod tone_mapping;
+pub mod triangulate;
 pub mod vector;
 pub mod vertex;
 pub mod vertex_buffer;
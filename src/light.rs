@@ -1,5 +1,6 @@
 use crate::{
-    color::Color, matrix::Matrix4, model_matrices::ModelMatrixId, shadow_map_atlas, vector::Vec3,
+    aabb::Aabb, camera::Camera, clip, color::Color, cuboid::Cuboid, frustum::Frustum,
+    matrix::Matrix4, model_matrices::ModelMatrixId, point::Point3, shadow_map_atlas, vector::Vec3,
     wireframe::Wireframe,
 };
 
@@ -48,10 +49,27 @@ pub struct PointLightShadowMapFaces {
     pub neg_z: PointLightShadowMapFace,
 }
 
+impl PointLightShadowMapFaces {
+    pub fn iter(&self) -> impl Iterator<Item = &PointLightShadowMapFace> {
+        [
+            &self.x,
+            &self.neg_x,
+            &self.y,
+            &self.neg_y,
+            &self.z,
+            &self.neg_z,
+        ]
+        .into_iter()
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct PointLightShadowMapFace {
     pub shadow_map_light_gpu_id: u32,
     pub shadow_map_atlas_entry: shadow_map_atlas::ShadowMapAtlasEntry,
+
+    /// What the face's shadow map sees, in world space.
+    pub frustum: Frustum,
 }
 
 #[repr(C)]
@@ -71,5 +89,151 @@ pub struct DirectionalLight {
     pub shadow_map_atlas_entry: shadow_map_atlas::ShadowMapAtlasEntry,
     pub shadow_view: Matrix4,
     pub shadow_view_inverse: Matrix4,
+
+    /// What the shadow map sees, in world space. It changes when the projection is fitted to the
+    /// camera (see [`fit_orthographic_projection_to_camera`]).
+    pub frustum: Frustum,
+
     pub wireframe: Option<Wireframe>,
 }
+
+/**
+The bounds of an orthographic projection for a directional light's shadow map, in the light's view
+space (`shadow_view`).
+
+The projection covers the camera's view frustum (using its bounding sphere, so that the projection
+doesn't change size as the camera rotates) and is snapped to shadow map texels, which stops the
+shadows from shimmering as the camera moves. Its near and far planes are fitted to the part of
+`scene_bounds` that it covers.
+*/
+pub fn fit_orthographic_projection_to_camera(
+    shadow_map_size: [f32; 2],
+    scene_bounds: &Aabb,
+    camera: &Camera,
+    shadow_view: Matrix4,
+) -> Aabb {
+    let camera_frustum_world_space_bounding_sphere = camera.frustum_world_space().bounding_sphere();
+
+    let shadow_texel_x =
+        2.0 * camera_frustum_world_space_bounding_sphere.radius / shadow_map_size[0];
+    let shadow_texel_y =
+        2.0 * camera_frustum_world_space_bounding_sphere.radius / shadow_map_size[1];
+
+    let camera_frustum_light_space_bounding_sphere_center = Point3::from(
+        shadow_view
+            * camera_frustum_world_space_bounding_sphere
+                .center
+                .with_w(1.0),
+    );
+
+    let left = camera_frustum_light_space_bounding_sphere_center.x
+        - camera_frustum_world_space_bounding_sphere.radius;
+    let right = camera_frustum_light_space_bounding_sphere_center.x
+        + camera_frustum_world_space_bounding_sphere.radius;
+    let bottom = camera_frustum_light_space_bounding_sphere_center.y
+        - camera_frustum_world_space_bounding_sphere.radius;
+    let top = camera_frustum_light_space_bounding_sphere_center.y
+        + camera_frustum_world_space_bounding_sphere.radius;
+
+    let left = (left / shadow_texel_x).floor() * shadow_texel_x;
+    let right = (right / shadow_texel_x).floor() * shadow_texel_x;
+    let bottom = (bottom / shadow_texel_y).floor() * shadow_texel_y;
+    let top = (top / shadow_texel_y).floor() * shadow_texel_y;
+
+    let left_clipping_plane = clip::Plane::new(
+        Vec3::X,
+        Point3 {
+            x: left,
+            y: 0.0,
+            z: 0.0,
+        },
+    );
+    let right_clipping_plane = clip::Plane::new(
+        -Vec3::X,
+        Point3 {
+            x: right,
+            y: 0.0,
+            z: 0.0,
+        },
+    );
+    let bottom_clipping_plane = clip::Plane::new(
+        Vec3::Y,
+        Point3 {
+            x: 0.0,
+            y: bottom,
+            z: 0.0,
+        },
+    );
+    let top_clipping_plane = clip::Plane::new(
+        -Vec3::Y,
+        Point3 {
+            x: 0.0,
+            y: top,
+            z: 0.0,
+        },
+    );
+
+    let triangles: Vec<clip::Triangle> = {
+        let Cuboid {
+            near_top_left,
+            near_top_right,
+            near_bottom_left,
+            near_bottom_right,
+            far_top_left,
+            far_top_right,
+            far_bottom_left,
+            far_bottom_right,
+        } = shadow_view * scene_bounds.as_cuboid();
+
+        vec![
+            // Near face
+            clip::Triangle(near_top_right, near_top_left, near_bottom_left),
+            clip::Triangle(near_top_right, near_bottom_left, near_bottom_right),
+            // Far face
+            clip::Triangle(far_top_right, far_top_left, far_bottom_left),
+            clip::Triangle(far_top_right, far_bottom_left, far_bottom_right),
+            // Top face
+            clip::Triangle(far_top_right, far_top_left, near_top_left),
+            clip::Triangle(far_top_right, near_top_left, near_top_right),
+            // Bottom face
+            clip::Triangle(near_bottom_right, near_bottom_left, far_bottom_left),
+            clip::Triangle(near_bottom_right, far_bottom_left, far_bottom_right),
+            // Left face
+            clip::Triangle(near_top_left, far_top_left, far_bottom_left),
+            clip::Triangle(near_top_left, far_bottom_left, near_bottom_left),
+            // Right face
+            clip::Triangle(far_top_right, near_top_right, near_bottom_right),
+            clip::Triangle(far_top_right, near_bottom_right, far_bottom_right),
+        ]
+    };
+    let clipped_triangles: Vec<clip::Triangle> =
+        clip::clip_triangles(left_clipping_plane, triangles);
+    let clipped_triangles: Vec<clip::Triangle> =
+        clip::clip_triangles(right_clipping_plane, clipped_triangles);
+    let clipped_triangles: Vec<clip::Triangle> =
+        clip::clip_triangles(bottom_clipping_plane, clipped_triangles);
+    let clipped_triangles: Vec<clip::Triangle> =
+        clip::clip_triangles(top_clipping_plane, clipped_triangles);
+
+    let (near, far) =
+        clipped_triangles
+            .into_iter()
+            .fold((f32::NEG_INFINITY, f32::INFINITY), |acc, triangle| {
+                triangle.into_iter().fold(acc, |(near, far), point| {
+                    (near.max(point.z), far.min(point.z))
+                })
+            });
+
+    Aabb {
+        min: Point3 {
+            x: left,
+            y: bottom,
+            z: far,
+        },
+        max: Point3 {
+            x: right,
+            y: top,
+            z: near,
+        },
+    }
+}
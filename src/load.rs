@@ -1,177 +1,1173 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
 use crate::{
     aabb::Aabb,
-    material::MaterialId,
+    color::Color,
+    error::{CapacityError, LoadError},
+    index_buffer::{IndexBuffer, IndexRange},
+    instance::Instance,
+    load_ply, load_stl, lod,
+    material::{Material, MaterialId, Materials},
     matrix::Matrix4,
+    mesh::{self, Mesh},
+    mesh_file::{self, MeshFile},
     model_matrices::{ModelMatrices, ModelMatrixId},
     point::Point3,
-    vector::Vec3,
+    tangent,
+    triangulate::triangulate,
+    vector::{Vec2, Vec3, Vec4},
     vertex::Vertex,
     vertex_buffer::VertexBuffer,
 };
 
+#[derive(Debug, Clone)]
+pub struct LoadOptions {
+    /// Place every object in the file under a single model matrix, so that they're transformed
+    /// together. By default each object gets its own model matrix.
+    pub merge: bool,
+
+    /// Use this material for every object in the file, instead of the materials from the file's
+    /// `.mtl` library.
+    pub material: Option<MaterialId>,
+
+    /// How to calculate normals for meshes that don't have them.
+    pub normal_style: NormalStyle,
+
+    /// The number of simplified levels of detail to generate for each object. See [`lod`].
+    pub lod_levels: usize,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        LoadOptions {
+            merge: false,
+            material: None,
+            normal_style: NormalStyle::default(),
+            lod_levels: lod::DEFAULT_LOD_LEVELS,
+        }
+    }
+}
+
+/// An object from a model file (e.g. an `o` or `g` from an `.obj` file), after it's been loaded onto
+/// the GPU.
+#[derive(Debug, Clone)]
+pub struct LoadedObject {
+    pub name: String,
+    pub model_matrix_id: ModelMatrixId,
+
+    /// The first of the object's materials. See [`Instance::material_count`].
+    pub material_id: MaterialId,
+
+    /// Objects from model files have one material for each of the model's materials, which their
+    /// vertices choose from.
+    pub material_count: u32,
+
+    /// The object's bounds in world space.
+    pub aabb: Aabb,
+
+    /// Where each of the object's levels of detail are in the index buffer, from full detail to
+    /// the coarsest level. See [`lod`].
+    pub lods: Vec<IndexRange>,
+}
+
+/// A model that's been loaded onto the GPU with [`insert_model`], and what it uses of the buffers.
+#[derive(Debug, Clone, Default)]
+pub struct LoadedModel {
+    pub objects: Vec<LoadedObject>,
+
+    /// The model's vertices in the [`VertexBuffer`].
+    pub vertices: Range<u64>,
+
+    /// The model's indices in the [`IndexBuffer`], which every object's levels of detail are in.
+    pub indices: Range<u64>,
+
+    /// The materials that were inserted for the model. Empty when [`LoadOptions::material`] is set.
+    pub material_ids: Vec<MaterialId>,
+}
+
+impl LoadedObject {
+    pub fn instance(&self) -> Instance {
+        Instance {
+            model_matrix_id: self.model_matrix_id,
+            material_id: self.material_id,
+            material_count: self.material_count,
+        }
+    }
+}
+
+/**
+A model that's been read from a file, but not loaded onto the GPU.
+
+The vertices use file-local ids: a vertex's `model_matrix_id` is the index of its object in
+[`Model::objects`], and its `material_id` is the index of its material in [`Model::materials`].
+They're uploaded as they are: [`insert_model`] inserts the model's materials next to each other, and
+the objects' instances point at the first one (see [`Instance::material_count`]).
+
+The model's data is either owned (e.g. when it's read from an `.obj` file) or borrowed (e.g. from a
+memory-mapped [`mesh_file`]).
+*/
+pub struct Model<'a> {
+    pub materials: Cow<'a, [Material]>,
+    pub objects: Vec<ModelObject>,
+    pub vertices: Cow<'a, [Vertex]>,
+
+    /// Every 3 indices are a triangle. Indices point into [`Model::vertices`].
+    pub indices: Cow<'a, [u32]>,
+}
+
+impl Model<'_> {
+    /// The same model, borrowing this one's materials, vertices and indices.
+    pub fn as_borrowed(&self) -> Model<'_> {
+        Model {
+            materials: Cow::Borrowed(&self.materials),
+            objects: self.objects.clone(),
+            vertices: Cow::Borrowed(&self.vertices),
+            indices: Cow::Borrowed(&self.indices),
+        }
+    }
+}
+
+/**
+A model file that's been read by [`read_model`].
+
+Mesh files stay memory-mapped, so that their vertices and indices are uploaded straight from the
+file rather than being copied into a [`Model`] first.
+*/
+pub enum ModelFile {
+    Parsed(Model<'static>),
+    Mapped(MeshFile),
+}
+
+impl ModelFile {
+    /// The file's model, borrowing its materials, vertices and indices.
+    pub fn model(&self) -> Model<'_> {
+        match self {
+            ModelFile::Parsed(model) => model.as_borrowed(),
+            ModelFile::Mapped(mesh_file) => mesh_file.model(),
+        }
+    }
+
+    /// Generate up to `levels` levels of detail for each object (see [`lod::generate_lods`]). Mesh
+    /// files keep the levels of detail that they were converted with.
+    pub fn generate_lods(&mut self, levels: usize) {
+        if let ModelFile::Parsed(model) = self {
+            lod::generate_lods(model, levels);
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ModelObject {
+    pub name: String,
+
+    /**
+    The object's triangles at each level of detail, as ranges of [`Model::indices`]. The first level
+    is the full-detail object. See [`lod`].
+    */
+    pub lods: Vec<IndexRange>,
+
+    /// The object's bounds in model space.
+    pub aabb: Aabb,
+}
+
+/**
+Read a model file, choosing the format from the file's extension.
+
+* `.mesh`: see [`MeshFile::open`]. Mesh files already have their normals, so `normal_style` isn't
+  used.
+* `.ply`: see [`read_ply`](crate::load_ply::read_ply)
+* `.stl`: see [`read_stl`](crate::load_stl::read_stl)
+* anything else: see [`read_obj`]
+*/
+pub fn read_model(file_name: &str, normal_style: NormalStyle) -> Result<ModelFile, LoadError> {
+    let extension = Path::new(file_name)
+        .extension()
+        .map(|extension| extension.to_ascii_lowercase());
+    let model = match extension.as_ref().and_then(|extension| extension.to_str()) {
+        Some(mesh_file::EXTENSION) => return MeshFile::open(file_name).map(ModelFile::Mapped),
+        Some("ply") => load_ply::read_ply(file_name, normal_style),
+        Some("stl") => load_stl::read_stl(file_name, normal_style),
+        _ => read_obj(file_name, normal_style),
+    };
+    model.map(ModelFile::Parsed)
+}
+
+/// Read an `.obj` file and its `.mtl` library.
+pub fn read_obj(file_name: &str, normal_style: NormalStyle) -> Result<Model<'static>, LoadError> {
+    let (models, mtl_materials) = tobj::load_obj(
+        file_name,
+        &tobj::LoadOptions {
+            single_index: true,
+            ..Default::default()
+        },
+    )
+    .map_err(|error| LoadError::Obj {
+        path: PathBuf::from(file_name),
+        error,
+    })?;
+
+    let mut materials: Vec<Material> = match mtl_materials {
+        Ok(mtl_materials) => mtl_materials.iter().map(material_from_mtl).collect(),
+        Err(err) => {
+            log::warn!("failed to load materials for {}: {}", file_name, err);
+            Vec::new()
+        }
+    };
+    let mtl_materials_len = materials.len();
+
+    // Only added if an object doesn't have a material.
+    let mut default_material: Option<usize> = None;
+
+    let mut objects: Vec<ModelObject> = Vec::with_capacity(models.len());
+    let mut vertices: Vec<Vertex> = Vec::new();
+    let mut indices: Vec<u32> = Vec::new();
+    for model in models.iter() {
+        /* `tobj` splits an object into one model per material that it uses, so consecutive models
+        with the same name are parts of the same object.
+        */
+        let is_previous_object = objects
+            .last()
+            .is_some_and(|object| object.name == model.name);
+        let object = if is_previous_object {
+            objects.len() - 1
+        } else {
+            objects.len()
+        };
+
+        let material = match model
+            .mesh
+            .material_id
+            .filter(|index| *index < mtl_materials_len)
+        {
+            Some(index) => index,
+            None => *default_material.get_or_insert_with(|| {
+                materials.push(DEFAULT_MATERIAL);
+                materials.len() - 1
+            }),
+        };
+
+        let mesh = model_mesh(
+            ModelMatrixId(object as u32),
+            MaterialId(material as u32),
+            model,
+            normal_style,
+        );
+        let aabb = mesh.aabb();
+
+        let first_index = indices.len() as u32;
+        let base_vertex = vertices.len() as u32;
+        indices.extend(mesh.indices.iter().map(|index| base_vertex + index));
+        vertices.extend(mesh.vertices);
+
+        if is_previous_object {
+            let previous_object = &mut objects[object];
+            previous_object.lods[0].index_count += indices.len() as u32 - first_index;
+            previous_object.aabb = previous_object.aabb.union(aabb);
+        } else {
+            objects.push(ModelObject {
+                name: model.name.clone(),
+                lods: vec![IndexRange {
+                    first_index,
+                    index_count: indices.len() as u32 - first_index,
+                }],
+                aabb,
+            });
+        }
+    }
+
+    Ok(Model {
+        materials: Cow::Owned(materials),
+        objects,
+        vertices: Cow::Owned(vertices),
+        indices: Cow::Owned(indices),
+    })
+}
+
+/**
+Load a [`Model`] onto the GPU.
+
+Allocates a material for each of the model's materials (unless [`LoadOptions::material`] is set),
+and a model matrix for each of its objects (or one for the whole model when
+[`LoadOptions::merge`] is set).
+*/
+#[allow(clippy::too_many_arguments)]
+pub fn insert_model(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    model_matrices: &mut ModelMatrices,
+    materials: &mut Materials,
+    vertex_buffer: &mut VertexBuffer,
+    index_buffer: &mut IndexBuffer,
+    model: &Model,
+    transform: Matrix4,
+    options: &LoadOptions,
+) -> Result<LoadedModel, CapacityError> {
+    let model_matrix_ids: Vec<ModelMatrixId> = if options.merge {
+        let model_matrix_id = model_matrices.insert(device, queue, transform)?;
+        vec![model_matrix_id; model.objects.len()]
+    } else {
+        model
+            .objects
+            .iter()
+            .map(|_| model_matrices.insert(device, queue, transform))
+            .collect::<Result<_, _>>()?
+    };
+
+    insert_objects(
+        device,
+        queue,
+        materials,
+        vertex_buffer,
+        index_buffer,
+        model,
+        model_matrix_ids,
+        transform,
+        options,
+    )
+}
+
+/**
+Replace a model that was loaded with [`insert_model`] with a new version of it, e.g. after its file
+has changed.
+
+The previous version's vertices, indices and materials are removed first, so a new version that's
+no bigger reuses their space. The new objects reuse the previous objects' model matrices, so
+anything that refers to them (e.g. a wireframe) follows the new version, and the model matrices of
+objects that the new version doesn't have are removed.
+
+When this fails, the previous version's data is still removed, so it can't be drawn anymore.
+*/
+#[allow(clippy::too_many_arguments)]
+pub fn replace_model(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    model_matrices: &mut ModelMatrices,
+    materials: &mut Materials,
+    vertex_buffer: &mut VertexBuffer,
+    index_buffer: &mut IndexBuffer,
+    model: &Model,
+    previous: &LoadedModel,
+    transform: Matrix4,
+    options: &LoadOptions,
+) -> Result<LoadedModel, CapacityError> {
+    vertex_buffer.remove(previous.vertices.clone());
+    index_buffer.remove(previous.indices.clone());
+    for material_id in &previous.material_ids {
+        materials.remove(queue, *material_id);
+    }
+    let previous = &previous.objects;
+
+    // Objects that the previous version didn't have get new model matrices.
+    let mut reuse_or_insert = |previous: Option<&LoadedObject>| match previous {
+        Some(object) => {
+            model_matrices.update(queue, object.model_matrix_id, transform);
+            Ok(object.model_matrix_id)
+        }
+        None => model_matrices.insert(device, queue, transform),
+    };
+    let model_matrix_ids: Vec<ModelMatrixId> = if options.merge {
+        let model_matrix_id = reuse_or_insert(previous.first())?;
+        vec![model_matrix_id; model.objects.len()]
+    } else {
+        let model_matrix_ids = (0..model.objects.len())
+            .map(|index| reuse_or_insert(previous.get(index)))
+            .collect::<Result<_, _>>()?;
+        for object in previous.iter().skip(model.objects.len()) {
+            model_matrices.remove(queue, object.model_matrix_id);
+        }
+        model_matrix_ids
+    };
+
+    insert_objects(
+        device,
+        queue,
+        materials,
+        vertex_buffer,
+        index_buffer,
+        model,
+        model_matrix_ids,
+        transform,
+        options,
+    )
+}
+
+/// Upload a model's materials, vertices and indices, using the given model matrix for each object.
+#[allow(clippy::too_many_arguments)]
+fn insert_objects(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    materials: &mut Materials,
+    vertex_buffer: &mut VertexBuffer,
+    index_buffer: &mut IndexBuffer,
+    model: &Model,
+    model_matrix_ids: Vec<ModelMatrixId>,
+    transform: Matrix4,
+    options: &LoadOptions,
+) -> Result<LoadedModel, CapacityError> {
+    let (material_ids, material_id, material_count) = match options.material {
+        Some(material_id) => (Vec::new(), material_id, 1),
+        None => {
+            let material_ids = materials.insert_many(device, queue, &model.materials)?;
+            let material_id = material_ids.first().copied().unwrap_or(MaterialId(0));
+            let material_count = material_ids.len() as u32;
+            (material_ids, material_id, material_count)
+        }
+    };
+
+    let (vertices, index_range) = match mesh::insert(
+        device,
+        queue,
+        vertex_buffer,
+        index_buffer,
+        &model.vertices,
+        &model.indices,
+    ) {
+        Ok(ranges) => ranges,
+        Err(error) => {
+            for material_id in material_ids {
+                materials.remove(queue, material_id);
+            }
+            return Err(error);
+        }
+    };
+
+    let objects = model
+        .objects
+        .iter()
+        .zip(model_matrix_ids)
+        .map(|(object, model_matrix_id)| LoadedObject {
+            name: object.name.clone(),
+            model_matrix_id,
+            material_id,
+            material_count,
+            aabb: object.aabb.transform(transform),
+            lods: object
+                .lods
+                .iter()
+                .map(|lod| IndexRange {
+                    first_index: index_range.first_index + lod.first_index,
+                    index_count: lod.index_count,
+                })
+                .collect(),
+        })
+        .collect();
+
+    Ok(LoadedModel {
+        objects,
+        vertices,
+        indices: index_range.first_index as u64
+            ..index_range.first_index as u64 + index_range.index_count as u64,
+        material_ids,
+    })
+}
+
+/**
+Load a model file onto the GPU, with [`LoadOptions::lod_levels`] levels of detail. See
+[`read_model`], [`lod::generate_lods`] and [`insert_model`].
+*/
+#[allow(clippy::too_many_arguments)]
 pub fn load_model(
+    device: &wgpu::Device,
     queue: &wgpu::Queue,
     model_matrices: &mut ModelMatrices,
+    materials: &mut Materials,
     vertex_buffer: &mut VertexBuffer,
+    index_buffer: &mut IndexBuffer,
     file_name: &str,
     transform: Matrix4,
-    material_id: MaterialId,
-) -> Aabb {
-    let (models, _materials) = tobj::load_obj(
-        file_name,
-        &tobj::LoadOptions {
-            single_index: true,
-            ..Default::default()
-        },
+    options: &LoadOptions,
+) -> Result<LoadedModel, LoadError> {
+    let mut model_file = read_model(file_name, options.normal_style)?;
+    model_file.generate_lods(options.lod_levels);
+    insert_model(
+        device,
+        queue,
+        model_matrices,
+        materials,
+        vertex_buffer,
+        index_buffer,
+        &model_file.model(),
+        transform,
+        options,
     )
-    .unwrap();
-
-    let model = &models[0];
-
-    let model_matrix_id = model_matrices.insert(queue, transform);
-    let vertices = {
-        let mut vertices = Vec::with_capacity(model.mesh.indices.len());
-
-        if model.mesh.face_arities.is_empty() {
-            // all faces are triangles
-            if model.mesh.normals.is_empty() {
-                extract_and_normalise(model_matrix_id, material_id, model, &mut vertices);
-            } else {
-                /*
-                assert!(
-                    !model.mesh.normals.is_empty(),
-                    "model {} is missing vertex normals",
-                    file_name
-                );
-                */
-                for index in model.mesh.indices.iter() {
-                    let index = *index as usize;
-                    vertices.push(Vertex {
-                        position: Point3 {
-                            x: model.mesh.positions[3 * index],
-                            y: model.mesh.positions[3 * index + 1],
-                            z: model.mesh.positions[3 * index + 2],
-                        },
-                        model_matrix_id,
-                        normal: Vec3 {
-                            x: model.mesh.normals[3 * index],
-                            y: model.mesh.normals[3 * index + 1],
-                            z: model.mesh.normals[3 * index + 2],
-                        },
-                        material_id,
-                    });
+    .map_err(|error| LoadError::Capacity {
+        path: PathBuf::from(file_name),
+        error,
+    })
+}
+
+/// A triangle mesh, stored the way most file formats store them.
+pub(crate) struct RawMesh {
+    /// Flattened `xyz` triples.
+    pub positions: Vec<f32>,
+
+    /// Flattened `xyz` triples. Empty when the file doesn't have normals.
+    pub normals: Vec<f32>,
+
+    /// Every 3 indices are a triangle.
+    pub indices: Vec<u32>,
+}
+
+/// A model for file formats that only contain a single mesh, like `.ply` and `.stl`.
+pub(crate) fn single_object_model(
+    name: &str,
+    mesh: RawMesh,
+    normal_style: NormalStyle,
+) -> Model<'static> {
+    let mesh = triangle_mesh(
+        ModelMatrixId(0),
+        MaterialId(0),
+        &mesh.positions,
+        &mesh.normals,
+        &[],
+        &mesh.indices,
+        normal_style,
+    );
+
+    Model {
+        materials: Cow::Owned(vec![DEFAULT_MATERIAL]),
+        objects: vec![ModelObject {
+            name: String::from(name),
+            lods: vec![IndexRange {
+                first_index: 0,
+                index_count: mesh.indices.len() as u32,
+            }],
+            aabb: mesh.aabb(),
+        }],
+        vertices: Cow::Owned(mesh.vertices),
+        indices: Cow::Owned(mesh.indices),
+    }
+}
+
+/// The file's name without its extension. Used to name objects from formats that don't name them.
+pub(crate) fn file_stem(path: &Path) -> String {
+    path.file_stem()
+        .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned())
+}
+
+/// Used for objects that aren't assigned a material by their `.mtl` library.
+const DEFAULT_MATERIAL: Material = Material {
+    color: Color {
+        r: 0.8,
+        g: 0.8,
+        b: 0.8,
+        a: 1.0,
+    },
+    roughness: 0.5,
+    metallic: 0.0,
+    _padding: [0, 0],
+};
+
+/**
+Convert an `.mtl` material to a physically-based [`Material`].
+
+* `Kd` (diffuse color) becomes the base color, and `d` (dissolve) its alpha.
+* `Pr` (roughness, from the [PBR extension](http://exocortex.com/blog/extending_wavefront_mtl_to_support_pbr))
+  is used as the roughness when present. Otherwise `Ns` (Blinn-Phong specular exponent) is
+  converted to a roughness using the Beckmann approximation `alpha = sqrt(2 / (Ns + 2))`, where
+  `alpha = roughness^2` (see `render_hdr.wgsl:brdf`).
+* `Pm` (metallic, from the PBR extension) becomes the metalness.
+*/
+fn material_from_mtl(mtl_material: &tobj::Material) -> Material {
+    let pbr_param = |name: &str| -> Option<f32> {
+        mtl_material
+            .unknown_param
+            .get(name)
+            .and_then(|value| value.trim().parse().ok())
+    };
+
+    let color = match mtl_material.diffuse {
+        Some([r, g, b]) => Color {
+            r,
+            g,
+            b,
+            a: mtl_material.dissolve.unwrap_or(1.0),
+        },
+        None => DEFAULT_MATERIAL.color,
+    };
+
+    let roughness = match pbr_param("Pr") {
+        Some(roughness) => roughness,
+        None => match mtl_material.shininess {
+            Some(shininess) => (2.0 / (shininess.max(0.0) + 2.0)).powf(0.25),
+            None => DEFAULT_MATERIAL.roughness,
+        },
+    };
+
+    let metallic = pbr_param("Pm").unwrap_or(DEFAULT_MATERIAL.metallic);
+
+    Material {
+        color,
+        roughness: roughness.clamp(0.0, 1.0),
+        metallic: metallic.clamp(0.0, 1.0),
+        _padding: [0, 0],
+    }
+}
+
+fn model_mesh(
+    model_matrix_id: ModelMatrixId,
+    material_id: MaterialId,
+    model: &tobj::Model,
+    normal_style: NormalStyle,
+) -> Mesh {
+    // `.obj` texture coordinates start at the bottom left.
+    let texcoords: Vec<f32> = model
+        .mesh
+        .texcoords
+        .chunks(2)
+        .flat_map(|uv| [uv[0], 1.0 - uv[1]])
+        .collect();
+
+    if model.mesh.face_arities.is_empty() {
+        // all faces are triangles
+        triangle_mesh(
+            model_matrix_id,
+            material_id,
+            &model.mesh.positions,
+            &model.mesh.normals,
+            &texcoords,
+            &model.mesh.indices,
+            normal_style,
+        )
+    } else {
+        let indices = triangulate_faces(
+            &model.mesh.positions,
+            &model.mesh.face_arities,
+            &model.mesh.indices,
+        );
+        triangle_mesh(
+            model_matrix_id,
+            material_id,
+            &model.mesh.positions,
+            &model.mesh.normals,
+            &texcoords,
+            &indices,
+            normal_style,
+        )
+    }
+}
+
+/**
+Split polygonal faces into triangles.
+
+`face_arities` are the number of vertices in each face, and `indices` lists each face's vertices
+in order. Returns 3 indices per triangle.
+*/
+pub(crate) fn triangulate_faces(
+    positions: &[f32],
+    face_arities: &[u32],
+    indices: &[u32],
+) -> Vec<u32> {
+    let mut triangles = Vec::with_capacity(indices.len());
+
+    let mut face_start = 0;
+    for face_arity in face_arities {
+        let face = &indices[face_start..face_start + *face_arity as usize];
+        face_start += face.len();
+
+        let polygon: Vec<Point3> = face
+            .iter()
+            .map(|index| {
+                let index = *index as usize;
+                Point3 {
+                    x: positions[3 * index],
+                    y: positions[3 * index + 1],
+                    z: positions[3 * index + 2],
                 }
-            }
-        } else {
-            panic!("mesh is not triangulated");
+            })
+            .collect();
+
+        for [a, b, c] in triangulate(&polygon) {
+            triangles.extend([face[a], face[b], face[c]]);
         }
+    }
+
+    triangles
+}
+
+/**
+Create an indexed [`Mesh`] from a triangle mesh.
+
+`positions` and `normals` are flattened `xyz` triples, `texcoords` are flattened `uv` pairs (see
+[`Vertex::uv`]), and every 3 `indices` are a triangle. When `normals` is empty, they're calculated
+from the triangles according to `normal_style`.
+
+Tangents are calculated from the texture coordinates (see [`tangent::generate_tangents`]). When
+`texcoords` is empty, every vertex's `uv` is `(0, 0)` and its tangent is arbitrary. When it's shorter
+than `positions`, the remaining vertices' `uv`s are `(0, 0)`.
+*/
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn triangle_mesh(
+    model_matrix_id: ModelMatrixId,
+    material_id: MaterialId,
+    positions: &[f32],
+    normals: &[f32],
+    texcoords: &[f32],
+    indices: &[u32],
+    normal_style: NormalStyle,
+) -> Mesh {
+    // Vertices without texture coordinates get `(0, 0)`.
+    let mut uvs: Vec<Vec2> = texcoords
+        .chunks_exact(2)
+        .map(|uv| Vec2 { x: uv[0], y: uv[1] })
+        .collect();
+    uvs.resize(positions.len() / 3, Vec2::ZERO);
+
+    let mut mesh = if normals.is_empty() {
+        extract_and_normalise(
+            model_matrix_id,
+            material_id,
+            positions,
+            &uvs,
+            indices,
+            normal_style,
+        )
+    } else {
+        let vertices = positions
+            .chunks(3)
+            .zip(normals.chunks(3))
+            .zip(uvs)
+            .map(|((position, normal), uv)| Vertex {
+                position: Point3 {
+                    x: position[0],
+                    y: position[1],
+                    z: position[2],
+                },
+                model_matrix_id,
+                normal: Vec3 {
+                    x: normal[0],
+                    y: normal[1],
+                    z: normal[2],
+                },
+                material_id,
+                uv,
+                tangent: Vec3::X.with_w(1.0),
+                joints: [0; 4],
+                weights: Vec4::ZERO,
+            })
+            .collect();
 
-        vertices
+        Mesh {
+            vertices,
+            indices: indices.to_vec(),
+        }
     };
-    vertex_buffer.insert_many(queue, &vertices);
 
-    let model_aabb = vertices.into_iter().fold(Aabb::EMPTY, |aabb, vertex| {
-        aabb.union(Aabb::point(vertex.position))
-    });
+    if texcoords.is_empty() {
+        for vertex in mesh.vertices.iter_mut() {
+            vertex.tangent = tangent::perpendicular_tangent(vertex.normal);
+        }
+    } else {
+        tangent::generate_tangents(&mut mesh);
+    }
 
-    model_aabb.transform(transform)
+    mesh
 }
 
-enum NormalStyle {
-    /// For flat shading.
+/// How normals are calculated for meshes that don't have them.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum NormalStyle {
+    /// For flat shading. Each face's vertices get the face's normal.
     Face,
-    /// For smooth shading.
-    Vertex,
-}
 
-const NORMAL_STYLE: NormalStyle = NormalStyle::Vertex;
+    /**
+    For smooth shading. Each vertex's normal is the average of the normals of the faces around it,
+    with every face counting the same.
+
+    This is how normals were always calculated before they could be configured, so it's the
+    default.
+    */
+    #[default]
+    Unweighted,
+
+    /**
+    For smooth shading. Each vertex's normal is the average of the normals of the faces around it,
+    weighted by the faces' areas.
 
+    Large faces have more influence than small ones.
+    */
+    AreaWeighted,
+
+    /**
+    For smooth shading. Each vertex's normal is the average of the normals of the faces around it,
+    weighted by the angle of each face's corner at the vertex.
+
+    Unlike [`NormalStyle::AreaWeighted`], the result doesn't depend on how a surface is split into
+    triangles.
+    */
+    AngleWeighted,
+
+    /**
+    For smooth shading with sharp edges. Like [`NormalStyle::AngleWeighted`], but a face only
+    contributes to the normal at one of another face's corners when the angle between the two
+    faces is at most `crease_angle` (in radians).
+
+    Vertices on sharper edges are split, so that each side of the edge is shaded separately.
+    */
+    Crease { crease_angle: f32 },
+}
+
+/// `uvs` has one entry per position. The vertices' tangents are left for the caller to calculate.
 fn extract_and_normalise(
     model_matrix_id: ModelMatrixId,
     material_id: MaterialId,
-    model: &tobj::Model,
-    vertices: &mut Vec<Vertex>,
-) {
-    let mut normals: Vec<Vec3> = match NORMAL_STYLE {
-        NormalStyle::Vertex => std::iter::repeat(Vec3::ZERO)
-            .take(model.mesh.positions.len())
-            .collect(),
-        NormalStyle::Face => Vec::new(),
+    positions: &[f32],
+    uvs: &[Vec2],
+    indices: &[u32],
+    normal_style: NormalStyle,
+) -> Mesh {
+    let points: Vec<Point3> = positions
+        .chunks(3)
+        .map(|position| Point3 {
+            x: position[0],
+            y: position[1],
+            z: position[2],
+        })
+        .collect();
+
+    let triangles: Vec<[usize; 3]> = indices
+        .chunks(3)
+        .map(|triangle| {
+            [
+                triangle[0] as usize,
+                triangle[1] as usize,
+                triangle[2] as usize,
+            ]
+        })
+        .collect();
+
+    /* Assumes CCW vertex order.
+
+    The cross product's length is twice the triangle's area, which is used by
+    `NormalStyle::AreaWeighted`.
+    */
+    let face_normals: Vec<Vec3> = triangles
+        .iter()
+        .map(|[a, b, c]| (points[*b] - points[*a]).cross(points[*c] - points[*a]))
+        .collect();
+    let unit_face_normals: Vec<Vec3> = face_normals
+        .iter()
+        .map(|normal| normalize_or(*normal, Vec3::ZERO))
+        .collect();
+
+    let vertex = |index: usize, normal: Vec3| Vertex {
+        position: points[index],
+        model_matrix_id,
+        normal,
+        material_id,
+        uv: uvs[index],
+        tangent: Vec3::X.with_w(1.0),
+        joints: [0; 4],
+        weights: Vec4::ZERO,
     };
 
-    for triangle in model.mesh.indices.chunks(3) {
-        let [index_a, index_b, index_c] = triangle
-                        else { unreachable!() };
+    match normal_style {
+        // Each face needs its own copy of its vertices, so that they can have the face's normal.
+        NormalStyle::Face => {
+            let mut vertices = Vec::with_capacity(indices.len());
+            for (triangle, normal) in triangles.iter().zip(&unit_face_normals) {
+                for index in triangle {
+                    vertices.push(vertex(*index, *normal));
+                }
+            }
 
-        let index_a = *index_a as usize;
-        let index_b = *index_b as usize;
-        let index_c = *index_c as usize;
+            Mesh {
+                indices: (0..vertices.len() as u32).collect(),
+                vertices,
+            }
+        }
 
-        let a = Point3 {
-            x: model.mesh.positions[3 * index_a],
-            y: model.mesh.positions[3 * index_a + 1],
-            z: model.mesh.positions[3 * index_a + 2],
-        };
+        /* Each vertex's normal is the weighted sum of the normals of the faces that use it, so the
+        vertices can be shared between faces.
+        */
+        NormalStyle::Unweighted | NormalStyle::AreaWeighted | NormalStyle::AngleWeighted => {
+            let mut normals: Vec<Vec3> = vec![Vec3::ZERO; points.len()];
 
-        let b = Point3 {
-            x: model.mesh.positions[3 * index_b],
-            y: model.mesh.positions[3 * index_b + 1],
-            z: model.mesh.positions[3 * index_b + 2],
-        };
+            for (face, triangle) in triangles.iter().enumerate() {
+                for corner in 0..3 {
+                    normals[triangle[corner]] += match normal_style {
+                        NormalStyle::Unweighted => unit_face_normals[face],
+                        NormalStyle::AreaWeighted => face_normals[face],
+                        _ => corner_angle(&points, triangle, corner) * unit_face_normals[face],
+                    };
+                }
+            }
 
-        let c = Point3 {
-            x: model.mesh.positions[3 * index_c],
-            y: model.mesh.positions[3 * index_c + 1],
-            z: model.mesh.positions[3 * index_c + 2],
-        };
+            let vertices = normals
+                .into_iter()
+                .enumerate()
+                .map(|(index, normal)| vertex(index, normalize_or(normal, Vec3::ZERO)))
+                .collect();
+
+            Mesh {
+                vertices,
+                indices: indices.to_vec(),
+            }
+        }
 
-        // Assumes CCW vertex order.
-        let face_normal = (b - a).cross(c - a).normalize();
+        /* Each corner's normal only includes the faces around its vertex that are within the
+        crease angle of the corner's face. Corners of the same vertex that end up with the same
+        normal share a vertex.
+        */
+        NormalStyle::Crease { crease_angle } => {
+            let min_cos = crease_angle.cos();
 
-        let normal = match NORMAL_STYLE {
-            NormalStyle::Face => face_normal,
-            NormalStyle::Vertex => {
-                normals[index_a] += face_normal;
-                normals[index_b] += face_normal;
-                normals[index_c] += face_normal;
-                Vec3::ZERO
+            let mut vertex_faces: Vec<Vec<usize>> = vec![Vec::new(); points.len()];
+            for (face, triangle) in triangles.iter().enumerate() {
+                for index in triangle {
+                    vertex_faces[*index].push(face);
+                }
             }
-        };
 
-        vertices.push(Vertex {
-            position: a,
-            model_matrix_id,
-            normal,
-            material_id,
-        });
+            let mut vertices = Vec::new();
+            let mut split_indices = Vec::with_capacity(indices.len());
+            let mut split_vertices: HashMap<(usize, [u32; 3]), u32> = HashMap::new();
 
-        vertices.push(Vertex {
-            position: b,
-            model_matrix_id,
-            normal,
-            material_id,
-        });
+            for (face, triangle) in triangles.iter().enumerate() {
+                for index in triangle {
+                    let mut normal = Vec3::ZERO;
+                    for other_face in vertex_faces[*index].iter() {
+                        let is_smooth =
+                            unit_face_normals[face].dot(unit_face_normals[*other_face]) >= min_cos;
+                        if is_smooth {
+                            let other_triangle = &triangles[*other_face];
+                            let other_corner = other_triangle
+                                .iter()
+                                .position(|other_index| other_index == index)
+                                .unwrap();
+                            normal += corner_angle(&points, other_triangle, other_corner)
+                                * unit_face_normals[*other_face];
+                        }
+                    }
+                    let normal = normalize_or(normal, unit_face_normals[face]);
 
-        vertices.push(Vertex {
-            position: c,
-            model_matrix_id,
-            normal,
-            material_id,
-        });
+                    let key = (*index, [normal.x, normal.y, normal.z].map(f32::to_bits));
+                    let split_index = *split_vertices.entry(key).or_insert_with(|| {
+                        vertices.push(vertex(*index, normal));
+                        (vertices.len() - 1) as u32
+                    });
+                    split_indices.push(split_index);
+                }
+            }
+
+            Mesh {
+                vertices,
+                indices: split_indices,
+            }
+        }
     }
+}
 
-    if let NormalStyle::Vertex = NORMAL_STYLE {
-        for triangle in model.mesh.indices.chunks(3).enumerate() {
-            let (face, [index_a, index_b, index_c]) = triangle
-                        else { unreachable!() };
+/// The interior angle of a triangle at one of its corners, in radians.
+fn corner_angle(points: &[Point3], triangle: &[usize; 3], corner: usize) -> f32 {
+    let point = points[triangle[corner]];
+    let to_next = points[triangle[(corner + 1) % 3]] - point;
+    let to_previous = points[triangle[(corner + 2) % 3]] - point;
+    to_next
+        .cross(to_previous)
+        .length()
+        .atan2(to_next.dot(to_previous))
+}
 
-            let index_a = *index_a as usize;
-            let index_b = *index_b as usize;
-            let index_c = *index_c as usize;
+/// Normalize `vector`, or return `fallback` when it has no length.
+fn normalize_or(vector: Vec3, fallback: Vec3) -> Vec3 {
+    if vector.length() > 0.0 {
+        vector.normalize()
+    } else {
+        fallback
+    }
+}
 
-            vertices[3 * face].normal = normals[index_a].normalize();
-            vertices[3 * face + 1].normal = normals[index_b].normalize();
-            vertices[3 * face + 2].normal = normals[index_c].normalize();
+#[test]
+fn test_read_mesh_file() {
+    let mut obj = read_model("models/monkey.obj", NormalStyle::default()).unwrap();
+    obj.generate_lods(2);
+    let obj = obj.model();
+    let path =
+        std::env::temp_dir().join(format!("test_read_mesh_file_{}.mesh", std::process::id()));
+    mesh_file::write(&path, &obj).unwrap();
+
+    let mesh_file = read_model(path.to_str().unwrap(), NormalStyle::default());
+    std::fs::remove_file(&path).unwrap();
+    let mesh_file = mesh_file.unwrap();
+    assert!(matches!(mesh_file, ModelFile::Mapped(_)));
+
+    let model = mesh_file.model();
+    assert!(matches!(model.vertices, Cow::Borrowed(_)));
+    assert_eq!(model.objects.len(), obj.objects.len());
+    assert_eq!(model.objects[0].name, obj.objects[0].name);
+    assert_eq!(model.objects[0].lods, obj.objects[0].lods);
+    assert_eq!(model.materials.len(), obj.materials.len());
+    assert_eq!(model.indices, obj.indices);
+    assert_eq!(
+        bytemuck::cast_slice::<Vertex, u8>(&model.vertices),
+        bytemuck::cast_slice::<Vertex, u8>(&obj.vertices)
+    );
+}
+
+#[test]
+fn test_triangle_mesh_missing_texcoords() {
+    let positions = [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+    let mesh = triangle_mesh(
+        ModelMatrixId(0),
+        MaterialId(0),
+        &positions,
+        &[0.0, 0.0, 1.0].repeat(3),
+        &[0.5, 0.5],
+        &[0, 1, 2],
+        NormalStyle::default(),
+    );
+    assert_eq!(mesh.vertices.len(), 3);
+    assert_eq!(<[f32; 2]>::from(mesh.vertices[0].uv), [0.5, 0.5]);
+    assert_eq!(<[f32; 2]>::from(mesh.vertices[2].uv), [0.0, 0.0]);
+}
+
+#[test]
+fn test_normal_styles_cube() {
+    // A unit cube centered on the origin, with 2 CCW triangles per side.
+    let mut positions = Vec::new();
+    for corner in 0..8 {
+        positions.extend([
+            if corner & 1 == 0 { -0.5 } else { 0.5 },
+            if corner & 2 == 0 { -0.5 } else { 0.5 },
+            if corner & 4 == 0 { -0.5 } else { 0.5 },
+        ]);
+    }
+    let sides: [[u32; 4]; 6] = [
+        [0, 4, 6, 2], // -x
+        [1, 3, 7, 5], // +x
+        [0, 1, 5, 4], // -y
+        [2, 6, 7, 3], // +y
+        [0, 2, 3, 1], // -z
+        [4, 5, 7, 6], // +z
+    ];
+    let indices: Vec<u32> = sides
+        .iter()
+        .flat_map(|[a, b, c, d]| [*a, *b, *c, *a, *c, *d])
+        .collect();
+
+    let is_axis = |normal: Vec3| {
+        let components = [normal.x.abs(), normal.y.abs(), normal.z.abs()];
+        components
+            .iter()
+            .filter(|component| **component > 0.999)
+            .count()
+            == 1
+    };
+    let uvs = vec![Vec2 { x: 0.0, y: 0.0 }; positions.len() / 3];
+    let mesh = |normal_style| {
+        extract_and_normalise(
+            ModelMatrixId(0),
+            MaterialId(0),
+            &positions,
+            &uvs,
+            &indices,
+            normal_style,
+        )
+    };
+
+    let face = mesh(NormalStyle::Face);
+    assert_eq!(face.vertices.len(), 36);
+    assert!(face.vertices.iter().all(|vertex| is_axis(vertex.normal)));
+
+    // Each corner touches 3 sides at right angles, so the corner normals point diagonally out.
+    let angle_weighted = mesh(NormalStyle::AngleWeighted);
+    assert_eq!(angle_weighted.vertices.len(), 8);
+    for vertex in angle_weighted.vertices.iter() {
+        let expected = Vec3::from(vertex.position).normalize();
+        assert!(vertex.normal.dot(expected) > 0.9999, "{:?}", vertex);
+    }
+
+    /* The sides' triangles are split differently at each corner, so area weighting and unweighted
+    averaging aren't exact.
+    */
+    for normal_style in [NormalStyle::AreaWeighted, NormalStyle::Unweighted] {
+        let weighted = mesh(normal_style);
+        assert_eq!(weighted.vertices.len(), 8);
+        for vertex in weighted.vertices.iter() {
+            assert!(vertex.normal.dot(Vec3::from(vertex.position)) > 0.0);
+        }
+    }
+
+    // Every edge is sharper than the crease angle, so each corner is split into 3 vertices.
+    let crease = mesh(NormalStyle::Crease {
+        crease_angle: 30_f32.to_radians(),
+    });
+    assert_eq!(crease.vertices.len(), 24);
+    assert!(crease.vertices.iter().all(|vertex| is_axis(vertex.normal)));
+}
+
+#[test]
+fn test_normal_styles_cylinder() {
+    // A closed cylinder along the y axis, with each cap's rim shared with the side.
+    let segments = 16;
+    let mut positions = Vec::new();
+    for y in [-1.0, 1.0] {
+        for segment in 0..segments {
+            let angle = segment as f32 / segments as f32 * std::f32::consts::TAU;
+            positions.extend([angle.cos(), y, -angle.sin()]);
         }
     }
+    let bottom = |segment: u32| segment % segments;
+    let top = |segment: u32| segments + segment % segments;
+
+    let mut indices = Vec::new();
+    for segment in 0..segments {
+        indices.extend([bottom(segment), bottom(segment + 1), top(segment + 1)]);
+        indices.extend([bottom(segment), top(segment + 1), top(segment)]);
+    }
+    for segment in 1..segments - 1 {
+        indices.extend([top(0), top(segment), top(segment + 1)]);
+        indices.extend([bottom(0), bottom(segment + 1), bottom(segment)]);
+    }
+
+    let uvs = vec![Vec2 { x: 0.0, y: 0.0 }; positions.len() / 3];
+    let mesh = |normal_style| {
+        extract_and_normalise(
+            ModelMatrixId(0),
+            MaterialId(0),
+            &positions,
+            &uvs,
+            &indices,
+            normal_style,
+        )
+    };
+
+    /* The side is smooth (each segment is 22.5° from its neighbours), and the caps are at 90° to
+    the side. The rim is split into a side vertex and a cap vertex.
+    */
+    let crease = mesh(NormalStyle::Crease {
+        crease_angle: 45_f32.to_radians(),
+    });
+    assert_eq!(crease.vertices.len(), 4 * segments as usize);
+    for vertex in crease.vertices.iter() {
+        let radial = Vec3 {
+            x: vertex.position.x,
+            y: 0.0,
+            z: vertex.position.z,
+        };
+        let cap = Vec3 {
+            x: 0.0,
+            y: vertex.position.y,
+            z: 0.0,
+        };
+        assert!(
+            vertex.normal.dot(radial) > 0.9999 || vertex.normal.dot(cap) > 0.9999,
+            "{:?}",
+            vertex
+        );
+    }
+
+    // Without a crease angle, the rim's normals bend towards the caps.
+    let angle_weighted = mesh(NormalStyle::AngleWeighted);
+    assert_eq!(angle_weighted.vertices.len(), 2 * segments as usize);
+    for vertex in angle_weighted.vertices.iter() {
+        assert!(vertex.normal.y.abs() > 0.1, "{:?}", vertex);
+        assert!(vertex.normal.y.signum() == vertex.position.y.signum());
+    }
 }
@@ -0,0 +1,1179 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    aabb::Aabb,
+    animation::{Curve, Interpolation, Keyframe},
+    color::Color,
+    error::{CapacityError, LoadError},
+    index_buffer::{IndexBuffer, IndexRange},
+    joint_matrices::{JointMatrices, JointMatrixId},
+    light::{DirectionalLightGpu, PointLightGpu, ShadowMapLightIds},
+    load::{self, LoadOptions, LoadedModel, LoadedObject, NormalStyle},
+    lod,
+    material::{Material, MaterialId, Materials},
+    matrix::Matrix4,
+    mesh::{self, Mesh},
+    model_matrices::{ModelMatrices, ModelMatrixId},
+    point::Point4,
+    scene_graph::{NodeId, SceneGraph},
+    skin::{
+        self, Joint, JointCurve, JointTrack, JointTransform, Skeleton, SkeletonAnimation,
+        SkinBounds,
+    },
+    vector::{Vec3, Vec4},
+    vertex_buffer::VertexBuffer,
+};
+
+/// The extensions of glTF files: JSON with separate or embedded buffers, and binary.
+pub const EXTENSIONS: [&str; 2] = ["gltf", "glb"];
+
+/// Whether the file's extension is one of [`EXTENSIONS`].
+pub fn is_gltf(file_name: &str) -> bool {
+    Path::new(file_name).extension().is_some_and(|extension| {
+        EXTENSIONS
+            .iter()
+            .any(|gltf| extension.eq_ignore_ascii_case(gltf))
+    })
+}
+
+/// A glTF scene that's been read into memory, but not loaded onto the GPU.
+pub struct GltfScene {
+    pub materials: Vec<Material>,
+
+    /// Parents come before their children.
+    pub nodes: Vec<GltfNode>,
+
+    pub skins: Vec<GltfSkin>,
+}
+
+pub struct GltfNode {
+    pub name: String,
+
+    /// The index of the node's parent in [`GltfScene::nodes`].
+    pub parent: Option<usize>,
+
+    /// Takes the node's coordinates to its parent's coordinates.
+    pub local_transform: Matrix4,
+
+    /// Takes the node's coordinates to the scene's coordinates. Includes the transforms of all
+    /// the node's ancestors.
+    pub transform: Matrix4,
+
+    pub primitives: Vec<GltfPrimitive>,
+    pub light: Option<GltfLight>,
+
+    /// The index of the skin that deforms the node's primitives in [`GltfScene::skins`].
+    pub skin: Option<usize>,
+}
+
+pub struct GltfPrimitive {
+    /// Flattened `xyz` triples.
+    pub positions: Vec<f32>,
+
+    /// Flattened `xyz` triples. Empty when the primitive doesn't have normals.
+    pub normals: Vec<f32>,
+
+    /// Flattened `uv` pairs from `TEXCOORD_0`. Empty when the primitive doesn't have texture
+    /// coordinates.
+    pub texcoords: Vec<f32>,
+
+    /// Every 3 indices are a triangle.
+    pub indices: Vec<u32>,
+
+    /// The index of the primitive's material in [`GltfScene::materials`].
+    pub material: usize,
+
+    /// Flattened quadruples from `JOINTS_0`, as indices in the node's skin's
+    /// [`Skeleton::joints`]. Empty when the primitive isn't skinned.
+    pub joints: Vec<u32>,
+
+    /// Flattened quadruples from `WEIGHTS_0`. Empty when the primitive isn't skinned.
+    pub weights: Vec<f32>,
+}
+
+/**
+A glTF skin, with the animations that move its joints.
+
+The skin's joints are nodes, and are also in [`GltfScene::nodes`], but their transforms there stay
+in the rest pose.
+*/
+pub struct GltfSkin {
+    /// The skeleton's joints are in the order of their nodes in [`GltfScene::nodes`].
+    pub skeleton: Skeleton,
+
+    /// The index of each joint's node in [`GltfScene::nodes`].
+    pub joint_nodes: Vec<usize>,
+
+    /// The index in [`GltfScene::nodes`] of the node that the skeleton's root joints are children
+    /// of. Its transform places the skeleton in the scene.
+    pub parent: Option<usize>,
+
+    /**
+    The channels of the file's animations that target the skin's joints, as one animation per
+    glTF animation.
+
+    glTF rotations are interpolated with slerp, but they're interpolated component-wise (and
+    normalised) here, which is close enough for the small rotations between keyframes. `CUBICSPLINE`
+    samplers keep their keyframes' values, but use Catmull-Rom tangents instead of the file's.
+    */
+    pub animations: Vec<SkeletonAnimation>,
+}
+
+/// A light from the [`KHR_lights_punctual`](https://github.com/KhronosGroup/glTF/blob/main/extensions/2.0/Khronos/KHR_lights_punctual/README.md)
+/// extension, converted to the units used by [`PointLightGpu`] and [`DirectionalLightGpu`].
+#[derive(Debug, Clone, Copy)]
+pub enum GltfLight {
+    Point {
+        color: Color,
+
+        /// Measured in lumens.
+        luminous_power: f32,
+    },
+    Directional {
+        color: Color,
+
+        /// Measured in lux.
+        illuminance: f32,
+    },
+}
+
+impl GltfScene {
+    pub fn read(file_name: &str) -> Result<Self, LoadError> {
+        let gltf_error = |error| LoadError::Gltf {
+            path: PathBuf::from(file_name),
+            error,
+        };
+        let gltf::Gltf { document, blob } = gltf::Gltf::open(file_name).map_err(gltf_error)?;
+        let buffers = gltf::import_buffers(&document, Path::new(file_name).parent(), blob)
+            .map_err(gltf_error)?;
+
+        let mut scene = GltfScene {
+            materials: document.materials().map(material_from_gltf).collect(),
+            nodes: Vec::new(),
+            skins: Vec::new(),
+        };
+
+        // Primitives without a material use the glTF default material, which is only added if
+        // it's needed.
+        let mut default_material: Option<usize> = None;
+
+        let gltf_scene = match document
+            .default_scene()
+            .or_else(|| document.scenes().next())
+        {
+            Some(gltf_scene) => gltf_scene,
+            None => return Ok(scene),
+        };
+
+        let mut stack: Vec<(Option<usize>, gltf::Node)> =
+            gltf_scene.nodes().map(|node| (None, node)).collect();
+        // Nodes are popped off the end of the stack, so reverse them to read them in order.
+        stack.reverse();
+
+        // The index of each glTF node in `scene.nodes`, and each node's glTF skin and transform.
+        let mut node_indices: HashMap<usize, usize> = HashMap::new();
+        let mut node_skins: Vec<Option<usize>> = Vec::new();
+        let mut rest_transforms: Vec<JointTransform> = Vec::new();
+
+        while let Some((parent, node)) = stack.pop() {
+            let local_transform = Matrix4::from(cgmath::Matrix4::from(node.transform().matrix()));
+            let transform = match parent {
+                Some(parent) => scene.nodes[parent].transform * local_transform,
+                None => local_transform,
+            };
+
+            let mut primitives = Vec::new();
+            if let Some(mesh) = node.mesh() {
+                for primitive in mesh.primitives() {
+                    if primitive.mode() != gltf::mesh::Mode::Triangles {
+                        log::warn!(
+                            "skipping {:?} primitive in mesh {:?} of {}",
+                            primitive.mode(),
+                            mesh.name(),
+                            file_name
+                        );
+                        continue;
+                    }
+
+                    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+
+                    let positions: Vec<f32> = match reader.read_positions() {
+                        Some(positions) => positions.flatten().collect(),
+                        None => continue,
+                    };
+
+                    let normals: Vec<f32> = match reader.read_normals() {
+                        Some(normals) => normals.flatten().collect(),
+                        None => Vec::new(),
+                    };
+
+                    // glTF texture coordinates start at the top left, like `Vertex::uv`.
+                    let texcoords: Vec<f32> = match reader.read_tex_coords(0) {
+                        Some(texcoords) => texcoords.into_f32().flatten().collect(),
+                        None => Vec::new(),
+                    };
+
+                    let indices: Vec<u32> = match reader.read_indices() {
+                        Some(indices) => indices.into_u32().collect(),
+                        None => (0..(positions.len() / 3) as u32).collect(),
+                    };
+
+                    let material = match primitive.material().index() {
+                        Some(index) => index,
+                        None => *default_material.get_or_insert_with(|| {
+                            scene
+                                .materials
+                                .push(material_from_gltf(primitive.material()));
+                            scene.materials.len() - 1
+                        }),
+                    };
+
+                    let joints: Vec<u32> = match reader.read_joints(0) {
+                        Some(joints) => joints.into_u16().flatten().map(u32::from).collect(),
+                        None => Vec::new(),
+                    };
+
+                    let weights: Vec<f32> = match reader.read_weights(0) {
+                        Some(weights) if !joints.is_empty() => {
+                            weights.into_f32().flatten().collect()
+                        }
+                        _ => Vec::new(),
+                    };
+
+                    primitives.push(GltfPrimitive {
+                        positions,
+                        normals,
+                        texcoords,
+                        indices,
+                        material,
+                        // Joints without weights don't move their vertices.
+                        joints: if weights.is_empty() {
+                            Vec::new()
+                        } else {
+                            joints
+                        },
+                        weights,
+                    });
+                }
+            }
+
+            let light = node.light().and_then(|light| match light.kind() {
+                gltf::khr_lights_punctual::Kind::Point => {
+                    let [r, g, b] = light.color();
+                    Some(GltfLight::Point {
+                        color: Color::from_linear(r, g, b, 1.0),
+                        // Point light intensity is measured in candela (lm/sr), and a point light
+                        // emits over 4π steradians.
+                        luminous_power: 4.0 * std::f32::consts::PI * light.intensity(),
+                    })
+                }
+                gltf::khr_lights_punctual::Kind::Directional => {
+                    let [r, g, b] = light.color();
+                    Some(GltfLight::Directional {
+                        color: Color::from_linear(r, g, b, 1.0),
+                        illuminance: light.intensity(),
+                    })
+                }
+                gltf::khr_lights_punctual::Kind::Spot { .. } => {
+                    log::warn!(
+                        "skipping spot light {:?} in {}: spot lights aren't supported",
+                        light.name(),
+                        file_name
+                    );
+                    None
+                }
+            });
+
+            let index = scene.nodes.len();
+            node_indices.insert(node.index(), index);
+            node_skins.push(node.skin().map(|skin| skin.index()));
+            let ([tx, ty, tz], [x, y, z, w], [sx, sy, sz]) = node.transform().decomposed();
+            rest_transforms.push(JointTransform {
+                translation: Vec3 {
+                    x: tx,
+                    y: ty,
+                    z: tz,
+                },
+                rotation: Vec4 { x, y, z, w },
+                scale: Vec3 {
+                    x: sx,
+                    y: sy,
+                    z: sz,
+                },
+            });
+            scene.nodes.push(GltfNode {
+                name: node
+                    .name()
+                    .map_or_else(|| format!("node_{}", node.index()), String::from),
+                parent,
+                local_transform,
+                transform,
+                primitives,
+                light,
+                skin: None,
+            });
+
+            let mut children: Vec<(Option<usize>, gltf::Node)> =
+                node.children().map(|child| (Some(index), child)).collect();
+            children.reverse();
+            stack.extend(children);
+        }
+
+        // The index of each readable glTF skin in `scene.skins`.
+        let mut skin_indices: HashMap<usize, usize> = HashMap::new();
+
+        for gltf_skin in document.skins() {
+            // The skin's joints in the order of the file, which is the order that `JOINTS_0`
+            // refers to them in.
+            let file_joint_nodes: Option<Vec<usize>> = gltf_skin
+                .joints()
+                .map(|joint| node_indices.get(&joint.index()).copied())
+                .collect();
+            let Some(file_joint_nodes) = file_joint_nodes else {
+                log::warn!(
+                    "skipping skin {:?} in {}: some of its joints aren't in the scene",
+                    gltf_skin.name(),
+                    file_name
+                );
+                continue;
+            };
+
+            let reader = gltf_skin.reader(|buffer| Some(&buffers[buffer.index()]));
+            let inverse_bind_matrices: Vec<Matrix4> = match reader.read_inverse_bind_matrices() {
+                Some(matrices) => matrices
+                    .map(|matrix| Matrix4::from(cgmath::Matrix4::from(matrix)))
+                    .collect(),
+                None => vec![Matrix4::IDENTITY; file_joint_nodes.len()],
+            };
+            if inverse_bind_matrices.len() != file_joint_nodes.len() {
+                return Err(LoadError::Invalid {
+                    path: PathBuf::from(file_name),
+                    reason: format!(
+                        "skin {:?} has {} joints but {} inverse bind matrices",
+                        gltf_skin.name(),
+                        file_joint_nodes.len(),
+                        inverse_bind_matrices.len()
+                    ),
+                });
+            }
+
+            // Sorting the joints by node puts parents before their children.
+            let mut joint_nodes = file_joint_nodes.clone();
+            joint_nodes.sort_unstable();
+            joint_nodes.dedup();
+            let joint_index = |node: usize| joint_nodes.binary_search(&node).ok();
+
+            let joints: Vec<Joint> = joint_nodes
+                .iter()
+                .map(|&node| {
+                    let file_joint = file_joint_nodes
+                        .iter()
+                        .position(|file_node| *file_node == node)
+                        .unwrap();
+                    Joint {
+                        name: scene.nodes[node].name.clone(),
+                        parent: scene.nodes[node].parent.and_then(joint_index),
+                        rest_transform: rest_transforms[node],
+                        inverse_bind_matrix: inverse_bind_matrices[file_joint],
+                    }
+                })
+                .collect();
+
+            let parent = joint_nodes
+                .first()
+                .and_then(|&node| scene.nodes[node].parent);
+            if joints
+                .iter()
+                .zip(&joint_nodes)
+                .any(|(joint, &node)| joint.parent.is_none() && scene.nodes[node].parent != parent)
+            {
+                log::warn!(
+                    "skin {:?} in {} has root joints with different parents, they'll all be placed under the first one's parent",
+                    gltf_skin.name(),
+                    file_name
+                );
+            }
+
+            // `JOINTS_0` refers to joints in the order of the file.
+            for (node, node_skin) in scene.nodes.iter_mut().zip(&node_skins) {
+                if *node_skin != Some(gltf_skin.index()) {
+                    continue;
+                }
+                for primitive in node.primitives.iter_mut() {
+                    for joint in primitive.joints.iter_mut() {
+                        *joint = file_joint_nodes
+                            .get(*joint as usize)
+                            .and_then(|&node| joint_index(node))
+                            .ok_or_else(|| LoadError::Invalid {
+                                path: PathBuf::from(file_name),
+                                reason: format!(
+                                    "a primitive of {:?} refers to joint {}, but its skin only has {}",
+                                    node.name,
+                                    joint,
+                                    file_joint_nodes.len()
+                                ),
+                            })? as u32;
+                    }
+                }
+            }
+
+            let animations = document
+                .animations()
+                .filter_map(|animation| {
+                    let tracks: Vec<JointTrack> = animation
+                        .channels()
+                        .filter_map(|channel| {
+                            let joint = node_indices
+                                .get(&channel.target().node().index())
+                                .and_then(|&node| joint_index(node))?;
+                            let curve = joint_curve(&channel, &buffers)?;
+                            Some(JointTrack { joint, curve })
+                        })
+                        .collect();
+                    (!tracks.is_empty()).then(|| SkeletonAnimation {
+                        name: animation.name().map_or_else(
+                            || format!("animation_{}", animation.index()),
+                            String::from,
+                        ),
+                        tracks,
+                    })
+                })
+                .collect();
+
+            skin_indices.insert(gltf_skin.index(), scene.skins.len());
+            scene.skins.push(GltfSkin {
+                skeleton: Skeleton { joints },
+                joint_nodes,
+                parent,
+                animations,
+            });
+        }
+
+        for (node, node_skin) in scene.nodes.iter_mut().zip(node_skins) {
+            node.skin = node_skin.and_then(|skin| skin_indices.get(&skin).copied());
+            if node.skin.is_none() {
+                // Primitives that can't be skinned are drawn in their rest pose.
+                for primitive in node.primitives.iter_mut() {
+                    primitive.joints.clear();
+                    primitive.weights.clear();
+                }
+            }
+        }
+
+        Ok(scene)
+    }
+}
+
+fn material_from_gltf(material: gltf::Material) -> Material {
+    let pbr = material.pbr_metallic_roughness();
+    let [r, g, b, a] = pbr.base_color_factor();
+    Material {
+        // glTF colors are linear.
+        color: Color::from_linear(r, g, b, a),
+        roughness: pbr.roughness_factor(),
+        metallic: pbr.metallic_factor(),
+        _padding: [0, 0],
+    }
+}
+
+/// The curve of an animation channel that moves a node, or `None` for morph target weights.
+fn joint_curve(
+    channel: &gltf::animation::Channel,
+    buffers: &[gltf::buffer::Data],
+) -> Option<JointCurve> {
+    let reader = channel.reader(|buffer| Some(&buffers[buffer.index()]));
+    let times: Vec<f32> = reader.read_inputs()?.collect();
+    let interpolation = channel.sampler().interpolation();
+
+    match reader.read_outputs()? {
+        gltf::animation::util::ReadOutputs::Translations(translations) => {
+            Some(JointCurve::Translation(curve(
+                interpolation,
+                &times,
+                translations.map(|[x, y, z]| Vec3 { x, y, z }),
+            )))
+        }
+        gltf::animation::util::ReadOutputs::Rotations(rotations) => {
+            Some(JointCurve::Rotation(curve(
+                interpolation,
+                &times,
+                rotations.into_f32().map(|[x, y, z, w]| Vec4 { x, y, z, w }),
+            )))
+        }
+        gltf::animation::util::ReadOutputs::Scales(scales) => Some(JointCurve::Scale(curve(
+            interpolation,
+            &times,
+            scales.map(|[x, y, z]| Vec3 { x, y, z }),
+        ))),
+        gltf::animation::util::ReadOutputs::MorphTargetWeights(_) => None,
+    }
+}
+
+fn curve<T>(
+    interpolation: gltf::animation::Interpolation,
+    times: &[f32],
+    values: impl Iterator<Item = T>,
+) -> Curve<T> {
+    let (interpolation, values): (Interpolation, Vec<T>) = match interpolation {
+        gltf::animation::Interpolation::Step => (Interpolation::Step, values.collect()),
+        gltf::animation::Interpolation::Linear => (Interpolation::Linear, values.collect()),
+        // Each keyframe has an in-tangent, a value and an out-tangent. Only the values are kept.
+        gltf::animation::Interpolation::CubicSpline => {
+            (Interpolation::Cubic, values.skip(1).step_by(3).collect())
+        }
+    };
+    Curve {
+        interpolation,
+        keyframes: times
+            .iter()
+            .zip(values)
+            .map(|(&time, value)| Keyframe { time, value })
+            .collect(),
+    }
+}
+
+/// The result of [`insert_gltf`].
+#[derive(Default)]
+pub struct LoadedGltf {
+    /// A node in the scene graph for each of the scene's nodes, in the order of
+    /// [`GltfScene::nodes`].
+    pub nodes: Vec<NodeId>,
+
+    /**
+    The scene's nodes that have meshes, and what they use of the buffers. Their meshes are uploaded
+    together, like a [`Model`](load::Model)'s.
+
+    The objects' bounds are in world space as of when they were loaded. The scene graph has their
+    current bounds.
+    */
+    pub model: LoadedModel,
+
+    /**
+    The node of each of the model's objects, in the same order. It's the object's node in
+    [`LoadedGltf::nodes`], except for skinned meshes, which have a node of their own under their
+    skeleton's parent (see [`LoadedSkin::parent`]).
+    */
+    pub object_nodes: Vec<NodeId>,
+
+    /**
+    The scene's point lights.
+
+    Shadow maps aren't allocated for the lights, so [`PointLightGpu::shadow_map_light_ids`]
+    needs to be filled in before they're added to the point light buffer.
+    */
+    pub point_lights: Vec<PointLightGpu>,
+
+    /**
+    The scene's directional lights.
+
+    Shadow maps aren't allocated for the lights, so [`DirectionalLightGpu::shadow_map_light_id`]
+    needs to be filled in before they're added to the directional light buffer.
+    */
+    pub directional_lights: Vec<DirectionalLightGpu>,
+
+    /// The scene's skins, in the order of [`GltfScene::skins`].
+    pub skins: Vec<LoadedSkin>,
+}
+
+impl LoadedGltf {
+    /**
+    Remove everything that was inserted for the scene: its model matrices (including the point
+    lights'), joint matrices, materials, vertices and indices.
+
+    The scene's nodes stay in the graph, because nodes can't be removed, but they lose their model
+    matrices and bounds.
+    */
+    #[allow(clippy::too_many_arguments)]
+    pub fn remove(
+        &self,
+        queue: &wgpu::Queue,
+        model_matrices: &mut ModelMatrices,
+        joint_matrices: &mut JointMatrices,
+        materials: &mut Materials,
+        vertex_buffer: &mut VertexBuffer,
+        index_buffer: &mut IndexBuffer,
+        graph: &mut SceneGraph,
+    ) {
+        // Point lights that share a node with a mesh share its model matrix.
+        let mut model_matrix_ids: Vec<ModelMatrixId> = self
+            .model
+            .objects
+            .iter()
+            .map(|object| object.model_matrix_id)
+            .chain(self.point_lights.iter().map(|light| light.model_matrix_id))
+            .collect();
+        model_matrix_ids.sort_unstable_by_key(|id| id.0);
+        model_matrix_ids.dedup();
+        for model_matrix_id in model_matrix_ids {
+            model_matrices.remove(queue, model_matrix_id);
+        }
+
+        for skin in &self.skins {
+            for joint_matrix_id in &skin.joint_matrix_ids {
+                joint_matrices.remove(queue, *joint_matrix_id);
+            }
+        }
+        for material_id in &self.model.material_ids {
+            materials.remove(queue, *material_id);
+        }
+        vertex_buffer.remove(self.model.vertices.clone());
+        index_buffer.remove(self.model.indices.clone());
+
+        for node in self.nodes.iter().chain(&self.object_nodes) {
+            graph.set_model_matrix(*node, None);
+            graph.set_bounds(*node, None);
+        }
+    }
+}
+
+/// A skin whose joint matrices are in [`JointMatrices`].
+pub struct LoadedSkin {
+    pub skeleton: Skeleton,
+    pub animations: Vec<SkeletonAnimation>,
+
+    /**
+    A joint matrix for each of the skeleton's joints, in the order of [`Skeleton::joints`]. They
+    take skinned vertices to the coordinates of the skeleton's parent, and the skinned meshes'
+    model matrices take them from there to world space.
+    */
+    pub joint_matrix_ids: Vec<JointMatrixId>,
+
+    /**
+    The scene graph node that places the skeleton in the scene (see [`GltfSkin::parent`]). The
+    meshes that it skins have nodes under it, so that their model matrices follow it.
+    */
+    pub parent: Option<NodeId>,
+
+    /// The node of each mesh that the skin moves, and the bounds that give the mesh's bounds in
+    /// each pose.
+    pub meshes: Vec<(NodeId, SkinBounds)>,
+}
+
+impl LoadedSkin {
+    /**
+    Pose the skeleton at `time` in one of its animations, or in its rest pose when `animation` is
+    `None`, and write its joint matrices.
+
+    The bounds of the skin's meshes are set for the pose, and change on the next
+    [`SceneGraph::update`].
+    */
+    pub fn update(
+        &self,
+        queue: &wgpu::Queue,
+        graph: &mut SceneGraph,
+        joint_matrices: &mut JointMatrices,
+        animation: Option<usize>,
+        time: f32,
+    ) {
+        let palette = self.palette(animation, time);
+        for (node, bounds) in &self.meshes {
+            graph.set_bounds(*node, bounds.posed(&palette));
+        }
+        for (id, matrix) in self.joint_matrix_ids.iter().zip(palette) {
+            joint_matrices.update(queue, *id, matrix);
+        }
+    }
+
+    fn palette(&self, animation: Option<usize>, time: f32) -> Vec<Matrix4> {
+        let pose = match animation {
+            Some(animation) => self.animations[animation].pose(&self.skeleton, time),
+            None => self.skeleton.rest_pose(),
+        };
+        skin::joint_palette(&self.skeleton, &pose, Matrix4::IDENTITY)
+    }
+}
+
+/// Read a glTF file and load it onto the GPU. See [`GltfScene::read`] and [`insert_gltf`].
+#[allow(clippy::too_many_arguments)]
+pub fn load_gltf(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    model_matrices: &mut ModelMatrices,
+    joint_matrices: &mut JointMatrices,
+    materials: &mut Materials,
+    vertex_buffer: &mut VertexBuffer,
+    index_buffer: &mut IndexBuffer,
+    graph: &mut SceneGraph,
+    parent: Option<NodeId>,
+    file_name: &str,
+    options: &LoadOptions,
+) -> Result<LoadedGltf, LoadError> {
+    let scene = GltfScene::read(file_name)?;
+    insert_gltf(
+        device,
+        queue,
+        model_matrices,
+        joint_matrices,
+        materials,
+        vertex_buffer,
+        index_buffer,
+        graph,
+        parent,
+        &scene,
+        options,
+    )
+    .map_err(|error| LoadError::Capacity {
+        path: PathBuf::from(file_name),
+        error,
+    })
+}
+
+/**
+Load a glTF scene onto the GPU, adding its node hierarchy to `graph` under `parent`.
+
+Nodes with meshes get model matrices and bounds, and point lights that don't share a node with a
+mesh get a model matrix of their own, so they all follow their nodes when the graph is updated. The
+graph is updated before this returns.
+
+Skins get joint matrices for their rest pose, which skinned vertices are blended with before their
+model matrix is applied. A skinned mesh's model matrix follows its skeleton's parent instead of its
+own node, because glTF places skinned meshes with their skeletons. Skinned meshes' bounds are for
+the rest pose until their skins are posed with [`LoadedSkin::update`].
+
+Only `options`' [`material`](LoadOptions::material), [`normal_style`](LoadOptions::normal_style)
+(for primitives without normals) and [`lod_levels`](LoadOptions::lod_levels) are used.
+
+When this fails, whatever was inserted is removed again (see [`LoadedGltf::remove`]).
+*/
+#[allow(clippy::too_many_arguments)]
+pub fn insert_gltf(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    model_matrices: &mut ModelMatrices,
+    joint_matrices: &mut JointMatrices,
+    materials: &mut Materials,
+    vertex_buffer: &mut VertexBuffer,
+    index_buffer: &mut IndexBuffer,
+    graph: &mut SceneGraph,
+    parent: Option<NodeId>,
+    scene: &GltfScene,
+    options: &LoadOptions,
+) -> Result<LoadedGltf, CapacityError> {
+    let mut loaded = LoadedGltf::default();
+    match insert_gltf_into(
+        device,
+        queue,
+        model_matrices,
+        joint_matrices,
+        materials,
+        vertex_buffer,
+        index_buffer,
+        graph,
+        parent,
+        scene,
+        options,
+        &mut loaded,
+    ) {
+        Ok(()) => Ok(loaded),
+        Err(error) => {
+            loaded.remove(
+                queue,
+                model_matrices,
+                joint_matrices,
+                materials,
+                vertex_buffer,
+                index_buffer,
+                graph,
+            );
+            Err(error)
+        }
+    }
+}
+
+/// [`insert_gltf`], recording everything that's inserted in `loaded` as it goes.
+#[allow(clippy::too_many_arguments)]
+fn insert_gltf_into(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    model_matrices: &mut ModelMatrices,
+    joint_matrices: &mut JointMatrices,
+    materials: &mut Materials,
+    vertex_buffer: &mut VertexBuffer,
+    index_buffer: &mut IndexBuffer,
+    graph: &mut SceneGraph,
+    parent: Option<NodeId>,
+    scene: &GltfScene,
+    options: &LoadOptions,
+    loaded: &mut LoadedGltf,
+) -> Result<(), CapacityError> {
+    // Vertices refer to materials by their index in the scene (see `Instance::material_count`).
+    let (material_id, material_count) = match options.material {
+        Some(material_id) => (material_id, 1),
+        None => {
+            loaded.model.material_ids = materials.insert_many(device, queue, &scene.materials)?;
+            (
+                loaded
+                    .model
+                    .material_ids
+                    .first()
+                    .copied()
+                    .unwrap_or(MaterialId(0)),
+                loaded.model.material_ids.len() as u32,
+            )
+        }
+    };
+
+    // Point lights that share a node with a mesh share its model matrix.
+    let mut node_model_matrix_ids = HashMap::new();
+
+    for node in scene.nodes.iter() {
+        let parent = match node.parent {
+            Some(parent) => Some(loaded.nodes[parent]),
+            None => parent,
+        };
+        loaded.nodes.push(graph.add(parent, node.local_transform));
+    }
+    graph.update(queue, model_matrices);
+
+    for gltf_skin in &scene.skins {
+        let mut skin = LoadedSkin {
+            skeleton: gltf_skin.skeleton.clone(),
+            animations: gltf_skin.animations.clone(),
+            joint_matrix_ids: Vec::new(),
+            parent: match gltf_skin.parent {
+                Some(node) => Some(loaded.nodes[node]),
+                None => parent,
+            },
+            meshes: Vec::new(),
+        };
+        let palette = skin.palette(None, 0.0);
+        let mut joint_matrix_ids = Vec::with_capacity(palette.len());
+        let result = palette.into_iter().try_for_each(|matrix| {
+            joint_matrix_ids.push(joint_matrices.insert(device, queue, matrix)?);
+            Ok(())
+        });
+        skin.joint_matrix_ids = joint_matrix_ids;
+        loaded.skins.push(skin);
+        result?;
+    }
+
+    // The nodes' meshes are uploaded together, so each node's levels of detail are offset by where
+    // its indices are in the combined mesh.
+    let mut scene_mesh = Mesh {
+        vertices: Vec::new(),
+        indices: Vec::new(),
+    };
+    for (index, node) in scene.nodes.iter().enumerate() {
+        let graph_node = loaded.nodes[index];
+        let node_transform = graph.world_transform(graph_node);
+
+        if !node.primitives.is_empty() {
+            let (object_node, object_transform) = match node.skin {
+                Some(skin) => {
+                    let skeleton_parent = loaded.skins[skin].parent;
+                    (
+                        graph.add(skeleton_parent, Matrix4::IDENTITY),
+                        skeleton_parent
+                            .map_or(Matrix4::IDENTITY, |parent| graph.world_transform(parent)),
+                    )
+                }
+                None => (graph_node, node_transform),
+            };
+            let model_matrix_id = model_matrices.insert(device, queue, object_transform)?;
+            if node.skin.is_none() {
+                node_model_matrix_ids.insert(index, model_matrix_id);
+            }
+            graph.set_model_matrix(object_node, Some(model_matrix_id));
+            loaded.object_nodes.push(object_node);
+
+            // The node's primitives are combined, so that they share levels of detail.
+            let mut node_mesh = Mesh {
+                vertices: Vec::new(),
+                indices: Vec::new(),
+            };
+            let mut skin_bounds = node
+                .skin
+                .map(|skin| SkinBounds::new(loaded.skins[skin].joint_matrix_ids.len()));
+            for primitive in &node.primitives {
+                /* Joints are per position, so skinned vertices can't be split. The normal styles
+                that split vertices fall back to the default, which keeps a vertex for each position.
+                */
+                let normal_style = match options.normal_style {
+                    NormalStyle::Face | NormalStyle::Crease { .. } if node.skin.is_some() => {
+                        NormalStyle::default()
+                    }
+                    normal_style => normal_style,
+                };
+                let mut mesh = load::triangle_mesh(
+                    model_matrix_id,
+                    MaterialId(primitive.material as u32),
+                    &primitive.positions,
+                    &primitive.normals,
+                    &primitive.texcoords,
+                    &primitive.indices,
+                    normal_style,
+                );
+
+                if let (Some(skin), Some(skin_bounds)) = (node.skin, &mut skin_bounds) {
+                    let joint_matrix_ids = &loaded.skins[skin].joint_matrix_ids;
+                    for ((vertex, joints), weights) in mesh
+                        .vertices
+                        .iter_mut()
+                        .zip(primitive.joints.chunks(4))
+                        .zip(primitive.weights.chunks(4))
+                    {
+                        let joints = std::array::from_fn(|corner| joints[corner] as usize);
+                        vertex.joints = joints.map(|joint| joint_matrix_ids[joint].0);
+                        vertex.weights = Vec4 {
+                            x: weights[0],
+                            y: weights[1],
+                            z: weights[2],
+                            w: weights[3],
+                        };
+                        skin_bounds.add(vertex.position, joints, vertex.weights);
+                    }
+                }
+                let base_vertex = node_mesh.vertices.len() as u32;
+                node_mesh
+                    .indices
+                    .extend(mesh.indices.iter().map(|index| base_vertex + index));
+                node_mesh.vertices.extend(mesh.vertices);
+            }
+
+            let mut lods = vec![IndexRange {
+                first_index: 0,
+                index_count: node_mesh.indices.len() as u32,
+            }];
+            lod::add_lods(
+                &node_mesh.vertices,
+                &mut node_mesh.indices,
+                &mut lods,
+                options.lod_levels,
+            );
+            // Skinned meshes' bounds are for the pose that their skin is in.
+            let bounds = match (node.skin, skin_bounds) {
+                (Some(skin), Some(skin_bounds)) => {
+                    let skin = &mut loaded.skins[skin];
+                    let bounds = skin_bounds
+                        .posed(&skin.palette(None, 0.0))
+                        .unwrap_or(Aabb::EMPTY);
+                    skin.meshes.push((object_node, skin_bounds));
+                    bounds
+                }
+                _ => node_mesh.aabb(),
+            };
+            graph.set_bounds(object_node, Some(bounds));
+
+            let base_vertex = scene_mesh.vertices.len() as u32;
+            let first_index = scene_mesh.indices.len() as u32;
+            scene_mesh
+                .indices
+                .extend(node_mesh.indices.iter().map(|index| base_vertex + index));
+            scene_mesh
+                .vertices
+                .extend(node_mesh.vertices.iter().copied());
+
+            loaded.model.objects.push(LoadedObject {
+                name: node.name.clone(),
+                model_matrix_id,
+                // A node's primitives can have different materials.
+                material_id,
+                material_count,
+                aabb: bounds.transform(object_transform),
+                lods: lods
+                    .iter()
+                    .map(|lod| IndexRange {
+                        first_index: first_index + lod.first_index,
+                        index_count: lod.index_count,
+                    })
+                    .collect(),
+            });
+        }
+
+        match node.light {
+            Some(GltfLight::Point {
+                color,
+                luminous_power,
+            }) => {
+                let model_matrix_id = match node_model_matrix_ids.get(&index) {
+                    Some(model_matrix_id) => *model_matrix_id,
+                    None => {
+                        let model_matrix_id =
+                            model_matrices.insert(device, queue, node_transform)?;
+                        graph.set_model_matrix(graph_node, Some(model_matrix_id));
+                        model_matrix_id
+                    }
+                };
+                loaded.point_lights.push(PointLightGpu {
+                    model_matrix_id,
+                    _padding0: [0, 0, 0],
+                    color,
+                    luminous_power,
+                    shadow_map_light_ids: ShadowMapLightIds {
+                        x: 0,
+                        neg_x: 0,
+                        y: 0,
+                        neg_y: 0,
+                        z: 0,
+                        neg_z: 0,
+                    },
+                });
+            }
+            Some(GltfLight::Directional { color, illuminance }) => {
+                loaded.directional_lights.push(DirectionalLightGpu {
+                    color,
+                    direction: light_direction(node_transform),
+                    illuminance,
+                    shadow_map_light_id: 0,
+                });
+            }
+            None => {}
+        }
+    }
+
+    let (vertices, index_range) = mesh::insert(
+        device,
+        queue,
+        vertex_buffer,
+        index_buffer,
+        &scene_mesh.vertices,
+        &scene_mesh.indices,
+    )?;
+    loaded.model.vertices = vertices;
+    loaded.model.indices = index_range.first_index as u64
+        ..index_range.first_index as u64 + index_range.index_count as u64;
+    for object in &mut loaded.model.objects {
+        for lod in &mut object.lods {
+            lod.first_index += index_range.first_index;
+        }
+    }
+    graph.update(queue, model_matrices);
+
+    Ok(())
+}
+
+/// glTF lights point along their node's -Z axis.
+fn light_direction(transform: Matrix4) -> Vec3 {
+    let direction = transform
+        * Point4 {
+            x: 0.0,
+            y: 0.0,
+            z: -1.0,
+            w: 0.0,
+        };
+    Vec3 {
+        x: direction.x,
+        y: direction.y,
+        z: direction.z,
+    }
+    .normalize()
+}
+
+#[test]
+fn test_read_gltf_scene() {
+    use crate::point::Point3;
+
+    let scene = GltfScene::read("models/test_scene.gltf").unwrap();
+
+    let names: Vec<&str> = scene.nodes.iter().map(|node| node.name.as_str()).collect();
+    assert_eq!(names, ["base", "arm", "lamp", "sun"]);
+
+    let arm = &scene.nodes[1];
+    assert_eq!(arm.parent, Some(0));
+    let arm_local_position = Point3::from(arm.local_transform * Point3::ZERO.with_w(1.0));
+    assert_eq!(
+        (
+            arm_local_position.x,
+            arm_local_position.y,
+            arm_local_position.z
+        ),
+        (0.0, 2.0, 0.0)
+    );
+    let arm_position = Point3::from(arm.transform * Point3::ZERO.with_w(1.0));
+    assert_eq!(
+        (arm_position.x, arm_position.y, arm_position.z),
+        (0.0, 2.0, -5.0)
+    );
+
+    // The quad has its own normals and indices, and the triangle has neither.
+    let quad = &scene.nodes[0].primitives[0];
+    assert_eq!(quad.indices, [0, 2, 1, 0, 3, 2]);
+    assert_eq!(quad.normals.len(), quad.positions.len());
+    let triangle = &arm.primitives[0];
+    assert_eq!(triangle.indices, [0, 1, 2]);
+    assert!(triangle.normals.is_empty());
+
+    // The triangle uses the default material, which is added after the file's materials.
+    assert_eq!(scene.materials.len(), 2);
+    assert_eq!(quad.material, 0);
+    assert_eq!(triangle.material, 1);
+    assert_eq!(scene.materials[0].roughness, 0.4);
+    assert_eq!(scene.materials[0].metallic, 1.0);
+    assert_eq!(scene.materials[1].roughness, 1.0);
+
+    match scene.nodes[2].light {
+        Some(GltfLight::Point { luminous_power, .. }) => {
+            assert!((luminous_power - 400.0 * std::f32::consts::PI).abs() < 1e-3);
+        }
+        light => panic!("expected a point light, got {:?}", light),
+    }
+
+    match scene.nodes[3].light {
+        Some(GltfLight::Directional { illuminance, .. }) => {
+            assert_eq!(illuminance, 10.0);
+            let direction = light_direction(scene.nodes[3].transform);
+            assert!((direction.y + 1.0).abs() < 1e-6, "{:?}", direction);
+        }
+        light => panic!("expected a directional light, got {:?}", light),
+    }
+}
+
+#[test]
+fn test_read_gltf_skin() {
+    use crate::point::Point3;
+
+    let scene = GltfScene::read("models/test_skin.gltf").unwrap();
+    assert_eq!(scene.skins.len(), 1);
+    assert_eq!(scene.nodes[0].skin, Some(0));
+
+    // The file lists the elbow before the shoulder, but the skeleton has parents first.
+    let skin = &scene.skins[0];
+    let names: Vec<&str> = skin
+        .skeleton
+        .joints
+        .iter()
+        .map(|joint| joint.name.as_str())
+        .collect();
+    assert_eq!(names, ["shoulder", "elbow"]);
+    assert_eq!(skin.skeleton.joints[0].parent, None);
+    assert_eq!(skin.skeleton.joints[1].parent, Some(0));
+    assert_eq!(skin.joint_nodes, [1, 2]);
+    assert_eq!(skin.parent, None);
+
+    // The joints are remapped to the skeleton's order, including the ones with no weight.
+    let primitive = &scene.nodes[0].primitives[0];
+    assert_eq!(&primitive.joints[..4], [0, 1, 1, 1]);
+    assert_eq!(&primitive.joints[16..20], [1, 1, 1, 1]);
+    assert_eq!(&primitive.weights[8..12], [0.5, 0.5, 0.0, 0.0]);
+
+    // The shoulder turns 90° around Z over a second, swinging the tip of the arm round to -X.
+    assert_eq!(skin.animations.len(), 1);
+    let animation = &skin.animations[0];
+    assert_eq!(animation.name, "wave");
+    assert_eq!(animation.duration(), 1.0);
+
+    let tip = Point3 {
+        x: 0.0,
+        y: 2.0,
+        z: 0.0,
+    };
+    for (time, expected) in [(0.0, (0.0, 2.0, -3.0)), (1.0, (-2.0, 0.0, -3.0))] {
+        let palette = skin::joint_palette(
+            &skin.skeleton,
+            &animation.pose(&skin.skeleton, time),
+            Matrix4::IDENTITY,
+        );
+        let position = Point3::from(palette[1] * tip.with_w(1.0));
+        assert!(
+            (position.x - expected.0).abs() < 1e-5
+                && (position.y - expected.1).abs() < 1e-5
+                && (position.z - expected.2).abs() < 1e-5,
+            "{:?} at {}s",
+            position,
+            time
+        );
+    }
+}
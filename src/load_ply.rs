@@ -0,0 +1,376 @@
+/*! [PLY](https://paulbourke.net/dataformats/ply/) mesh import.
+
+Reads ASCII and binary (little and big endian) files. Only the `vertex` element's `x`, `y`, `z`
+and optional `nx`, `ny`, `nz` properties, and the `face` element's `vertex_indices` (or
+`vertex_index`) list are used. Other elements and properties are skipped.
+*/
+
+use std::path::{Path, PathBuf};
+
+use crate::{
+    error::LoadError,
+    load::{self, Model, NormalStyle, RawMesh},
+};
+
+/// Read a `.ply` file as a model with a single object.
+pub fn read_ply(file_name: &str, normal_style: NormalStyle) -> Result<Model<'static>, LoadError> {
+    let bytes = std::fs::read(file_name).map_err(|error| LoadError::Io {
+        path: PathBuf::from(file_name),
+        error,
+    })?;
+    let mesh = parse_ply(&bytes).map_err(|reason| LoadError::Invalid {
+        path: PathBuf::from(file_name),
+        reason,
+    })?;
+    Ok(load::single_object_model(
+        &load::file_stem(Path::new(file_name)),
+        mesh,
+        normal_style,
+    ))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ScalarType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl ScalarType {
+    fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "char" | "int8" => Ok(ScalarType::I8),
+            "uchar" | "uint8" => Ok(ScalarType::U8),
+            "short" | "int16" => Ok(ScalarType::I16),
+            "ushort" | "uint16" => Ok(ScalarType::U16),
+            "int" | "int32" => Ok(ScalarType::I32),
+            "uint" | "uint32" => Ok(ScalarType::U32),
+            "float" | "float32" => Ok(ScalarType::F32),
+            "double" | "float64" => Ok(ScalarType::F64),
+            _ => Err(format!("unknown property type {:?}", name)),
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            ScalarType::I8 | ScalarType::U8 => 1,
+            ScalarType::I16 | ScalarType::U16 => 2,
+            ScalarType::I32 | ScalarType::U32 | ScalarType::F32 => 4,
+            ScalarType::F64 => 8,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Property {
+    Scalar {
+        name: String,
+        scalar_type: ScalarType,
+    },
+    List {
+        name: String,
+        count_type: ScalarType,
+        item_type: ScalarType,
+    },
+}
+
+impl Property {
+    fn name(&self) -> &str {
+        match self {
+            Property::Scalar { name, .. } | Property::List { name, .. } => name,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+/// The part of the file after `end_header`.
+enum Body<'a> {
+    Ascii(std::str::SplitAsciiWhitespace<'a>),
+    Binary { bytes: &'a [u8], big_endian: bool },
+}
+
+impl<'a> Body<'a> {
+    fn read(&mut self, scalar_type: ScalarType) -> Result<f64, String> {
+        match self {
+            Body::Ascii(tokens) => {
+                let token = tokens.next().ok_or("unexpected end of file")?;
+                token
+                    .parse()
+                    .map_err(|_| format!("invalid number {:?}", token))
+            }
+            Body::Binary { bytes, big_endian } => {
+                let size = scalar_type.size();
+                if bytes.len() < size {
+                    return Err(String::from("unexpected end of file"));
+                }
+                let (value, rest) = bytes.split_at(size);
+                *bytes = rest;
+
+                let mut buffer = [0; 8];
+                buffer[..size].copy_from_slice(value);
+                if *big_endian {
+                    buffer[..size].reverse();
+                }
+                Ok(match scalar_type {
+                    ScalarType::I8 => buffer[0] as i8 as f64,
+                    ScalarType::U8 => buffer[0] as f64,
+                    ScalarType::I16 => i16::from_le_bytes([buffer[0], buffer[1]]) as f64,
+                    ScalarType::U16 => u16::from_le_bytes([buffer[0], buffer[1]]) as f64,
+                    ScalarType::I32 => {
+                        i32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]) as f64
+                    }
+                    ScalarType::U32 => {
+                        u32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]) as f64
+                    }
+                    ScalarType::F32 => {
+                        f32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]) as f64
+                    }
+                    ScalarType::F64 => f64::from_le_bytes(buffer),
+                })
+            }
+        }
+    }
+}
+
+fn parse_header(header: &str) -> Result<(Format, Vec<Element>), String> {
+    let mut lines = header.lines();
+    if lines.next().map(str::trim) != Some("ply") {
+        return Err(String::from("not a PLY file"));
+    }
+
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    for line in lines {
+        let words: Vec<&str> = line.split_ascii_whitespace().collect();
+        match words.as_slice() {
+            ["format", "ascii", _] => format = Some(Format::Ascii),
+            ["format", "binary_little_endian", _] => format = Some(Format::BinaryLittleEndian),
+            ["format", "binary_big_endian", _] => format = Some(Format::BinaryBigEndian),
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| format!("invalid element count {:?}", count))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count_type, item_type, name] => elements
+                .last_mut()
+                .ok_or("property before the first element")?
+                .properties
+                .push(Property::List {
+                    name: name.to_string(),
+                    count_type: ScalarType::parse(count_type)?,
+                    item_type: ScalarType::parse(item_type)?,
+                }),
+            ["property", scalar_type, name] => elements
+                .last_mut()
+                .ok_or("property before the first element")?
+                .properties
+                .push(Property::Scalar {
+                    name: name.to_string(),
+                    scalar_type: ScalarType::parse(scalar_type)?,
+                }),
+            ["comment", ..] | ["obj_info", ..] | [] => {}
+            _ => return Err(format!("unexpected header line {:?}", line)),
+        }
+    }
+
+    let format = format.ok_or("missing format")?;
+
+    // Reading one doesn't consume any of the body, so a huge count would loop for a long time.
+    if let Some(element) = elements
+        .iter()
+        .find(|element| element.count > 0 && element.properties.is_empty())
+    {
+        return Err(format!("element {:?} has no properties", element.name));
+    }
+
+    Ok((format, elements))
+}
+
+pub(crate) fn parse_ply(bytes: &[u8]) -> Result<RawMesh, String> {
+    const END_HEADER: &[u8] = b"end_header";
+    let end_header = bytes
+        .windows(END_HEADER.len())
+        .position(|window| window == END_HEADER)
+        .ok_or("missing end_header")?;
+    let body_start = match bytes[end_header..].iter().position(|byte| *byte == b'\n') {
+        Some(newline) => end_header + newline + 1,
+        None => bytes.len(),
+    };
+
+    let header = String::from_utf8_lossy(&bytes[..end_header]);
+    let (format, elements) = parse_header(&header)?;
+
+    let mut body = match format {
+        Format::Ascii => Body::Ascii(
+            std::str::from_utf8(&bytes[body_start..])
+                .map_err(|_| "ASCII body isn't valid UTF-8")?
+                .split_ascii_whitespace(),
+        ),
+        Format::BinaryLittleEndian | Format::BinaryBigEndian => Body::Binary {
+            bytes: &bytes[body_start..],
+            big_endian: format == Format::BinaryBigEndian,
+        },
+    };
+
+    let mut mesh = RawMesh {
+        positions: Vec::new(),
+        normals: Vec::new(),
+        indices: Vec::new(),
+    };
+    let mut face_arities: Vec<u32> = Vec::new();
+
+    for element in elements.iter() {
+        let property_index = |name: &str| {
+            element
+                .properties
+                .iter()
+                .position(|property| property.name() == name)
+        };
+
+        let position_properties = [
+            property_index("x"),
+            property_index("y"),
+            property_index("z"),
+        ];
+        let normal_properties = [
+            property_index("nx"),
+            property_index("ny"),
+            property_index("nz"),
+        ];
+        let has_normals = normal_properties.iter().all(Option::is_some);
+        let indices_property = property_index("vertex_indices").or(property_index("vertex_index"));
+
+        let mut values: Vec<f64> = Vec::with_capacity(element.properties.len());
+        let mut list: Vec<u32> = Vec::new();
+        for _ in 0..element.count {
+            values.clear();
+            for (index, property) in element.properties.iter().enumerate() {
+                match property {
+                    Property::Scalar { scalar_type, .. } => values.push(body.read(*scalar_type)?),
+                    Property::List {
+                        count_type,
+                        item_type,
+                        ..
+                    } => {
+                        let count = body.read(*count_type)? as usize;
+                        let is_indices = element.name == "face" && Some(index) == indices_property;
+                        if is_indices {
+                            list.clear();
+                        }
+                        for _ in 0..count {
+                            let item = body.read(*item_type)?;
+                            if is_indices {
+                                list.push(item as u32);
+                            }
+                        }
+                        values.push(count as f64);
+                    }
+                }
+            }
+
+            match element.name.as_str() {
+                "vertex" => {
+                    for property in position_properties {
+                        let property = property.ok_or("vertex is missing a position")?;
+                        mesh.positions.push(values[property] as f32);
+                    }
+                    if has_normals {
+                        for property in normal_properties.into_iter().flatten() {
+                            mesh.normals.push(values[property] as f32);
+                        }
+                    }
+                }
+                // Faces with fewer than 3 vertices don't cover any area.
+                "face" if indices_property.is_some() && list.len() >= 3 => {
+                    face_arities.push(list.len() as u32);
+                    mesh.indices.extend(&list);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let vertex_count = (mesh.positions.len() / 3) as u32;
+    if mesh.indices.iter().any(|index| *index >= vertex_count) {
+        return Err(String::from("face refers to a vertex that doesn't exist"));
+    }
+
+    mesh.indices = load::triangulate_faces(&mesh.positions, &face_arities, &mesh.indices);
+    Ok(mesh)
+}
+
+#[test]
+fn test_parse_ply() {
+    // A unit square, made of one quad.
+    let ascii = b"ply
+format ascii 1.0
+comment a square
+element vertex 4
+property float x
+property float y
+property float z
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0
+1 0 0
+1 1 0
+0 1 0
+4 0 1 2 3
+";
+    let mesh = parse_ply(ascii).unwrap();
+    assert_eq!(mesh.positions.len(), 12);
+    assert!(mesh.normals.is_empty());
+    assert_eq!(mesh.indices.len(), 6);
+
+    // The same square, as a big endian binary file.
+    let mut binary = b"ply
+format binary_big_endian 1.0
+element vertex 4
+property float x
+property float y
+property float z
+element face 1
+property list uchar int vertex_indices
+end_header
+"
+    .to_vec();
+    for position in mesh.positions.iter() {
+        binary.extend(position.to_be_bytes());
+    }
+    binary.push(4);
+    for index in [0_i32, 1, 2, 3] {
+        binary.extend(index.to_be_bytes());
+    }
+
+    let binary_mesh = parse_ply(&binary).unwrap();
+    assert_eq!(binary_mesh.positions, mesh.positions);
+    assert_eq!(binary_mesh.indices, mesh.indices);
+
+    let empty_element = b"ply
+format ascii 1.0
+element nothing 18446744073709551615
+end_header
+";
+    assert!(parse_ply(empty_element).is_err());
+}
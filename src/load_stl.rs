@@ -0,0 +1,247 @@
+/*! [STL](https://en.wikipedia.org/wiki/STL_(file_format)) mesh import.
+
+Reads ASCII and binary files. STL stores each triangle's corners separately, with a normal for each
+triangle (a facet). With [`NormalStyle::Face`], the corners are kept separate and get their facet's
+normal, or one calculated from the corners when an exporter left it zeroed. The other normal styles
+smooth across triangles, so corners with the same position are merged into one vertex, and the
+normals are calculated from the triangles.
+*/
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::LoadError,
+    load::{self, Model, NormalStyle, RawMesh},
+    point::Point3,
+    vector::Vec3,
+};
+
+/// Read a `.stl` file as a model with a single object.
+pub fn read_stl(file_name: &str, normal_style: NormalStyle) -> Result<Model<'static>, LoadError> {
+    let bytes = std::fs::read(file_name).map_err(|error| LoadError::Io {
+        path: PathBuf::from(file_name),
+        error,
+    })?;
+    let mesh = parse_stl(&bytes, normal_style).map_err(|reason| LoadError::Invalid {
+        path: PathBuf::from(file_name),
+        reason,
+    })?;
+    Ok(load::single_object_model(
+        &load::file_stem(Path::new(file_name)),
+        mesh,
+        normal_style,
+    ))
+}
+
+/// The size of a binary file's header and triangle count.
+const BINARY_HEADER_SIZE: usize = 84;
+
+/// The size of a binary file's triangle: a normal, 3 corners, and a 2 byte "attribute byte count".
+const BINARY_TRIANGLE_SIZE: usize = 50;
+
+/// A triangle's normal and corners.
+struct Facet {
+    normal: [f32; 3],
+    corners: [[f32; 3]; 3],
+}
+
+pub(crate) fn parse_stl(bytes: &[u8], normal_style: NormalStyle) -> Result<RawMesh, String> {
+    /* ASCII files start with `solid`, but so do some binary files. A binary file's size is
+    determined by its triangle count, so that's a more reliable test.
+    */
+    let is_binary = bytes.len() >= BINARY_HEADER_SIZE && {
+        let triangle_count =
+            u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]) as usize;
+        bytes.len() == BINARY_HEADER_SIZE + triangle_count * BINARY_TRIANGLE_SIZE
+    };
+
+    let facets = if is_binary {
+        parse_binary(bytes)
+    } else {
+        parse_ascii(
+            std::str::from_utf8(bytes).map_err(|_| "neither a binary nor an ASCII STL file")?,
+        )?
+    };
+
+    Ok(match normal_style {
+        NormalStyle::Face => separate(&facets),
+        _ => weld(&facets),
+    })
+}
+
+fn parse_binary(bytes: &[u8]) -> Vec<Facet> {
+    bytes[BINARY_HEADER_SIZE..]
+        .chunks_exact(BINARY_TRIANGLE_SIZE)
+        .map(|triangle| {
+            let vector = |offset: usize| {
+                std::array::from_fn(|component| {
+                    let offset = offset + 4 * component;
+                    f32::from_le_bytes([
+                        triangle[offset],
+                        triangle[offset + 1],
+                        triangle[offset + 2],
+                        triangle[offset + 3],
+                    ])
+                })
+            };
+            Facet {
+                normal: vector(0),
+                corners: [vector(12), vector(24), vector(36)],
+            }
+        })
+        .collect()
+}
+
+fn parse_ascii(text: &str) -> Result<Vec<Facet>, String> {
+    let mut normals = Vec::new();
+    let mut corners = Vec::new();
+    let mut tokens = text.split_ascii_whitespace();
+    if tokens.next() != Some("solid") {
+        return Err(String::from("not an STL file"));
+    }
+
+    let parse_number = |token: Option<&str>| -> Result<f32, String> {
+        let token = token.ok_or("unexpected end of file")?;
+        token
+            .parse()
+            .map_err(|_| format!("invalid number {:?}", token))
+    };
+
+    while let Some(token) = tokens.next() {
+        match token {
+            "vertex" => corners.push([
+                parse_number(tokens.next())?,
+                parse_number(tokens.next())?,
+                parse_number(tokens.next())?,
+            ]),
+            "normal" => normals.push([
+                parse_number(tokens.next())?,
+                parse_number(tokens.next())?,
+                parse_number(tokens.next())?,
+            ]),
+            _ => {}
+        }
+    }
+
+    if corners.len() != 3 * normals.len() {
+        return Err(String::from("facet doesn't have 3 vertices"));
+    }
+    Ok(normals
+        .into_iter()
+        .zip(corners.chunks_exact(3))
+        .map(|(normal, corners)| Facet {
+            normal,
+            corners: [corners[0], corners[1], corners[2]],
+        })
+        .collect())
+}
+
+/// Give each facet its own corners, with the facet's normal.
+fn separate(facets: &[Facet]) -> RawMesh {
+    let mut mesh = RawMesh {
+        positions: Vec::with_capacity(9 * facets.len()),
+        normals: Vec::with_capacity(9 * facets.len()),
+        indices: (0..3 * facets.len() as u32).collect(),
+    };
+
+    for facet in facets {
+        let [x, y, z] = facet.normal;
+        let mut normal = Vec3 { x, y, z };
+        if normal == Vec3::ZERO {
+            // Assumes CCW corners, like `NormalStyle::Face` does.
+            let [a, b, c] = facet.corners.map(|[x, y, z]| Point3 { x, y, z });
+            normal = (b - a).cross(c - a);
+            if normal.length() > 0.0 {
+                normal = normal.normalize();
+            }
+        }
+        for corner in facet.corners {
+            mesh.positions.extend(corner);
+            mesh.normals.extend([normal.x, normal.y, normal.z]);
+        }
+    }
+
+    mesh
+}
+
+/// Merge corners with the same position into an indexed mesh, without normals.
+fn weld(facets: &[Facet]) -> RawMesh {
+    let mut mesh = RawMesh {
+        positions: Vec::new(),
+        normals: Vec::new(),
+        indices: Vec::with_capacity(3 * facets.len()),
+    };
+
+    let mut vertex_indices: HashMap<[u32; 3], u32> = HashMap::new();
+    for corner in facets.iter().flat_map(|facet| facet.corners) {
+        // `-0.0` and `0.0` are the same position, but have different bits.
+        let key = corner.map(|component| (component + 0.0).to_bits());
+        let index = *vertex_indices.entry(key).or_insert_with(|| {
+            mesh.positions.extend(corner);
+            (mesh.positions.len() / 3 - 1) as u32
+        });
+        mesh.indices.push(index);
+    }
+
+    mesh
+}
+
+#[test]
+fn test_parse_stl() {
+    // Two triangles that share an edge.
+    let ascii = "solid square
+facet normal 0 0 1
+  outer loop
+    vertex 0 0 0
+    vertex 1 0 0
+    vertex 1 1 0
+  endloop
+endfacet
+facet normal 0 0 1
+  outer loop
+    vertex 0 0 0
+    vertex 1 1 0
+    vertex 0 1 0
+  endloop
+endfacet
+endsolid square
+";
+    let mesh = parse_stl(ascii.as_bytes(), NormalStyle::AreaWeighted).unwrap();
+    assert_eq!(mesh.positions.len(), 4 * 3);
+    assert!(mesh.normals.is_empty());
+    assert_eq!(mesh.indices, [0, 1, 2, 0, 2, 3]);
+
+    // Flat shading keeps the facet normals, so the corners aren't merged.
+    let flat_mesh = parse_stl(ascii.as_bytes(), NormalStyle::Face).unwrap();
+    assert_eq!(flat_mesh.positions.len(), 6 * 3);
+    assert_eq!(flat_mesh.normals, [0.0, 0.0, 1.0].repeat(6));
+    assert_eq!(flat_mesh.indices, [0, 1, 2, 3, 4, 5]);
+
+    // `-0` is the same position as `0`.
+    let signed_zero = ascii.replacen("vertex 0 0 0", "vertex -0 0 -0", 1);
+    let signed_zero_mesh = parse_stl(signed_zero.as_bytes(), NormalStyle::AreaWeighted).unwrap();
+    assert_eq!(signed_zero_mesh.indices, mesh.indices);
+
+    // The same triangles in a binary file, whose header starts with `solid`, with zeroed normals.
+    let mut binary = b"solid".to_vec();
+    binary.resize(80, 0);
+    binary.extend(2_u32.to_le_bytes());
+    for triangle in mesh.indices.chunks(3) {
+        binary.extend([0; 12]);
+        for index in triangle {
+            let position = &mesh.positions[3 * *index as usize..3 * *index as usize + 3];
+            binary.extend(position.iter().flat_map(|x| x.to_le_bytes()));
+        }
+        binary.extend([0, 0]);
+    }
+
+    let binary_mesh = parse_stl(&binary, NormalStyle::AreaWeighted).unwrap();
+    assert_eq!(binary_mesh.positions, mesh.positions);
+    assert_eq!(binary_mesh.indices, mesh.indices);
+
+    let binary_flat_mesh = parse_stl(&binary, NormalStyle::Face).unwrap();
+    assert_eq!(binary_flat_mesh.normals, flat_mesh.normals);
+}
@@ -0,0 +1,166 @@
+/*! Levels of detail.
+
+Each object in a [`Model`] has a list of index ranges ([`ModelObject::lods`]): the first is the
+object at full detail, and each one after it is a [simplified](crate::simplify) copy of the one
+before it with about half as many triangles. Every level uses the model's vertices, so switching
+levels only changes which indices are drawn.
+
+The renderer picks a level for each object from how large the object appears on screen (see
+[`select_lod`]).
+*/
+
+use crate::{
+    aabb::Aabb, camera::Camera, index_buffer::IndexRange, load::Model, simplify::simplify,
+    vertex::Vertex,
+};
+
+/// The number of simplified levels that are generated for each object by default.
+pub const DEFAULT_LOD_LEVELS: usize = 3;
+
+/// The maximum number of levels an object can have, including the full-detail level.
+pub const MAX_LODS: usize = 8;
+
+/**
+Objects that cover at least this fraction of the viewport's height are drawn at full detail.
+
+Each time an object's size halves, it moves to the next level of detail.
+*/
+pub const FULL_DETAIL_SCREEN_SIZE: f32 = 0.25;
+
+/**
+A level stops being simplified further when it keeps more than this fraction of the previous
+level's triangles.
+
+Simplification stalls when most of a mesh's vertices are locked (see [`crate::simplify`]), and
+levels that barely differ aren't worth their memory.
+*/
+const MAX_LOD_RATIO: f32 = 0.9;
+
+/**
+Add up to `levels` simplified levels of detail to each of the model's objects.
+
+Each level targets half the triangles of the previous one. Levels are added to the end of the
+model's indices.
+*/
+pub fn generate_lods(model: &mut Model, levels: usize) {
+    let indices = model.indices.to_mut();
+    for object in model.objects.iter_mut() {
+        add_lods(&model.vertices, indices, &mut object.lods, levels);
+    }
+}
+
+/**
+Add up to `levels` simplified levels of detail to `lods`, which starts with a single, full-detail
+range of `indices`. The new levels are added to the end of `indices`.
+*/
+pub fn add_lods(
+    vertices: &[Vertex],
+    indices: &mut Vec<u32>,
+    lods: &mut Vec<IndexRange>,
+    levels: usize,
+) {
+    let max_lods = (levels + 1).min(MAX_LODS);
+    while lods.len() < max_lods {
+        let previous = *lods.last().unwrap();
+        let simplified = simplify(
+            vertices,
+            &indices[previous.as_range()],
+            previous.index_count as usize / 6 * 3,
+        );
+
+        if simplified.is_empty()
+            || simplified.len() as f32 > previous.index_count as f32 * MAX_LOD_RATIO
+        {
+            break;
+        }
+
+        lods.push(IndexRange {
+            first_index: indices.len() as u32,
+            index_count: simplified.len() as u32,
+        });
+        indices.extend(simplified);
+    }
+}
+
+/// The level of detail to draw an object at, given its bounds in world space.
+pub fn select_lod(camera: &Camera, aabb: &Aabb, lod_count: usize) -> usize {
+    let screen_size = camera.projected_size(aabb);
+    if screen_size >= FULL_DETAIL_SCREEN_SIZE {
+        0
+    } else {
+        let level = (FULL_DETAIL_SCREEN_SIZE / screen_size).log2().ceil() as usize;
+        level.min(lod_count - 1)
+    }
+}
+
+#[test]
+fn test_generate_lods() {
+    use crate::{load::ModelObject, material::MaterialId, model_matrices::ModelMatrixId, shape};
+    use std::borrow::Cow;
+
+    let sphere = shape::sphere(ModelMatrixId(0), MaterialId(0), 1.0);
+    let mut model = Model {
+        materials: Cow::Owned(Vec::new()),
+        objects: vec![ModelObject {
+            name: String::from("sphere"),
+            lods: vec![IndexRange {
+                first_index: 0,
+                index_count: sphere.indices.len() as u32,
+            }],
+            aabb: sphere.aabb(),
+        }],
+        vertices: Cow::Borrowed(&sphere.vertices),
+        indices: Cow::Borrowed(&sphere.indices),
+    };
+    generate_lods(&mut model, 3);
+
+    let lods = &model.objects[0].lods;
+    assert_eq!(lods.len(), 4);
+    for (previous, lod) in lods.iter().zip(lods.iter().skip(1)) {
+        assert_eq!(lod.first_index, previous.first_index + previous.index_count);
+        assert!(lod.index_count <= previous.index_count / 2);
+    }
+    assert_eq!(
+        model.indices.len() as u32,
+        lods[3].first_index + lods[3].index_count
+    );
+}
+
+#[test]
+fn test_select_lod() {
+    use crate::point::Point3;
+
+    let camera = Camera {
+        eye: Point3::ZERO,
+        direction: cgmath::Vector3::new(0.0, 0.0, -1.0),
+        up: cgmath::Vector3::new(0.0, 1.0, 0.0),
+        aspect: 1.0,
+        fovy: 90.0,
+        near: 0.1,
+        far: 100.0,
+    };
+    let unit_cube_at = |z: f32| Aabb {
+        min: Point3 {
+            x: -0.5,
+            y: -0.5,
+            z: z - 0.5,
+        },
+        max: Point3 {
+            x: 0.5,
+            y: 0.5,
+            z: z + 0.5,
+        },
+    };
+
+    // The camera is inside the box.
+    assert_eq!(select_lod(&camera, &unit_cube_at(0.0), 4), 0);
+
+    // Each time the distance doubles, the box's screen size (roughly) halves.
+    assert_eq!(select_lod(&camera, &unit_cube_at(-2.0), 4), 0);
+    assert_eq!(select_lod(&camera, &unit_cube_at(-4.0), 4), 1);
+    assert_eq!(select_lod(&camera, &unit_cube_at(-8.0), 4), 2);
+
+    // Far away boxes use the coarsest level that there is.
+    assert_eq!(select_lod(&camera, &unit_cube_at(-1000.0), 4), 3);
+    assert_eq!(select_lod(&camera, &unit_cube_at(-1000.0), 1), 0);
+}
@@ -1,40 +1,44 @@
 use std::{
-    fs::File,
-    io::BufReader,
+    path::PathBuf,
     time::{Duration, Instant},
 };
 
 use cgmath::Rotation3;
-use image::codecs::hdr::HdrDecoder;
 use it::{
     aabb::Aabb,
+    animation,
+    asset_loader::{Asset, AssetLoader},
     camera::{self, Camera, CameraUniform},
-    clip,
     color::Color,
-    cuboid::Cuboid,
+    error::LoadError,
+    file_watcher::FileWatcher,
+    frustum::Frustum,
+    gizmo,
     gpu_buffer::GpuBuffer,
+    gpu_culling::{CullingPasses, GpuCulling},
     gpu_flag::GpuFlag,
     gpu_variable::GpuVariable,
-    light::{
-        DirectionalLight, DirectionalLightGpu, PointLight, PointLightGpu, PointLightShadowMapFace,
-        ShadowMapLightIds,
-    },
-    load::load_model,
+    hdri::Hdri,
+    index_buffer::IndexBuffer,
+    instance::{self, Draws, InstanceBuffer},
+    joint_matrices::JointMatrices,
+    light::{fit_orthographic_projection_to_camera, DirectionalLightGpu, PointLightGpu},
+    lod,
     luminance::{self, Luminance},
-    material::{Material, Materials},
+    material::Materials,
     matrix::Matrix4,
     model_matrices::ModelMatrices,
+    picking::{ObjectIdTargets, Pick},
     point::Point3,
     reactive,
     render_egui::RenderEgui,
     render_hdr::{self, RenderHdr},
     render_sky::{self, RenderSky},
     render_wireframe::{self, RenderWireframe},
+    scene::{Scene, SceneBuffers},
     shadow_map_atlas::ShadowMapAtlas,
-    shadow_maps::{self, ShadowMaps},
-    shape,
+    shadow_maps::{self, ShadowMapDraws, ShadowMaps},
     tone_mapping::{self, ToneMapping},
-    vector::Vec3,
     vertex_buffer::VertexBuffer,
     wireframe,
 };
@@ -87,6 +91,68 @@ impl Fps {
     }
 }
 
+/**
+Upload an HDRI to a new sky texture.
+
+Only a view of the texture is returned. The view keeps the texture alive, and the texture is freed
+once nothing uses the view.
+*/
+fn create_sky_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    hdri: &Hdri,
+) -> wgpu::TextureView {
+    let size = wgpu::Extent3d {
+        width: hdri.width,
+        height: hdri.height,
+        depth_or_array_layers: 1,
+    };
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("sky_texture"),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba32Float,
+        usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    });
+    queue.write_texture(
+        wgpu::ImageCopyTextureBase {
+            texture: &texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d { x: 0, y: 0, z: 0 },
+            aspect: wgpu::TextureAspect::All,
+        },
+        bytemuck::cast_slice(&hdri.pixels),
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(4 * 4 * size.width),
+            rows_per_image: Some(size.height),
+        },
+        size,
+    );
+    texture.create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+/// The transform handles for one of the scene's objects, at its origin.
+fn object_gizmo(scene: &Scene, mode: gizmo::Mode, camera: &Camera, object: usize) -> gizmo::Gizmo {
+    let world_transform = scene.graph.world_transform(scene.object_nodes[object]);
+    gizmo::Gizmo::new(
+        mode,
+        camera,
+        Point3::from(world_transform * Point3::ZERO.with_w(1.0)),
+    )
+}
+
+/// Edit a color's RGB components. Returns whether the color changed.
+fn color_edit(ui: &mut egui::Ui, color: &mut Color) -> bool {
+    let mut rgb = [color.r, color.g, color.b];
+    let changed = ui.color_edit_button_rgb(&mut rgb).changed();
+    [color.r, color.g, color.b] = rgb;
+    changed
+}
+
 fn main() {
     env_logger::init();
     let event_loop = EventLoop::new();
@@ -111,7 +177,10 @@ fn main() {
             // features: wgpu::Features::default(),
             features: wgpu::Features::TIMESTAMP_QUERY
                 | wgpu::Features::TIMESTAMP_QUERY_INSIDE_PASSES
-                | wgpu::Features::DEPTH_CLIP_CONTROL,
+                | wgpu::Features::DEPTH_CLIP_CONTROL
+                // Optional: GPU culling falls back to culling on the CPU without these.
+                | adapter.features()
+                    & (GpuCulling::FEATURES | wgpu::Features::MULTI_DRAW_INDIRECT),
             limits: wgpu::Limits::default(),
         },
         None,
@@ -155,239 +224,142 @@ fn main() {
     surface.configure(&device, surface_config.get());
 
     let mut model_matrices = ModelMatrices::new(&device, 1000);
-    let mut shadow_caster_scene_bounds: Aabb = Aabb {
-        min: Point3::ZERO,
-        max: Point3::ZERO,
-    };
+    let mut joint_matrices = JointMatrices::new(&device, 100);
     let mut materials = Materials::new(&device, 100);
+    let mut vertex_buffer = VertexBuffer::new(&device, 100000);
+    let mut index_buffer = IndexBuffer::new(&device, 300000);
+    let mut instance_buffer = InstanceBuffer::new(&device, 1000);
+    let mut gpu_culling = GpuCulling::new(&device, &instance_buffer);
+    let gpu_culling_supported = device.features().contains(GpuCulling::FEATURES);
 
-    let matte_gold_material = materials.insert(
-        &queue,
-        Material {
-            // color: Color::RED,
-            color: Color {
-                r: 1.0,
-                g: 0.86,
-                b: 0.57,
-                a: 1.0,
-            },
-            roughness: 0.45,
-            metallic: 1.0,
-            _padding: [0, 0],
-        },
-    );
-    let matte_red_material = materials.insert(
-        &queue,
-        Material {
-            color: Color::RED,
-            roughness: 0.5,
-            metallic: 0.0,
-            _padding: [0, 0],
-        },
-    );
+    let mut shadow_map_atlas =
+        ShadowMapAtlas::new(&device, wgpu::TextureFormat::Depth16Unorm, 4096);
 
-    let green_material = materials.insert(
-        &queue,
-        Material {
-            color: Color::GREEN,
-            roughness: 0.5,
-            metallic: 0.5,
-            _padding: [0, 0],
-        },
-    );
-    let blue_material = materials.insert(
-        &queue,
-        Material {
-            color: Color::BLUE,
-            roughness: 0.5,
-            metallic: 0.5,
-            _padding: [0, 0],
-        },
+    let mut shadow_map_lights_buffer: GpuBuffer<shadow_maps::Light> = GpuBuffer::new(
+        &device,
+        Some("shadow_map_lights"),
+        wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::UNIFORM,
+        10,
     );
-    let grey_material = materials.insert(
-        &queue,
-        Material {
-            color: Color {
-                r: 0.5,
-                g: 0.5,
-                b: 0.5,
-                a: 1.0,
-            },
-            roughness: 0.8,
-            metallic: 0.0,
-            _padding: [0, 0],
-        },
+
+    let mut point_lights_buffer: GpuBuffer<PointLightGpu> = GpuBuffer::new(
+        &device,
+        Some("point_lights"),
+        wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        10,
     );
 
-    let mut vertex_buffer = VertexBuffer::new(&device, 100000);
-    {
-        let model_matrix_id = model_matrices.insert(
-            &queue,
-            cgmath::Matrix4::from_translation(cgmath::Vector3 {
-                x: -1.0,
-                y: 0.0,
-                z: 0.0,
-            })
-            .into(),
-        );
-        vertex_buffer.insert_many(&queue, &shape::triangle(model_matrix_id, blue_material));
-    }
+    let mut directional_lights_buffer: GpuBuffer<DirectionalLightGpu> = GpuBuffer::new(
+        &device,
+        Some("directional_lights"),
+        wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        10,
+    );
 
-    {
-        let model_matrix_id = model_matrices.insert(
-            &queue,
-            cgmath::Matrix4::from_translation(cgmath::Vector3 {
-                x: 1.0,
-                y: 0.0,
-                z: 0.0,
-            })
-            .into(),
-        );
-        vertex_buffer.insert_many(
-            &queue,
-            &shape::square(model_matrix_id, green_material, 0.25),
+    let mut render_wireframe_vertex_buffer: GpuBuffer<render_wireframe::VertexInput> =
+        GpuBuffer::new(
+            &device,
+            Some("render_wireframe_vertex_buffer"),
+            wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            100,
         );
-    }
 
-    {
-        let model_matrix_id = model_matrices.insert(
-            &queue,
-            cgmath::Matrix4::from_translation(cgmath::Vector3 {
-                x: 0.0,
-                y: -2.5,
-                z: 0.0,
-            })
-            .into(),
-        );
-        vertex_buffer.insert_many(&queue, &shape::floor(model_matrix_id, grey_material, 100.0));
-    }
+    /* Models and the HDRI are read on background threads so that the window is responsive while
+    they load. Each finished asset is uploaded when it's picked up at the start of a frame.
+    */
+    let mut asset_loader = AssetLoader::new(
+        std::thread::available_parallelism().map_or(1, |worker_count| worker_count.get()),
+    );
 
-    for i in 0..10 {
-        let matte_grey_material = materials.insert(
-            &queue,
-            Material {
-                color: Color {
-                    r: 0.7,
-                    g: 0.7,
-                    b: 0.7,
-                    a: 1.0,
-                },
-                roughness: 0.1 + (i as f32 / 10.0) * 0.7,
-                metallic: 1.0,
-                _padding: [0, 0],
-            },
+    /* A model is shown as a box while it's loading. A model's bounds aren't known until it's been
+    read, so the box is a guess.
+    */
+    let placeholder_aabb = Aabb {
+        min: Point3 {
+            x: -1.0,
+            y: -1.0,
+            z: -1.0,
+        },
+        max: Point3 {
+            x: 1.0,
+            y: 1.0,
+            z: 1.0,
+        },
+    };
+    let mut placeholder_wireframe_vertex_buffer: GpuBuffer<render_wireframe::VertexInput> =
+        GpuBuffer::new(
+            &device,
+            Some("placeholder_wireframe_vertex_buffer"),
+            wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            100,
         );
-        let transform: Matrix4 = cgmath::Matrix4::from_translation(cgmath::Vector3 {
-            x: 1.0 + i as f32,
-            y: -2.0,
-            z: -4.0 - i as f32,
-        })
-        .into();
-        let model_matrix_id = model_matrices.insert(&queue, transform);
-        let radius = 0.5;
-        let vertices = shape::sphere(model_matrix_id, matte_grey_material, radius);
-        vertex_buffer.insert_many(&queue, &vertices);
-        let model_aabb = Aabb {
-            min: Point3 {
-                x: -radius,
-                y: -radius,
-                z: -radius,
-            },
-            max: Point3 {
-                x: radius,
-                y: radius,
-                z: radius,
-            },
-        };
-
-        shadow_caster_scene_bounds =
-            shadow_caster_scene_bounds.union(model_aabb.transform(transform));
-    }
 
-    let teapot_aabb = load_model(
-        &queue,
-        &mut model_matrices,
-        &mut vertex_buffer,
-        "models/teapot.obj",
-        cgmath::Matrix4::from_translation(cgmath::Vector3 {
-            x: -5.0,
-            y: 0.0,
-            z: -10.0,
-        })
-        .into(),
-        matte_gold_material,
-    );
-    shadow_caster_scene_bounds = shadow_caster_scene_bounds.union(teapot_aabb);
-
-    let monkey_aabb = load_model(
+    let scene_file_name = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| String::from("scenes/default.ron"));
+    let mut scene = match Scene::load(
+        &device,
         &queue,
-        &mut model_matrices,
-        &mut vertex_buffer,
-        "models/monkey.obj",
-        cgmath::Matrix4::from_translation(cgmath::Vector3 {
-            x: 0.0,
-            y: 0.0,
-            z: -10.0,
-        })
-        .into(),
-        matte_red_material,
-    );
-    shadow_caster_scene_bounds = shadow_caster_scene_bounds.union(monkey_aabb);
+        &scene_file_name,
+        surface_config.get().width as f32 / surface_config.get().height as f32,
+        SceneBuffers {
+            model_matrices: &mut model_matrices,
+            materials: &mut materials,
+            vertex_buffer: &mut vertex_buffer,
+            index_buffer: &mut index_buffer,
+            point_lights: &mut point_lights_buffer,
+            directional_lights: &mut directional_lights_buffer,
+            shadow_map_lights: &mut shadow_map_lights_buffer,
+            shadow_map_atlas: &mut shadow_map_atlas,
+            wireframe_vertex_buffer: &mut render_wireframe_vertex_buffer,
+        },
+        &mut asset_loader,
+    ) {
+        Ok(scene) => scene,
+        Err(error) => {
+            log::error!("{}", error);
+            std::process::exit(1);
+        }
+    };
 
-    let hdri = HdrDecoder::new(BufReader::new(
-        File::open("hdris/rustig_koppie_puresky_4k.hdr").unwrap(),
-    ))
-    .unwrap();
+    for model_asset in scene.model_assets.iter_mut() {
+        let placeholder = wireframe::add(
+            &device,
+            &queue,
+            &mut model_matrices,
+            &mut placeholder_wireframe_vertex_buffer,
+            scene.graph.world_transform(model_asset.node),
+            placeholder_aabb.as_cuboid().wireframe_mesh(),
+        )
+        .unwrap();
+        scene
+            .graph
+            .set_model_matrix(model_asset.node, Some(placeholder.model_matrix_id));
+        model_asset.placeholder = Some(placeholder);
+    }
 
-    let hdri_metadata = hdri.metadata();
-    let hdri_data = hdri.read_image_hdr().unwrap();
+    // Grows to include each model as it loads.
+    let mut shadow_caster_scene_bounds = scene.shadow_caster_bounds();
 
-    log::debug!("hdri exposure: {:?}", hdri_metadata.exposure);
+    // Assets are reloaded when an artist re-exports them.
+    let mut file_watcher = FileWatcher::new(Duration::from_millis(500));
+    for model_asset in scene.model_assets.iter() {
+        file_watcher.watch(&model_asset.file_name);
+    }
+    if let Some(hdri_file_name) = &scene.description.sky.hdri {
+        file_watcher.watch(hdri_file_name);
+    }
 
-    let hdri_texture_size = wgpu::Extent3d {
-        width: hdri_metadata.width,
-        height: hdri_metadata.height,
-        depth_or_array_layers: 1,
-    };
-    let sky_texture = device.create_texture(&wgpu::TextureDescriptor {
-        label: Some("sky_texture"),
-        size: hdri_texture_size,
-        mip_level_count: 1,
-        sample_count: 1,
-        dimension: wgpu::TextureDimension::D2,
-        format: wgpu::TextureFormat::Rgba32Float,
-        usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
-        view_formats: &[],
-    });
+    // The sky is black until the HDRI has loaded.
+    let mut sky_texture_view = create_sky_texture(&device, &queue, &Hdri::black());
 
-    let sky_intensity_buffer = GpuVariable::new(
+    let mut sky_intensity_buffer = GpuVariable::new(
         &device,
         Some("sky_intensity"),
-        wgpu::BufferUsages::UNIFORM,
-        80_000.0,
+        wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        scene.description.sky.intensity,
     );
 
-    queue.write_texture(
-        wgpu::ImageCopyTextureBase {
-            texture: &sky_texture,
-            mip_level: 0,
-            origin: wgpu::Origin3d { x: 0, y: 0, z: 0 },
-            aspect: wgpu::TextureAspect::All,
-        },
-        bytemuck::cast_slice(
-            &hdri_data
-                .into_iter()
-                .map(|pixel| [pixel.0[0], pixel.0[1], pixel.0[2], 1.0])
-                .collect::<Vec<[f32; 4]>>(),
-        ),
-        wgpu::ImageDataLayout {
-            offset: 0,
-            bytes_per_row: Some(4 * 4 * hdri_texture_size.width),
-            rows_per_image: Some(hdri_texture_size.height),
-        },
-        hdri_texture_size,
-    );
-    let sky_texture_view = sky_texture.create_view(&wgpu::TextureViewDescriptor::default());
     let sky_texture_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
         label: Some("sky_texture_sampler"),
         address_mode_u: wgpu::AddressMode::ClampToEdge,
@@ -446,27 +418,12 @@ fn main() {
     let min_far_plane = 1.0;
     let max_far_plane = 100.0;
 
-    let mut camera = reactive::Var::new(Camera {
-        eye: Point3 {
-            x: 0.0,
-            y: 0.0,
-            z: 1.0,
-        },
-        direction: cgmath::Vector3 {
-            x: 0.0,
-            y: 0.0,
-            z: -1.0,
-        },
-        up: cgmath::Vector3 {
-            x: 0.0,
-            y: 1.0,
-            z: 0.0,
-        },
-        aspect: surface_config.get().width as f32 / surface_config.get().height as f32,
-        fovy: 45.0,
-        near: 0.1,
-        far: 100.0,
-    });
+    let mut camera = reactive::Var::new(
+        scene
+            .description
+            .camera
+            .camera(surface_config.get().width as f32 / surface_config.get().height as f32),
+    );
     let camera_move_speed: f32 = 0.05;
     let mut camera_buffer: GpuVariable<CameraUniform> = GpuVariable::new(
         &device,
@@ -483,321 +440,6 @@ fn main() {
         *display_normals.get(),
     );
 
-    let mut shadow_map_atlas =
-        ShadowMapAtlas::new(&device, wgpu::TextureFormat::Depth16Unorm, 4096);
-
-    let mut shadow_map_lights_buffer: GpuBuffer<shadow_maps::Light> = GpuBuffer::new(
-        &device,
-        Some("shadow_map_lights"),
-        wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::UNIFORM,
-        10,
-    );
-
-    let mut point_lights_buffer: GpuBuffer<PointLightGpu> = GpuBuffer::new(
-        &device,
-        Some("point_lights"),
-        wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
-        10,
-    );
-    let mut point_lights: Vec<PointLight> = Vec::new();
-    {
-        let position = Point3 {
-            x: 2.0,
-            y: -2.0,
-            z: -10.0,
-        };
-        let point_light_id = model_matrices.insert(
-            &queue,
-            cgmath::Matrix4::from_translation(cgmath::Vector3 {
-                x: position.x,
-                y: position.y,
-                z: position.z,
-            })
-            .into(),
-        );
-
-        let shadow_projection = Matrix4::perspective(90.0, 1.0, 0.5, 15.0);
-
-        let mut create_shadow_map_face = |up, face_direction| -> PointLightShadowMapFace {
-            let shadow_map_atlas_entry = shadow_map_atlas.allocate();
-            let shadow_map_light_gpu_id = shadow_map_lights_buffer.insert(
-                &queue,
-                shadow_maps::Light {
-                    shadow_view: Matrix4::look_to(position, face_direction, up),
-                    shadow_projection,
-                    shadow_map_atlas_position: shadow_map_atlas_entry.position().into(),
-                    shadow_map_atlas_size: [
-                        shadow_map_atlas_entry.size(),
-                        shadow_map_atlas_entry.size(),
-                    ],
-                    _padding: [0, 0, 0, 0, 0, 0, 0],
-                },
-            );
-            PointLightShadowMapFace {
-                shadow_map_light_gpu_id,
-                shadow_map_atlas_entry,
-            }
-        };
-
-        let x = create_shadow_map_face(Vec3::Y, Vec3::X);
-        let neg_x = create_shadow_map_face(Vec3::Y, -Vec3::X);
-        let y = create_shadow_map_face(Vec3::Z, Vec3::Y);
-        let neg_y = create_shadow_map_face(Vec3::Z, -Vec3::Y);
-        let z = create_shadow_map_face(Vec3::Y, Vec3::Z);
-        let neg_z = create_shadow_map_face(Vec3::Y, -Vec3::Z);
-        point_lights.push(PointLight {
-            shadow_map_faces: it::light::PointLightShadowMapFaces {
-                x,
-                neg_x,
-                y,
-                neg_y,
-                z,
-                neg_z,
-            },
-        });
-
-        point_lights_buffer.insert(
-            &queue,
-            PointLightGpu {
-                model_matrix_id: point_light_id,
-                _padding0: [0, 0, 0],
-                color: Color {
-                    r: 0.0,
-                    g: 0.6,
-                    b: 1.0,
-                    a: 1.0,
-                },
-                luminous_power: 6e5,
-                shadow_map_light_ids: ShadowMapLightIds {
-                    x: x.shadow_map_light_gpu_id,
-                    neg_x: neg_x.shadow_map_light_gpu_id,
-                    y: y.shadow_map_light_gpu_id,
-                    neg_y: neg_y.shadow_map_light_gpu_id,
-                    z: z.shadow_map_light_gpu_id,
-                    neg_z: neg_z.shadow_map_light_gpu_id,
-                },
-            },
-        );
-    }
-
-    fn fit_orthographic_projection_to_camera(
-        shadow_map_size: [f32; 2],
-        scene_bounds: &Aabb,
-        camera: &Camera,
-        shadow_view: Matrix4,
-    ) -> Aabb {
-        let camera_frustum_world_space_bounding_sphere =
-            camera.frustum_world_space().bounding_sphere();
-
-        let shadow_texel_x =
-            2.0 * camera_frustum_world_space_bounding_sphere.radius / shadow_map_size[0];
-        let shadow_texel_y =
-            2.0 * camera_frustum_world_space_bounding_sphere.radius / shadow_map_size[1];
-
-        let camera_frustum_light_space_bounding_sphere_center = Point3::from(
-            shadow_view
-                * camera_frustum_world_space_bounding_sphere
-                    .center
-                    .with_w(1.0),
-        );
-
-        let left = camera_frustum_light_space_bounding_sphere_center.x
-            - camera_frustum_world_space_bounding_sphere.radius;
-        let right = camera_frustum_light_space_bounding_sphere_center.x
-            + camera_frustum_world_space_bounding_sphere.radius;
-        let bottom = camera_frustum_light_space_bounding_sphere_center.y
-            - camera_frustum_world_space_bounding_sphere.radius;
-        let top = camera_frustum_light_space_bounding_sphere_center.y
-            + camera_frustum_world_space_bounding_sphere.radius;
-
-        let left = (left / shadow_texel_x).floor() * shadow_texel_x;
-        let right = (right / shadow_texel_x).floor() * shadow_texel_x;
-        let bottom = (bottom / shadow_texel_y).floor() * shadow_texel_y;
-        let top = (top / shadow_texel_y).floor() * shadow_texel_y;
-
-        let left_clipping_plane = clip::Plane::new(
-            Vec3::X,
-            Point3 {
-                x: left,
-                y: 0.0,
-                z: 0.0,
-            },
-        );
-        let right_clipping_plane = clip::Plane::new(
-            -Vec3::X,
-            Point3 {
-                x: right,
-                y: 0.0,
-                z: 0.0,
-            },
-        );
-        let bottom_clipping_plane = clip::Plane::new(
-            Vec3::Y,
-            Point3 {
-                x: 0.0,
-                y: bottom,
-                z: 0.0,
-            },
-        );
-        let top_clipping_plane = clip::Plane::new(
-            -Vec3::Y,
-            Point3 {
-                x: 0.0,
-                y: top,
-                z: 0.0,
-            },
-        );
-
-        let triangles: Vec<clip::Triangle> = {
-            let Cuboid {
-                near_top_left,
-                near_top_right,
-                near_bottom_left,
-                near_bottom_right,
-                far_top_left,
-                far_top_right,
-                far_bottom_left,
-                far_bottom_right,
-            } = shadow_view * scene_bounds.as_cuboid();
-
-            vec![
-                // Near face
-                clip::Triangle(near_top_right, near_top_left, near_bottom_left),
-                clip::Triangle(near_top_right, near_bottom_left, near_bottom_right),
-                // Far face
-                clip::Triangle(far_top_right, far_top_left, far_bottom_left),
-                clip::Triangle(far_top_right, far_bottom_left, far_bottom_right),
-                // Top face
-                clip::Triangle(far_top_right, far_top_left, near_top_left),
-                clip::Triangle(far_top_right, near_top_left, near_top_right),
-                // Bottom face
-                clip::Triangle(near_bottom_right, near_bottom_left, far_bottom_left),
-                clip::Triangle(near_bottom_right, far_bottom_left, far_bottom_right),
-                // Left face
-                clip::Triangle(near_top_left, far_top_left, far_bottom_left),
-                clip::Triangle(near_top_left, far_bottom_left, near_bottom_left),
-                // Right face
-                clip::Triangle(far_top_right, near_top_right, near_bottom_right),
-                clip::Triangle(far_top_right, near_bottom_right, far_bottom_right),
-            ]
-        };
-        let clipped_triangles: Vec<clip::Triangle> =
-            clip::clip_triangles(left_clipping_plane, triangles);
-        let clipped_triangles: Vec<clip::Triangle> =
-            clip::clip_triangles(right_clipping_plane, clipped_triangles);
-        let clipped_triangles: Vec<clip::Triangle> =
-            clip::clip_triangles(bottom_clipping_plane, clipped_triangles);
-        let clipped_triangles: Vec<clip::Triangle> =
-            clip::clip_triangles(top_clipping_plane, clipped_triangles);
-
-        let (near, far) = clipped_triangles.into_iter().fold(
-            (f32::NEG_INFINITY, f32::INFINITY),
-            |acc, triangle| {
-                triangle.into_iter().fold(acc, |(near, far), point| {
-                    (near.max(point.z), far.min(point.z))
-                })
-            },
-        );
-
-        Aabb {
-            min: Point3 {
-                x: left,
-                y: bottom,
-                z: far,
-            },
-            max: Point3 {
-                x: right,
-                y: top,
-                z: near,
-            },
-        }
-    }
-
-    let mut render_wireframe_vertex_buffer: GpuBuffer<render_wireframe::VertexInput> =
-        GpuBuffer::new(
-            &device,
-            Some("render_wireframe_vertex_buffer"),
-            wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-            100,
-        );
-
-    let mut directional_lights_buffer: GpuBuffer<DirectionalLightGpu> = GpuBuffer::new(
-        &device,
-        Some("directional_lights"),
-        wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
-        10,
-    );
-    let mut directional_lights = Vec::new();
-
-    {
-        let direction = Vec3 {
-            x: -1.0,
-            y: -1.0,
-            z: -1.5,
-        }
-        .normalize();
-        let shadow_map_atlas_entry = shadow_map_atlas.allocate();
-        let position = shadow_map_atlas_entry.position();
-        let size = shadow_map_atlas_entry.size();
-
-        let shadow_view = Matrix4::look_to(Point3::ZERO, direction, Vec3::Y);
-
-        let aabb = fit_orthographic_projection_to_camera(
-            [shadow_map_atlas_entry.size(), shadow_map_atlas_entry.size()],
-            &shadow_caster_scene_bounds,
-            camera.get(),
-            shadow_view,
-        );
-        debug_assert!(aabb.valid(), "invalid aabb: {:?}", aabb);
-
-        let id = shadow_map_lights_buffer.insert(
-            &queue,
-            shadow_maps::Light {
-                shadow_view,
-                shadow_projection: Matrix4::ortho(
-                    aabb.min.x,
-                    aabb.max.x,
-                    aabb.min.y,
-                    aabb.max.y,
-                    // `ortho` takes positive near/far arguments but still assumes that far is
-                    // towards -Z.
-                    -aabb.max.z,
-                    -aabb.min.z,
-                ),
-                shadow_map_atlas_position: position.into(),
-                shadow_map_atlas_size: [size, size],
-                _padding: [0, 0, 0, 0, 0, 0, 0],
-            },
-        );
-        directional_lights_buffer.insert(
-            &queue,
-            DirectionalLightGpu {
-                color: Color::WHITE,
-                direction,
-                illuminance: 110_000.0,
-                shadow_map_light_id: id,
-            },
-        );
-
-        let shadow_view_inverse = shadow_view.inverse();
-
-        let wireframe = wireframe::add(
-            &queue,
-            &mut model_matrices,
-            &mut render_wireframe_vertex_buffer,
-            shadow_view_inverse,
-            aabb.as_cuboid().wireframe_mesh(),
-        );
-
-        directional_lights.push(DirectionalLight {
-            shadow_map_light_gpu_id: id,
-            shadow_map_atlas_entry,
-            shadow_view,
-            shadow_view_inverse,
-            wireframe: Some(wireframe),
-        });
-    };
-
     /*
     A floating point depth buffer seems pretty important for working with a high `camera.near` to
     `camera.far` ratio.
@@ -842,16 +484,29 @@ fn main() {
             .create_view(&depth_texture_view_descriptor),
     );
 
-    let shadow_maps = ShadowMaps::new(
+    /* Objects are picked by clicking on them when mouse look is disabled. The click's object ids
+    are written by the next frame's HDR pass, and read back after it's submitted.
+    */
+    let mut object_id_targets = ObjectIdTargets::new(
+        &device,
+        surface_config.get().width,
+        surface_config.get().height,
+    );
+    let mut cursor_position = winit::dpi::PhysicalPosition::new(0.0, 0.0);
+    let mut pick_requested = false;
+    let mut picked: Option<Pick> = None;
+
+    let mut shadow_maps = ShadowMaps::new(
         &device,
         shadow_map_atlas.texture_format(),
         shadow_maps::BindGroup0 {
             lights: &shadow_map_lights_buffer,
             model_matrices: &model_matrices,
+            joint_matrices: &joint_matrices,
         },
     );
 
-    let render_sky = RenderSky::new(
+    let mut render_sky = RenderSky::new(
         &device,
         hdr_render_target_format,
         render_sky::BindGroup0 {
@@ -870,7 +525,7 @@ fn main() {
         *show_directional_shadow_map_coverage.get(),
     );
 
-    let render_hdr = RenderHdr::new(
+    let mut render_hdr = RenderHdr::new(
         &device,
         hdr_render_target_format,
         depth_texture_format,
@@ -886,6 +541,7 @@ fn main() {
             shadow_map_lights: &shadow_map_lights_buffer,
             sky_texture: &sky_texture_view,
             sky_texture_sampler: &sky_texture_sampler,
+            joint_matrices: &joint_matrices,
         },
         render_hdr::BindGroup1 {
             show_directional_shadow_map_coverage: &show_directional_shadow_map_coverage_buffer,
@@ -982,15 +638,18 @@ fn main() {
         },
     );
 
-    wireframe::add(
+    let shadow_caster_scene_bounds_wireframe = wireframe::add(
+        &device,
         &queue,
         &mut model_matrices,
         &mut render_wireframe_vertex_buffer,
         Matrix4::IDENTITY,
         shadow_caster_scene_bounds.as_cuboid().wireframe_mesh(),
-    );
+    )
+    .unwrap();
 
     let camera_frustum_wireframe = wireframe::add(
+        &device,
         &queue,
         &mut model_matrices,
         &mut render_wireframe_vertex_buffer,
@@ -1002,18 +661,56 @@ fn main() {
         */
         camera.get().view_matrix().inverse(),
         camera.get().frustum_camera_space().wireframe_mesh(),
+    )
+    .unwrap();
+
+    let mut render_wireframe = RenderWireframe::new(
+        &device,
+        surface_format,
+        depth_texture_format,
+        wgpu::CompareFunction::Less,
+        render_wireframe::BindGroup0 {
+            camera: &camera_buffer,
+            model_matrices: &model_matrices,
+        },
     );
 
-    let render_wireframe = RenderWireframe::new(
+    /* The picked object's transform handles, for the mode that's in use. The wireframe is replaced
+    when the mode changes. The handles are drawn over everything else, so that they can still be
+    grabbed when they're inside the object.
+    */
+    let mut gizmo_mode = gizmo::Mode::Translate;
+    let mut gizmo_wireframe_vertex_buffer: GpuBuffer<render_wireframe::VertexInput> =
+        GpuBuffer::new(
+            &device,
+            Some("gizmo_wireframe_vertex_buffer"),
+            wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            100,
+        );
+    let mut gizmo_wireframe_mode = gizmo_mode;
+    let mut gizmo_wireframe = wireframe::add(
+        &device,
+        &queue,
+        &mut model_matrices,
+        &mut gizmo_wireframe_vertex_buffer,
+        Matrix4::IDENTITY,
+        gizmo::lines(gizmo_mode),
+    )
+    .unwrap();
+    let mut render_gizmo = RenderWireframe::new(
         &device,
         surface_format,
         depth_texture_format,
+        wgpu::CompareFunction::Always,
         render_wireframe::BindGroup0 {
             camera: &camera_buffer,
             model_matrices: &model_matrices,
         },
     );
 
+    // The index of the object whose handle is being dragged, and the drag.
+    let mut gizmo_drag: Option<(usize, gizmo::Drag)> = None;
+
     let mut render_egui = RenderEgui::new(&device, surface_format);
     let egui_context = egui::Context::default();
     let pixels_per_point = 2.0;
@@ -1035,6 +732,13 @@ fn main() {
 
     let mut display_debug_wireframes = false;
 
+    let mut animation_playback = animation::Playback::default();
+    let mut animation_scrubbed = false;
+    let mut last_frame = Instant::now();
+    let mut level_of_detail = true;
+    let mut frustum_culling = true;
+    let mut gpu_culling_enabled = gpu_culling_supported;
+
     event_loop.run(move |event, _, control_flow| {
         let depth_texture = &mut depth_texture;
         let depth_texture_view = &mut depth_texture_view;
@@ -1048,6 +752,16 @@ fn main() {
             Event::WindowEvent { window_id, event } if window_id == window.id() => {
                 let response = egui_winit_state.on_event(&egui_context, &event);
 
+                // A drag ends wherever the button is released, even over the UI.
+                if let WindowEvent::MouseInput {
+                    state: ElementState::Released,
+                    button: winit::event::MouseButton::Left,
+                    ..
+                } = event
+                {
+                    gizmo_drag = None;
+                }
+
                 if !response.consumed {
                     match event {
                         WindowEvent::CloseRequested
@@ -1110,10 +824,62 @@ fn main() {
                                 }
                             }
                         }
+                        WindowEvent::CursorMoved { position, .. } => {
+                            cursor_position = position;
+
+                            if let Some((object, drag)) = &mut gizmo_drag {
+                                let transform = drag.drag_to(
+                                    camera.get(),
+                                    [
+                                        surface_config.get().width as f32,
+                                        surface_config.get().height as f32,
+                                    ],
+                                    [position.x as f32, position.y as f32],
+                                );
+                                scene.set_object_transform(*object, transform);
+                            }
+                        }
                         WindowEvent::MouseInput {
                             state: winit::event::ElementState::Pressed,
                             button: winit::event::MouseButton::Left,
                             ..
+                        } if !mouse_look.enabled() => {
+                            // Grab one of the picked object's handles, or pick what's under the cursor.
+                            let cursor = [cursor_position.x as f32, cursor_position.y as f32];
+                            gizmo_drag = picked
+                                .and_then(|pick| scene.picked_object(&pick))
+                                .and_then(|object| {
+                                    let gizmo =
+                                        object_gizmo(&scene, gizmo_mode, camera.get(), object);
+                                    let axis = gizmo.grab(
+                                        camera.get(),
+                                        [
+                                            surface_config.get().width as f32,
+                                            surface_config.get().height as f32,
+                                        ],
+                                        cursor,
+                                    )?;
+                                    let parent_transform = scene
+                                        .graph
+                                        .parent(scene.object_nodes[object])
+                                        .map_or(Matrix4::IDENTITY, |parent| {
+                                            scene.graph.world_transform(parent)
+                                        });
+                                    let drag = gizmo::Drag::new(
+                                        gizmo,
+                                        axis,
+                                        parent_transform,
+                                        scene.object_transform(object),
+                                        cursor,
+                                    );
+                                    Some((object, drag))
+                                });
+                            pick_requested = gizmo_drag.is_none();
+                        }
+                        WindowEvent::MouseInput {
+                            state: winit::event::ElementState::Pressed,
+                            button: winit::event::MouseButton::Right,
+                            ..
                         } if !mouse_look.enabled() => {
                             mouse_look.set(&window, true);
                         }
@@ -1134,6 +900,266 @@ fn main() {
                 }
             }
             Event::RedrawRequested(window_id) if window_id == window.id() => {
+                for file_name in file_watcher.changed() {
+                    log::info!("reloading {}", file_name);
+                    if Some(&file_name) == scene.description.sky.hdri.as_ref() {
+                        scene.hdri_request = Some(asset_loader.load_hdri(&file_name));
+                    }
+                    for model_asset in scene.model_assets.iter_mut() {
+                        if model_asset.file_name == file_name {
+                            model_asset.request =
+                                asset_loader.load_model(&file_name, &model_asset.options);
+                        }
+                    }
+                }
+
+                for loaded_asset in asset_loader.poll() {
+                    let model_asset_index = scene
+                        .model_assets
+                        .iter()
+                        .position(|model_asset| model_asset.request == loaded_asset.id);
+
+                    match loaded_asset.result {
+                        Ok(asset @ (Asset::Model(_) | Asset::Gltf(_))) => {
+                            let Some(model_asset_index) = model_asset_index else {
+                                continue;
+                            };
+                            let result = match asset {
+                                Asset::Gltf(gltf_scene) => scene.replace_gltf(
+                                    &device,
+                                    &queue,
+                                    &mut model_matrices,
+                                    &mut joint_matrices,
+                                    &mut materials,
+                                    &mut vertex_buffer,
+                                    &mut index_buffer,
+                                    model_asset_index,
+                                    &gltf_scene,
+                                ),
+                                Asset::Model(model_file) => scene.replace_model(
+                                    &device,
+                                    &queue,
+                                    &mut model_matrices,
+                                    &mut materials,
+                                    &mut vertex_buffer,
+                                    &mut index_buffer,
+                                    model_asset_index,
+                                    &model_file.model(),
+                                ),
+                                Asset::Hdri(_) => unreachable!(),
+                            };
+                            match result {
+                                Ok(()) => {
+                                    let model_asset = &mut scene.model_assets[model_asset_index];
+                                    if let Some(placeholder) = model_asset.placeholder.take() {
+                                        scene.graph.set_model_matrix(model_asset.node, None);
+                                        wireframe::remove(
+                                            &queue,
+                                            &mut model_matrices,
+                                            &mut placeholder_wireframe_vertex_buffer,
+                                            &placeholder,
+                                        );
+                                    }
+                                }
+                                // The placeholder stays, to show where the model should be.
+                                Err(error) => log::error!(
+                                    "{}",
+                                    LoadError::Capacity {
+                                        path: PathBuf::from(loaded_asset.file_name),
+                                        error,
+                                    }
+                                ),
+                            }
+
+                            // Either way, the previous version's space was freed.
+                            let mut command_encoder = device
+                                .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+                            scene.compact(
+                                &device,
+                                &mut command_encoder,
+                                &mut vertex_buffer,
+                                &mut index_buffer,
+                            );
+                            queue.submit(std::iter::once(command_encoder.finish()));
+                        }
+                        Ok(Asset::Hdri(hdri)) => {
+                            if Some(loaded_asset.id) != scene.hdri_request {
+                                continue;
+                            }
+                            sky_texture_view = create_sky_texture(&device, &queue, &hdri);
+                            render_sky.set_bind_group_0(
+                                &device,
+                                render_sky::BindGroup0 {
+                                    camera: &camera_buffer,
+                                    sky_texture: &sky_texture_view,
+                                    sky_texture_sampler: &sky_texture_sampler,
+                                    sky_intensity: &sky_intensity_buffer,
+                                },
+                            );
+                            render_hdr.set_bind_group_0(
+                                &device,
+                                render_hdr::BindGroup0 {
+                                    camera: &camera_buffer,
+                                    model_matrices: &model_matrices,
+                                    display_normals: &display_normals_buffer,
+                                    point_lights: &point_lights_buffer,
+                                    directional_lights: &directional_lights_buffer,
+                                    materials: &materials,
+                                    shadow_map_atlas: shadow_map_atlas.texture_view(),
+                                    shadow_map_atlas_sampler: shadow_map_atlas.sampler(),
+                                    shadow_map_lights: &shadow_map_lights_buffer,
+                                    sky_texture: &sky_texture_view,
+                                    sky_texture_sampler: &sky_texture_sampler,
+                                    joint_matrices: &joint_matrices,
+                                },
+                            );
+                        }
+                        Err(error) => {
+                            // The previous version, or the placeholder, is kept.
+                            log::error!("{}", error);
+                        }
+                    }
+                }
+
+                if gizmo_wireframe_mode != gizmo_mode {
+                    wireframe::remove(
+                        &queue,
+                        &mut model_matrices,
+                        &mut gizmo_wireframe_vertex_buffer,
+                        &gizmo_wireframe,
+                    );
+                    gizmo_wireframe = wireframe::add(
+                        &device,
+                        &queue,
+                        &mut model_matrices,
+                        &mut gizmo_wireframe_vertex_buffer,
+                        Matrix4::IDENTITY,
+                        gizmo::lines(gizmo_mode),
+                    )
+                    .unwrap();
+                    gizmo_wireframe_mode = gizmo_mode;
+                }
+
+                /* Buffers grow when they're full, which replaces the underlying buffers that the
+                bind groups refer to.
+                */
+                let model_matrices_reallocated = model_matrices.take_reallocated();
+                let joint_matrices_reallocated = joint_matrices.take_reallocated();
+                let materials_reallocated = materials.take_reallocated();
+                let point_lights_reallocated = point_lights_buffer.take_reallocated();
+                let directional_lights_reallocated = directional_lights_buffer.take_reallocated();
+                let shadow_map_lights_reallocated = shadow_map_lights_buffer.take_reallocated();
+                if model_matrices_reallocated
+                    || joint_matrices_reallocated
+                    || materials_reallocated
+                    || point_lights_reallocated
+                    || directional_lights_reallocated
+                    || shadow_map_lights_reallocated
+                {
+                    render_hdr.set_bind_group_0(
+                        &device,
+                        render_hdr::BindGroup0 {
+                            camera: &camera_buffer,
+                            model_matrices: &model_matrices,
+                            display_normals: &display_normals_buffer,
+                            point_lights: &point_lights_buffer,
+                            directional_lights: &directional_lights_buffer,
+                            materials: &materials,
+                            shadow_map_atlas: shadow_map_atlas.texture_view(),
+                            shadow_map_atlas_sampler: shadow_map_atlas.sampler(),
+                            shadow_map_lights: &shadow_map_lights_buffer,
+                            sky_texture: &sky_texture_view,
+                            sky_texture_sampler: &sky_texture_sampler,
+                            joint_matrices: &joint_matrices,
+                        },
+                    );
+                }
+                if model_matrices_reallocated
+                    || joint_matrices_reallocated
+                    || shadow_map_lights_reallocated
+                {
+                    shadow_maps.set_bind_group_0(
+                        &device,
+                        shadow_maps::BindGroup0 {
+                            lights: &shadow_map_lights_buffer,
+                            model_matrices: &model_matrices,
+                            joint_matrices: &joint_matrices,
+                        },
+                    );
+                }
+                if model_matrices_reallocated {
+                    render_wireframe.set_bind_group_0(
+                        &device,
+                        render_wireframe::BindGroup0 {
+                            camera: &camera_buffer,
+                            model_matrices: &model_matrices,
+                        },
+                    );
+                    render_gizmo.set_bind_group_0(
+                        &device,
+                        render_wireframe::BindGroup0 {
+                            camera: &camera_buffer,
+                            model_matrices: &model_matrices,
+                        },
+                    );
+                }
+
+                let now = Instant::now();
+                let elapsed = now.duration_since(last_frame).as_secs_f32();
+                last_frame = now;
+                let animation_duration = scene.animation_duration();
+                if scene.is_animated()
+                    && (animation_playback.advance(elapsed, animation_duration)
+                        || animation_scrubbed)
+                {
+                    let (camera_value, camera_changed) = camera.as_components();
+                    let animated = scene.animate(animation_playback.time, camera_value);
+                    *camera_changed |= animated.camera;
+                    if animated.lights() {
+                        scene.upload_lights(
+                            &queue,
+                            &mut point_lights_buffer,
+                            &mut directional_lights_buffer,
+                        );
+                    }
+                    scene.animate_skins(&queue, &mut joint_matrices, animation_playback.time);
+                }
+                animation_scrubbed = false;
+
+                if scene.update(&queue, &mut model_matrices) {
+                    shadow_caster_scene_bounds = scene.shadow_caster_bounds();
+
+                    let vertices: Vec<render_wireframe::VertexInput> = shadow_caster_scene_bounds
+                        .as_cuboid()
+                        .wireframe_mesh()
+                        .into_iter()
+                        .flat_map(|(from, to)| {
+                            [
+                                render_wireframe::VertexInput {
+                                    position: from,
+                                    model_matrix_id: shadow_caster_scene_bounds_wireframe
+                                        .model_matrix_id,
+                                },
+                                render_wireframe::VertexInput {
+                                    position: to,
+                                    model_matrix_id: shadow_caster_scene_bounds_wireframe
+                                        .model_matrix_id,
+                                },
+                            ]
+                            .into_iter()
+                        })
+                        .collect();
+                    render_wireframe_vertex_buffer.update_slice(
+                        &queue,
+                        shadow_caster_scene_bounds_wireframe.vertex_buffer_offset,
+                        &vertices,
+                    );
+
+                    // Refit the directional lights' projections to the new bounds.
+                    let (_, camera_changed) = camera.as_components();
+                    *camera_changed = true;
+                }
+
                 if w_held {
                     camera.modify_mut(&mut |camera| {
                         let camera_movement = camera_move_speed * camera.direction;
@@ -1184,6 +1210,9 @@ fn main() {
 
                     num_pixels.set(surface_config.width * surface_config.height);
 
+                    object_id_targets =
+                        ObjectIdTargets::new(&device, surface_config.width, surface_config.height);
+
                     camera.modify_mut(&mut |camera| {
                         camera.aspect = surface_config.width as f32 / surface_config.height as f32;
                     });
@@ -1287,7 +1316,7 @@ fn main() {
                             );
                         }
 
-                        for directional_light in &directional_lights {
+                        for directional_light in &mut scene.directional_lights {
                             let aabb = fit_orthographic_projection_to_camera(
                                 [
                                     directional_light.shadow_map_atlas_entry.size(),
@@ -1297,20 +1326,23 @@ fn main() {
                                 camera,
                                 directional_light.shadow_view,
                             );
+                            let shadow_projection = Matrix4::ortho(
+                                aabb.min.x,
+                                aabb.max.x,
+                                aabb.min.y,
+                                aabb.max.y,
+                                -aabb.max.z,
+                                -aabb.min.z,
+                            );
+                            directional_light.frustum =
+                                Frustum::new(shadow_projection * directional_light.shadow_view);
 
                             shadow_map_lights_buffer.update(
                                 &queue,
                                 directional_light.shadow_map_light_gpu_id,
                                 shadow_maps::Light {
                                     shadow_view: directional_light.shadow_view,
-                                    shadow_projection: Matrix4::ortho(
-                                        aabb.min.x,
-                                        aabb.max.x,
-                                        aabb.min.y,
-                                        aabb.max.y,
-                                        -aabb.max.z,
-                                        -aabb.min.z,
-                                    ),
+                                    shadow_projection,
                                     shadow_map_atlas_position: directional_light
                                         .shadow_map_atlas_entry
                                         .position()
@@ -1371,21 +1403,150 @@ fn main() {
                     },
                 );
 
+                let selected_object = picked.and_then(|pick| scene.picked_object(&pick));
+                if let Some(object) = selected_object {
+                    let gizmo = object_gizmo(&scene, gizmo_mode, camera.get(), object);
+                    model_matrices.update(
+                        &queue,
+                        gizmo_wireframe.model_matrix_id,
+                        gizmo.model_matrix(),
+                    );
+                }
+
                 let surface_texture = surface.get_current_texture().unwrap();
                 let surface_texture_view = surface_texture
                     .texture
                     .create_view(&wgpu::TextureViewDescriptor::default());
 
+                /* The same levels of detail are used for the shadow maps, because they're chosen
+                for the camera that sees the shadows.
+                */
+                let objects: Vec<_> = scene
+                    .loaded_objects()
+                    .map(|object| {
+                        let lod = if level_of_detail {
+                            lod::select_lod(camera.get(), &object.aabb, object.lods.len())
+                        } else {
+                            0
+                        };
+                        (object.aabb, object.lods[lod], object.instance())
+                    })
+                    .collect();
+                let camera_frustum = Frustum::from_cuboid(&camera.get().frustum_world_space());
+
+                let point_light_faces = scene.point_lights.iter().flat_map(|point_light| {
+                    point_light.shadow_map_faces.iter().map(|face| {
+                        (
+                            face.shadow_map_light_gpu_id,
+                            face.shadow_map_atlas_entry,
+                            &face.frustum,
+                        )
+                    })
+                });
+                let directional_lights = scene.directional_lights.iter().map(|directional_light| {
+                    (
+                        directional_light.shadow_map_light_gpu_id,
+                        directional_light.shadow_map_atlas_entry,
+                        &directional_light.frustum,
+                    )
+                });
+                let shadow_map_frustums: Vec<_> =
+                    point_light_faces.chain(directional_lights).collect();
+
+                let gpu_culled = frustum_culling && gpu_culling_enabled;
+                let objects_drawn: Option<u32>;
+                let (draws, shadow_map_draws) = if gpu_culled {
+                    objects_drawn = gpu_culling.read_objects_drawn(&device);
+
+                    let mut passes = CullingPasses::default();
+                    let camera_draws = passes.add(&camera_frustum, objects.iter().copied());
+                    let shadow_map_passes: Vec<_> = shadow_map_frustums
+                        .iter()
+                        .map(|(_, _, frustum)| passes.add(frustum, objects.iter().copied()))
+                        .collect();
+
+                    match gpu_culling.write(&device, &queue, &mut instance_buffer, &passes) {
+                        Ok(()) => (
+                            gpu_culling.draws(camera_draws),
+                            shadow_map_frustums
+                                .iter()
+                                .zip(shadow_map_passes)
+                                .map(
+                                    |(
+                                        &(shadow_map_light_gpu_id, shadow_map_atlas_entry, _),
+                                        range,
+                                    )| {
+                                        ShadowMapDraws {
+                                            shadow_map_light_gpu_id,
+                                            shadow_map_atlas_entry,
+                                            draws: gpu_culling.draws(range),
+                                        }
+                                    },
+                                )
+                                .collect(),
+                        ),
+                        Err(error) => {
+                            log::error!("{}", error);
+                            (Draws::Direct(Vec::new()), Vec::new())
+                        }
+                    }
+                } else {
+                    let mut instances = Vec::new();
+                    let mut batch_visible = |frustum: &Frustum| {
+                        instance::batch(
+                            objects
+                                .iter()
+                                .filter(|(aabb, ..)| !frustum_culling || frustum.intersects(aabb))
+                                .map(|(_, indices, instance)| (*indices, *instance)),
+                            &mut instances,
+                        )
+                    };
+
+                    let draws = batch_visible(&camera_frustum);
+                    objects_drawn =
+                        Some(draws.iter().map(|draw| draw.instances.len() as u32).sum());
+                    let shadow_map_draws: Vec<ShadowMapDraws> = shadow_map_frustums
+                        .iter()
+                        .map(
+                            |&(shadow_map_light_gpu_id, shadow_map_atlas_entry, frustum)| {
+                                ShadowMapDraws {
+                                    shadow_map_light_gpu_id,
+                                    shadow_map_atlas_entry,
+                                    draws: Draws::Direct(batch_visible(frustum)),
+                                }
+                            },
+                        )
+                        .collect();
+
+                    match instance_buffer.write(&device, &queue, &instances) {
+                        Ok(()) => (Draws::Direct(draws), shadow_map_draws),
+                        Err(error) => {
+                            log::error!("{}", error);
+                            (Draws::Direct(Vec::new()), Vec::new())
+                        }
+                    }
+                };
+
+                // There's no pixel under the cursor to read back while the window is minimised.
+                if surface_config.get().width == 0 || surface_config.get().height == 0 {
+                    pick_requested = false;
+                }
+
                 let commands = {
                     let mut command_encoder =
                         device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
 
+                    if gpu_culled {
+                        gpu_culling.record(&mut command_encoder);
+                    }
+
                     shadow_maps.record(
                         &mut command_encoder,
                         shadow_map_atlas.texture_view(),
-                        &point_lights,
-                        &directional_lights,
                         &vertex_buffer,
+                        &index_buffer,
+                        &instance_buffer,
+                        &shadow_map_draws,
                     );
 
                     render_sky.record(&mut command_encoder, hdr_render_target_view.get());
@@ -1394,15 +1555,40 @@ fn main() {
                         &mut command_encoder,
                         hdr_render_target_view.get(),
                         depth_texture_view.get(),
+                        pick_requested.then_some(&object_id_targets),
                         &vertex_buffer,
+                        &index_buffer,
+                        &instance_buffer,
+                        &draws,
                     );
 
+                    if pick_requested {
+                        object_id_targets.record_readback(
+                            &mut command_encoder,
+                            (cursor_position.x as u32).min(surface_config.get().width - 1),
+                            (cursor_position.y as u32).min(surface_config.get().height - 1),
+                        );
+                    }
+
                     if *tone_mapping_enabled.get() {
                         luminance.record(&mut command_encoder);
                     }
 
                     tone_mapping.record(&mut command_encoder, &surface_texture_view);
 
+                    if scene
+                        .model_assets
+                        .iter()
+                        .any(|model_asset| model_asset.placeholder.is_some())
+                    {
+                        render_wireframe.record(
+                            &mut command_encoder,
+                            &surface_texture_view,
+                            depth_texture_view.get(),
+                            &placeholder_wireframe_vertex_buffer,
+                        );
+                    }
+
                     if display_debug_wireframes {
                         render_wireframe.record(
                             &mut command_encoder,
@@ -1412,6 +1598,15 @@ fn main() {
                         );
                     }
 
+                    if selected_object.is_some() {
+                        render_gizmo.record(
+                            &mut command_encoder,
+                            &surface_texture_view,
+                            depth_texture_view.get(),
+                            &gizmo_wireframe_vertex_buffer,
+                        );
+                    }
+
                     render_egui.record(
                         &device,
                         &queue,
@@ -1433,6 +1628,16 @@ fn main() {
                                 ui.label(fps.avg_fps().round().to_string());
                             });
 
+                            ui.horizontal(|ui| {
+                                ui.label("Objects drawn: ");
+                                ui.label(format!(
+                                    "{} / {}",
+                                    objects_drawn
+                                        .map_or(String::from("-"), |drawn| drawn.to_string()),
+                                    objects.len()
+                                ));
+                            });
+
                             let (display_normals_value, display_normals_changed) =
                                 display_normals.as_components();
                             if ui
@@ -1449,6 +1654,15 @@ fn main() {
 
                             ui.checkbox(&mut display_debug_wireframes, "Display debug wireframes");
 
+                            ui.checkbox(&mut level_of_detail, "Level of detail");
+
+                            ui.checkbox(&mut frustum_culling, "Frustum culling");
+
+                            ui.add_enabled(
+                                gpu_culling_supported,
+                                egui::Checkbox::new(&mut gpu_culling_enabled, "GPU culling"),
+                            );
+
                             let (
                                 show_directional_shadow_map_coverage_value,
                                 show_directional_shadow_map_coverage_changed,
@@ -1472,6 +1686,224 @@ fn main() {
                                     .changed();
                             });
 
+                            ui.collapsing("Scene", |ui| {
+                                ui.horizontal(|ui| {
+                                    ui.label("Sky intensity");
+                                    if ui
+                                        .add(
+                                            egui::DragValue::new(
+                                                &mut scene.description.sky.intensity,
+                                            )
+                                            .speed(100.0)
+                                            .clamp_range(0.0..=f32::MAX),
+                                        )
+                                        .changed()
+                                    {
+                                        sky_intensity_buffer
+                                            .update(&queue, scene.description.sky.intensity);
+                                    }
+                                });
+
+                                let mut lights_changed = false;
+                                for (index, point_light) in
+                                    scene.description.point_lights.iter_mut().enumerate()
+                                {
+                                    ui.horizontal(|ui| {
+                                        ui.label(format!("Point light {}", index));
+                                        lights_changed |= color_edit(ui, &mut point_light.color);
+                                        lights_changed |= ui
+                                            .add(
+                                                egui::DragValue::new(
+                                                    &mut point_light.luminous_power,
+                                                )
+                                                .speed(1000.0)
+                                                .clamp_range(0.0..=f32::MAX)
+                                                .suffix(" lm"),
+                                            )
+                                            .changed();
+                                    });
+                                }
+                                for (index, directional_light) in
+                                    scene.description.directional_lights.iter_mut().enumerate()
+                                {
+                                    ui.horizontal(|ui| {
+                                        ui.label(format!("Directional light {}", index));
+                                        lights_changed |=
+                                            color_edit(ui, &mut directional_light.color);
+                                        lights_changed |= ui
+                                            .add(
+                                                egui::DragValue::new(
+                                                    &mut directional_light.illuminance,
+                                                )
+                                                .speed(1000.0)
+                                                .clamp_range(0.0..=f32::MAX)
+                                                .suffix(" lx"),
+                                            )
+                                            .changed();
+                                    });
+                                }
+                                if lights_changed {
+                                    scene.upload_lights(
+                                        &queue,
+                                        &mut point_lights_buffer,
+                                        &mut directional_lights_buffer,
+                                    );
+                                }
+
+                                let mut materials_changed = false;
+                                for material in scene.description.materials.iter_mut() {
+                                    ui.horizontal(|ui| {
+                                        ui.label(&material.name);
+                                        materials_changed |= color_edit(ui, &mut material.color);
+                                        materials_changed |= ui
+                                            .add(
+                                                egui::Slider::new(
+                                                    &mut material.roughness,
+                                                    0.0..=1.0,
+                                                )
+                                                .text("roughness"),
+                                            )
+                                            .changed();
+                                        materials_changed |= ui
+                                            .add(
+                                                egui::Slider::new(
+                                                    &mut material.metallic,
+                                                    0.0..=1.0,
+                                                )
+                                                .text("metallic"),
+                                            )
+                                            .changed();
+                                    });
+                                }
+                                if materials_changed {
+                                    scene.upload_materials(&queue, &mut materials);
+                                }
+
+                                if ui.button("Save scene").clicked() {
+                                    match scene.save(&scene_file_name, camera.get()) {
+                                        Ok(()) => log::info!("saved {}", scene_file_name),
+                                        Err(error) => log::error!("{}", error),
+                                    }
+                                }
+                            });
+
+                            ui.collapsing("Animation", |ui| {
+                                if !scene.is_animated() {
+                                    ui.label("The scene doesn't have an animation.");
+                                    return;
+                                }
+                                let duration = scene.animation_duration();
+
+                                ui.horizontal(|ui| {
+                                    let label = if animation_playback.playing {
+                                        "Pause"
+                                    } else {
+                                        "Play"
+                                    };
+                                    if ui.button(label).clicked() {
+                                        // Playing from the end starts again.
+                                        if !animation_playback.playing
+                                            && animation_playback.time >= duration
+                                        {
+                                            animation_playback.time = 0.0;
+                                        }
+                                        animation_playback.playing = !animation_playback.playing;
+                                    }
+                                    ui.checkbox(&mut animation_playback.looping, "Loop");
+                                });
+
+                                animation_scrubbed |= ui
+                                    .add(
+                                        egui::Slider::new(
+                                            &mut animation_playback.time,
+                                            0.0..=duration,
+                                        )
+                                        .text("time")
+                                        .suffix(" s"),
+                                    )
+                                    .changed();
+                            });
+
+                            ui.collapsing("Picked object", |ui| {
+                                let Some(pick) = picked else {
+                                    ui.label(
+                                        "Press Escape to disable mouse look, then click on an \
+                                        object. Right click to enable mouse look again.",
+                                    );
+                                    return;
+                                };
+
+                                ui.label(format!(
+                                    "Model matrix {} (generation {})",
+                                    pick.model_matrix_id.index(),
+                                    pick.model_matrix_id.generation()
+                                ));
+                                if let Some(index) = scene.picked_object(&pick) {
+                                    let (_, object) = scene.description.objects()[index];
+                                    ui.label(format!("Object: {}", object.name));
+                                    ui.label(format!("Mesh: {:?}", object.mesh));
+
+                                    ui.horizontal(|ui| {
+                                        for mode in gizmo::Mode::ALL {
+                                            ui.radio_value(&mut gizmo_mode, mode, mode.name());
+                                        }
+                                    });
+
+                                    let mut transform = scene.object_transform(index);
+                                    let mut transform_changed = false;
+                                    for (label, vector, speed, suffix) in [
+                                        ("Translation", &mut transform.translation, 0.01, ""),
+                                        ("Rotation", &mut transform.rotation, 1.0, "°"),
+                                        ("Scale", &mut transform.scale, 0.01, ""),
+                                    ] {
+                                        ui.horizontal(|ui| {
+                                            ui.label(label);
+                                            for component in
+                                                [&mut vector.x, &mut vector.y, &mut vector.z]
+                                            {
+                                                transform_changed |= ui
+                                                    .add(
+                                                        egui::DragValue::new(component)
+                                                            .speed(speed)
+                                                            .suffix(suffix),
+                                                    )
+                                                    .changed();
+                                            }
+                                        });
+                                    }
+                                    if transform_changed {
+                                        scene.set_object_transform(index, transform);
+                                    }
+                                }
+                                if let Some(loaded_object) =
+                                    scene.loaded_object(pick.model_matrix_id)
+                                {
+                                    ui.label(format!("Part: {}", loaded_object.name));
+                                    ui.label(format!(
+                                        "Bounds: {:?} to {:?}",
+                                        loaded_object.aabb.min, loaded_object.aabb.max
+                                    ));
+                                }
+
+                                ui.label(format!(
+                                    "Material {} (generation {})",
+                                    pick.material_id.index(),
+                                    pick.material_id.generation()
+                                ));
+                                match scene.material_index(pick.material_id) {
+                                    Some(index) => {
+                                        let material = &scene.description.materials[index];
+                                        ui.label(format!("Material: {}", material.name));
+                                        ui.label(format!("Color: {:?}", material.color));
+                                        ui.label(format!("Roughness: {}", material.roughness));
+                                        ui.label(format!("Metallic: {}", material.metallic));
+                                    }
+                                    None => {
+                                        ui.label("Material: from the model file");
+                                    }
+                                }
+                            });
+
                             if ui.button("Exit").clicked() {
                                 *control_flow = ControlFlow::Exit;
                             }
@@ -1484,6 +1916,11 @@ fn main() {
                 queue.submit(std::iter::once(commands));
                 surface_texture.present();
 
+                if pick_requested {
+                    picked = object_id_targets.read(&device);
+                    pick_requested = false;
+                }
+
                 fps.end_frame();
             }
             Event::DeviceEvent {
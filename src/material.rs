@@ -1,9 +1,29 @@
-use crate::{color::Color, gpu_buffer::GpuBuffer};
+use crate::{
+    color::Color,
+    error::CapacityError,
+    gpu_buffer::{self, GpuBuffer},
+};
 
+/// A generational handle to a material in [`Materials`]. See
+/// [`ModelMatrixId`](crate::model_matrices::ModelMatrixId).
 #[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct MaterialId(pub u32);
 
+impl MaterialId {
+    pub fn new(index: u32, generation: u8) -> Self {
+        MaterialId(gpu_buffer::pack_id(index, generation))
+    }
+
+    pub fn index(self) -> u32 {
+        gpu_buffer::unpack_id(self.0).0
+    }
+
+    pub fn generation(self) -> u8 {
+        gpu_buffer::unpack_id(self.0).1
+    }
+}
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Material {
@@ -25,13 +45,53 @@ impl Materials {
         ))
     }
 
-    pub fn insert(&mut self, queue: &wgpu::Queue, data: Material) -> MaterialId {
-        let index = self.0.insert(queue, data);
-        MaterialId(index)
+    pub fn insert(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        data: Material,
+    ) -> Result<MaterialId, CapacityError> {
+        let index = self.0.insert(device, queue, data)?;
+        Ok(MaterialId::new(index, self.0.generation(index)))
+    }
+
+    /**
+    Insert materials into consecutive slots that have the same generation, so that a mesh's
+    vertices can refer to them by their offset from the first one (see
+    [`Instance::material_count`](crate::instance::Instance::material_count)).
+    */
+    pub fn insert_many(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        data: &[Material],
+    ) -> Result<Vec<MaterialId>, CapacityError> {
+        let first = self.0.insert_many(device, queue, data)?;
+        Ok((first..first + data.len() as u32)
+            .map(|index| MaterialId::new(index, self.0.generation(index)))
+            .collect())
+    }
+
+    /// Whether the id refers to a material that hasn't been removed.
+    pub fn contains(&self, id: MaterialId) -> bool {
+        self.0.contains(id.index()) && self.0.generation(id.index()) == id.generation()
+    }
+
+    pub fn update(&mut self, queue: &wgpu::Queue, id: MaterialId, value: Material) {
+        assert!(self.contains(id), "stale material id: {:?}", id);
+        self.0.update(queue, id.index(), value)
+    }
+
+    /// Zero the material and free its slot. Vertices that still refer to it will use whichever
+    /// material reuses the slot.
+    pub fn remove(&mut self, queue: &wgpu::Queue, id: MaterialId) {
+        assert!(self.contains(id), "stale material id: {:?}", id);
+        self.0.remove(queue, id.index())
     }
 
-    pub fn remove(&mut self, _material_id: MaterialId) {
-        todo!()
+    /// See [`GpuBuffer::take_reallocated`].
+    pub fn take_reallocated(&mut self) -> bool {
+        self.0.take_reallocated()
     }
 
     pub fn as_raw_buffer(&self) -> &wgpu::Buffer {
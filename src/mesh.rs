@@ -0,0 +1,83 @@
+use std::ops::Range;
+
+use crate::{
+    aabb::Aabb,
+    error::CapacityError,
+    index_buffer::{IndexBuffer, IndexRange},
+    vertex::Vertex,
+    vertex_buffer::VertexBuffer,
+};
+
+/// An indexed triangle mesh.
+pub struct Mesh {
+    pub vertices: Vec<Vertex>,
+
+    /// Every 3 indices are a triangle. Indices point into [`Mesh::vertices`].
+    pub indices: Vec<u32>,
+}
+
+impl Mesh {
+    /// The mesh's bounds in model space.
+    pub fn aabb(&self) -> Aabb {
+        self.vertices.iter().fold(Aabb::EMPTY, |aabb, vertex| {
+            aabb.union(Aabb::point(vertex.position))
+        })
+    }
+
+    /// Upload the mesh to the GPU, and return where its indices are in `index_buffer`. See
+    /// [`insert`].
+    pub fn insert(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        vertex_buffer: &mut VertexBuffer,
+        index_buffer: &mut IndexBuffer,
+    ) -> Result<IndexRange, CapacityError> {
+        let (_, index_range) = insert(
+            device,
+            queue,
+            vertex_buffer,
+            index_buffer,
+            &self.vertices,
+            &self.indices,
+        )?;
+        Ok(index_range)
+    }
+}
+
+/**
+Upload a mesh's vertices and indices to the GPU, and return where its vertices are in
+`vertex_buffer` and where its indices are in `index_buffer`.
+
+The vertices are uploaded as they are, so they can come straight from a memory-mapped file (see
+[`mesh_file`](crate::mesh_file)). The indices are offset so that they point at the vertices'
+positions in `vertex_buffer`.
+*/
+pub fn insert(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    vertex_buffer: &mut VertexBuffer,
+    index_buffer: &mut IndexBuffer,
+    vertices: &[Vertex],
+    indices: &[u32],
+) -> Result<(Range<u64>, IndexRange), CapacityError> {
+    /* Make room in the index buffer first, so that the vertices aren't added to the vertex buffer
+    when there isn't room for the indices.
+    */
+    index_buffer.reserve(device, queue, indices.len() as u64)?;
+
+    let first_vertex = vertex_buffer.insert_many(device, queue, vertices)?;
+
+    let indices: Vec<u32> = indices
+        .iter()
+        .map(|index| first_vertex as u32 + index)
+        .collect();
+    let first_index = index_buffer.insert_many(device, queue, &indices)? as u32;
+    Ok((
+        first_vertex..first_vertex + vertices.len() as u64,
+        IndexRange {
+            first_index,
+            index_count: indices.len() as u32,
+        },
+    ))
+}
@@ -0,0 +1,405 @@
+/*! A binary model format that can be loaded without parsing.
+
+A mesh file is a [`Model`] laid out the same way it is in memory, so that it can be
+memory-mapped and used in place, and its vertices uploaded without being touched. Values are in the
+byte order of the machine that wrote the file, which [`Header::byte_order`] records: files can only
+be read on machines with the same byte order. Every section is 4-byte aligned:
+
+1. [`Header`]
+2. `header.object_count` [`ObjectHeader`]s
+3. `header.material_count` [`Material`]s
+4. `header.vertex_count` [`Vertex`]s, with file-local ids (see [`Model`])
+5. `header.index_count` `u32` indices, for every object's [levels of detail](crate::lod)
+
+The version is bumped whenever the layout of any of these types changes.
+*/
+
+use std::{
+    borrow::Cow,
+    fmt,
+    fs::File,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use memmap2::Mmap;
+
+use crate::{
+    aabb::Aabb,
+    error::LoadError,
+    index_buffer::IndexRange,
+    load::{Model, ModelObject},
+    lod::MAX_LODS,
+    material::Material,
+    point::Point3,
+    vertex::Vertex,
+};
+
+pub const MAGIC: [u8; 4] = *b"MESH";
+pub const VERSION: u32 = 5;
+
+/// Reads as a different number on a machine with the other byte order.
+pub const BYTE_ORDER: u32 = 0x0102_0304;
+
+/// The conventional extension for mesh files.
+pub const EXTENSION: &str = "mesh";
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Header {
+    /// Always [`MAGIC`].
+    pub magic: [u8; 4],
+
+    /// [`BYTE_ORDER`], in the byte order of the machine that wrote the file.
+    pub byte_order: u32,
+    pub version: u32,
+    pub object_count: u32,
+    pub material_count: u32,
+    pub vertex_count: u32,
+    pub index_count: u32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ObjectHeader {
+    /// UTF-8, padded with zeros. Longer names are truncated.
+    pub name: [u8; 64],
+
+    /// The number of entries in `lods` that are used.
+    pub lod_count: u32,
+
+    /// See [`ModelObject::lods`].
+    pub lods: [IndexRange; MAX_LODS],
+    pub aabb_min: Point3,
+    pub aabb_max: Point3,
+}
+
+impl ObjectHeader {
+    fn new(object: &ModelObject) -> Self {
+        let mut name = [0; 64];
+
+        // Leave room for at least one zero, and don't split a character.
+        let mut name_len = object.name.len().min(name.len() - 1);
+        while !object.name.is_char_boundary(name_len) {
+            name_len -= 1;
+        }
+        name[..name_len].copy_from_slice(&object.name.as_bytes()[..name_len]);
+
+        let lod_count = object.lods.len().min(MAX_LODS);
+        let mut lods = [IndexRange {
+            first_index: 0,
+            index_count: 0,
+        }; MAX_LODS];
+        lods[..lod_count].copy_from_slice(&object.lods[..lod_count]);
+
+        ObjectHeader {
+            name,
+            lod_count: lod_count as u32,
+            lods,
+            aabb_min: object.aabb.min,
+            aabb_max: object.aabb.max,
+        }
+    }
+
+    fn to_object(self) -> ModelObject {
+        let name_len = self
+            .name
+            .iter()
+            .position(|byte| *byte == 0)
+            .unwrap_or(self.name.len());
+        ModelObject {
+            name: String::from_utf8_lossy(&self.name[..name_len]).into_owned(),
+            lods: self.lods[..self.lod_count as usize].to_vec(),
+            aabb: Aabb {
+                min: self.aabb_min,
+                max: self.aabb_max,
+            },
+        }
+    }
+}
+
+/// A file that isn't a valid mesh file.
+#[derive(Debug, Clone)]
+pub enum FormatError {
+    /// The file doesn't start with [`MAGIC`].
+    NotAMeshFile,
+
+    /// The file was written on a machine with a different byte order.
+    WrongByteOrder,
+
+    /// The file was written by a different version of the format.
+    UnsupportedVersion(u32),
+
+    /// The file's size doesn't match the sizes in its header.
+    WrongSize { expected: u64, actual: u64 },
+
+    /// An object, vertex or index refers to something that's not in the file.
+    OutOfBounds,
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatError::NotAMeshFile => write!(f, "not a mesh file"),
+            FormatError::WrongByteOrder => {
+                write!(f, "written on a machine with a different byte order")
+            }
+            FormatError::UnsupportedVersion(version) => {
+                write!(f, "unsupported version {} (expected {})", version, VERSION)
+            }
+            FormatError::WrongSize { expected, actual } => write!(
+                f,
+                "expected {} bytes, but the file has {}",
+                expected, actual
+            ),
+            FormatError::OutOfBounds => write!(f, "contains an out-of-bounds id or index"),
+        }
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+/// A memory-mapped mesh file.
+pub struct MeshFile {
+    mmap: Mmap,
+    header: Header,
+}
+
+impl MeshFile {
+    /**
+    Map a mesh file into memory and check that it's valid.
+
+    The file's contents aren't copied. The only work that's proportional to the size of the file
+    is a bounds check of the vertices' ids and the indices.
+    */
+    pub fn open(file_name: &str) -> Result<Self, LoadError> {
+        let format_error = |error| LoadError::MeshFile {
+            path: PathBuf::from(file_name),
+            error,
+        };
+
+        let file = File::open(file_name).map_err(|error| LoadError::Io {
+            path: PathBuf::from(file_name),
+            error,
+        })?;
+
+        /* Safety: the file mustn't be modified while it's mapped. Mesh files are build artifacts
+        (see `src/bin/convert_model.rs`), so nothing should be writing to them while the renderer
+        is running.
+        */
+        let mmap = unsafe { Mmap::map(&file) }.map_err(|error| LoadError::Io {
+            path: PathBuf::from(file_name),
+            error,
+        })?;
+
+        let header_size = std::mem::size_of::<Header>();
+        if mmap.len() < header_size {
+            return Err(format_error(FormatError::NotAMeshFile));
+        }
+        let header: Header = *bytemuck::from_bytes(&mmap[..header_size]);
+        if header.magic != MAGIC {
+            return Err(format_error(FormatError::NotAMeshFile));
+        }
+        if header.byte_order != BYTE_ORDER {
+            return Err(format_error(FormatError::WrongByteOrder));
+        }
+        if header.version != VERSION {
+            return Err(format_error(FormatError::UnsupportedVersion(
+                header.version,
+            )));
+        }
+
+        let expected_size = file_size(&header);
+        if mmap.len() as u64 != expected_size {
+            return Err(format_error(FormatError::WrongSize {
+                expected: expected_size,
+                actual: mmap.len() as u64,
+            }));
+        }
+
+        let mesh_file = MeshFile { mmap, header };
+        mesh_file.check_bounds().map_err(format_error)?;
+        Ok(mesh_file)
+    }
+
+    fn check_bounds(&self) -> Result<(), FormatError> {
+        let objects_in_bounds = self.object_headers().iter().all(|object| {
+            (1..=MAX_LODS as u32).contains(&object.lod_count)
+                && object.lods[..object.lod_count as usize].iter().all(|lod| {
+                    lod.first_index as u64 + lod.index_count as u64
+                        <= self.header.index_count as u64
+                })
+        });
+
+        let vertices_in_bounds = self.vertices().iter().all(|vertex| {
+            vertex.model_matrix_id.0 < self.header.object_count
+                && vertex.material_id.0 < self.header.material_count
+        });
+
+        let indices_in_bounds = self
+            .indices()
+            .iter()
+            .all(|index| *index < self.header.vertex_count);
+
+        if objects_in_bounds && vertices_in_bounds && indices_in_bounds {
+            Ok(())
+        } else {
+            Err(FormatError::OutOfBounds)
+        }
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Get the section of the file that starts at `offset` and contains `len` `T`s.
+    fn section<T: bytemuck::Pod>(&self, offset: usize, len: u32) -> &[T] {
+        bytemuck::cast_slice(&self.mmap[offset..offset + len as usize * std::mem::size_of::<T>()])
+    }
+
+    fn object_headers_offset(&self) -> usize {
+        std::mem::size_of::<Header>()
+    }
+
+    fn materials_offset(&self) -> usize {
+        self.object_headers_offset()
+            + self.header.object_count as usize * std::mem::size_of::<ObjectHeader>()
+    }
+
+    fn vertices_offset(&self) -> usize {
+        self.materials_offset()
+            + self.header.material_count as usize * std::mem::size_of::<Material>()
+    }
+
+    fn indices_offset(&self) -> usize {
+        self.vertices_offset() + self.header.vertex_count as usize * std::mem::size_of::<Vertex>()
+    }
+
+    pub fn object_headers(&self) -> &[ObjectHeader] {
+        self.section(self.object_headers_offset(), self.header.object_count)
+    }
+
+    pub fn materials(&self) -> &[Material] {
+        self.section(self.materials_offset(), self.header.material_count)
+    }
+
+    pub fn vertices(&self) -> &[Vertex] {
+        self.section(self.vertices_offset(), self.header.vertex_count)
+    }
+
+    pub fn indices(&self) -> &[u32] {
+        self.section(self.indices_offset(), self.header.index_count)
+    }
+
+    /// View the file as a [`Model`] that borrows its materials, vertices and indices.
+    pub fn model(&self) -> Model<'_> {
+        Model {
+            materials: Cow::Borrowed(self.materials()),
+            objects: self
+                .object_headers()
+                .iter()
+                .map(|object| object.to_object())
+                .collect(),
+            vertices: Cow::Borrowed(self.vertices()),
+            indices: Cow::Borrowed(self.indices()),
+        }
+    }
+}
+
+fn file_size(header: &Header) -> u64 {
+    std::mem::size_of::<Header>() as u64
+        + header.object_count as u64 * std::mem::size_of::<ObjectHeader>() as u64
+        + header.material_count as u64 * std::mem::size_of::<Material>() as u64
+        + header.vertex_count as u64 * std::mem::size_of::<Vertex>() as u64
+        + header.index_count as u64 * std::mem::size_of::<u32>() as u64
+}
+
+/// Write a model to a mesh file.
+pub fn write(path: &Path, model: &Model) -> io::Result<()> {
+    let header = Header {
+        magic: MAGIC,
+        byte_order: BYTE_ORDER,
+        version: VERSION,
+        object_count: model.objects.len() as u32,
+        material_count: model.materials.len() as u32,
+        vertex_count: model.vertices.len() as u32,
+        index_count: model.indices.len() as u32,
+    };
+    let object_headers: Vec<ObjectHeader> = model.objects.iter().map(ObjectHeader::new).collect();
+
+    let mut writer = io::BufWriter::new(File::create(path)?);
+    writer.write_all(bytemuck::bytes_of(&header))?;
+    writer.write_all(bytemuck::cast_slice(&object_headers))?;
+    writer.write_all(bytemuck::cast_slice(&model.materials))?;
+    writer.write_all(bytemuck::cast_slice(&model.vertices))?;
+    writer.write_all(bytemuck::cast_slice(&model.indices))?;
+    writer.flush()
+}
+
+#[test]
+fn test_mesh_file_round_trip() {
+    use crate::{color::Color, material::MaterialId, model_matrices::ModelMatrixId, shape};
+
+    let square = shape::square(ModelMatrixId(0), MaterialId(0), 1.0);
+    let model = Model {
+        materials: Cow::Owned(vec![Material {
+            color: Color {
+                r: 0.1,
+                g: 0.2,
+                b: 0.3,
+                a: 1.0,
+            },
+            roughness: 0.4,
+            metallic: 0.5,
+            _padding: [0, 0],
+        }]),
+        objects: vec![ModelObject {
+            name: String::from("square"),
+            lods: vec![
+                IndexRange {
+                    first_index: 0,
+                    index_count: square.indices.len() as u32,
+                },
+                IndexRange {
+                    first_index: 0,
+                    index_count: 3,
+                },
+            ],
+            aabb: square.aabb(),
+        }],
+        vertices: Cow::Borrowed(&square.vertices),
+        indices: Cow::Borrowed(&square.indices),
+    };
+
+    let path = std::env::temp_dir().join(format!("test_mesh_file_{}.mesh", std::process::id()));
+    write(&path, &model).unwrap();
+    let mesh_file = MeshFile::open(path.to_str().unwrap());
+    std::fs::remove_file(&path).unwrap();
+    let mesh_file = mesh_file.unwrap();
+
+    let read_model = mesh_file.model();
+    assert_eq!(read_model.objects.len(), 1);
+    assert_eq!(read_model.objects[0].name, "square");
+    assert_eq!(read_model.objects[0].lods, model.objects[0].lods);
+    assert_eq!(read_model.materials[0].roughness, 0.4);
+    assert_eq!(read_model.indices, model.indices);
+    assert_eq!(
+        bytemuck::cast_slice::<Vertex, u8>(&read_model.vertices),
+        bytemuck::cast_slice::<Vertex, u8>(&model.vertices)
+    );
+
+    // A file from a machine with the other byte order is rejected.
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&mesh_file.mmap);
+    bytes[4..8].reverse();
+    std::fs::write(&path, &bytes).unwrap();
+    let swapped = MeshFile::open(path.to_str().unwrap());
+    std::fs::remove_file(&path).unwrap();
+    assert!(matches!(
+        swapped,
+        Err(LoadError::MeshFile {
+            error: FormatError::WrongByteOrder,
+            ..
+        })
+    ));
+}
@@ -1,9 +1,32 @@
-use crate::{gpu_buffer::GpuBuffer, matrix::Matrix4};
+use crate::{
+    error::CapacityError,
+    gpu_buffer::{self, GpuBuffer},
+    matrix::Matrix4,
+};
 
+/**
+A generational handle to a model matrix: the matrix's index in [`ModelMatrices`], and the generation
+of its slot (see [`gpu_buffer::pack_id`]). An id of a removed model matrix never refers to the model
+matrix that reuses its slot, because slots are retired before their generations wrap around.
+*/
 #[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct ModelMatrixId(pub u32);
 
+impl ModelMatrixId {
+    pub fn new(index: u32, generation: u8) -> Self {
+        ModelMatrixId(gpu_buffer::pack_id(index, generation))
+    }
+
+    pub fn index(self) -> u32 {
+        gpu_buffer::unpack_id(self.0).0
+    }
+
+    pub fn generation(self) -> u8 {
+        gpu_buffer::unpack_id(self.0).1
+    }
+}
+
 pub struct ModelMatrices(GpuBuffer<Matrix4>);
 
 impl ModelMatrices {
@@ -16,17 +39,36 @@ impl ModelMatrices {
         ))
     }
 
-    pub fn insert(&mut self, queue: &wgpu::Queue, data: Matrix4) -> ModelMatrixId {
-        let index = self.0.insert(queue, data);
-        ModelMatrixId(index)
+    pub fn insert(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        data: Matrix4,
+    ) -> Result<ModelMatrixId, CapacityError> {
+        let index = self.0.insert(device, queue, data)?;
+        Ok(ModelMatrixId::new(index, self.0.generation(index)))
+    }
+
+    /// Whether the id refers to a model matrix that hasn't been removed.
+    pub fn contains(&self, id: ModelMatrixId) -> bool {
+        self.0.contains(id.index()) && self.0.generation(id.index()) == id.generation()
     }
 
     pub fn update(&mut self, queue: &wgpu::Queue, id: ModelMatrixId, value: Matrix4) {
-        self.0.update(queue, id.0, value)
+        assert!(self.contains(id), "stale model matrix id: {:?}", id);
+        self.0.update(queue, id.index(), value)
+    }
+
+    /// Zero the model matrix, which collapses the vertices that still refer to it, and free its
+    /// slot. Once the slot is reused, they'll use whichever model matrix reuses it.
+    pub fn remove(&mut self, queue: &wgpu::Queue, id: ModelMatrixId) {
+        assert!(self.contains(id), "stale model matrix id: {:?}", id);
+        self.0.remove(queue, id.index())
     }
 
-    pub fn remove(&mut self, _object_id: ModelMatrixId) {
-        todo!()
+    /// See [`GpuBuffer::take_reallocated`].
+    pub fn take_reallocated(&mut self) -> bool {
+        self.0.take_reallocated()
     }
 
     pub fn as_raw_buffer(&self) -> &wgpu::Buffer {
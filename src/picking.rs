@@ -0,0 +1,173 @@
+/*! Finding out which object is under the cursor.
+
+When picking, the HDR pass also writes each fragment's [`ModelMatrixId`] and [`MaterialId`] to a pair
+of [`FORMAT`] render targets ([`ObjectIdTargets`]). The ids under the cursor are then copied into a
+small buffer and read back on the CPU.
+*/
+
+use std::sync::mpsc;
+
+use crate::{material::MaterialId, model_matrices::ModelMatrixId};
+
+pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+
+/// The id that's left in the targets where nothing was drawn. It's never a valid id (see
+/// [`pack_id`](crate::gpu_buffer::pack_id)).
+pub const NOTHING: u32 = u32::MAX;
+
+/// The ids of the object that was drawn at a pixel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pick {
+    pub model_matrix_id: ModelMatrixId,
+    pub material_id: MaterialId,
+}
+
+impl Pick {
+    /// The pick for a pair of ids read from [`ObjectIdTargets`], or `None` when nothing was drawn.
+    pub fn from_ids(model_matrix_id: u32, material_id: u32) -> Option<Self> {
+        if model_matrix_id == NOTHING {
+            return None;
+        }
+        Some(Pick {
+            model_matrix_id: ModelMatrixId(model_matrix_id),
+            material_id: MaterialId(material_id),
+        })
+    }
+}
+
+pub struct ObjectIdTargets {
+    model_matrix_ids_view: wgpu::TextureView,
+    material_ids_view: wgpu::TextureView,
+    model_matrix_ids: wgpu::Texture,
+    material_ids: wgpu::Texture,
+
+    /// Holds the model matrix id at offset 0 and the material id at
+    /// [`wgpu::COPY_BYTES_PER_ROW_ALIGNMENT`].
+    readback: wgpu::Buffer,
+}
+
+impl ObjectIdTargets {
+    /// Targets for a `width` by `height` HDR render target.
+    pub fn new(device: &wgpu::Device, width: u32, height: u32) -> Self {
+        let create_texture = |label| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: FORMAT,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+                view_formats: &[],
+            })
+        };
+        let model_matrix_ids = create_texture("object_id_targets_model_matrix_ids");
+        let material_ids = create_texture("object_id_targets_material_ids");
+
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("object_id_targets_readback"),
+            size: wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as u64 + std::mem::size_of::<u32>() as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            model_matrix_ids_view: model_matrix_ids
+                .create_view(&wgpu::TextureViewDescriptor::default()),
+            material_ids_view: material_ids.create_view(&wgpu::TextureViewDescriptor::default()),
+            model_matrix_ids,
+            material_ids,
+            readback,
+        }
+    }
+
+    pub fn model_matrix_ids_view(&self) -> &wgpu::TextureView {
+        &self.model_matrix_ids_view
+    }
+
+    pub fn material_ids_view(&self) -> &wgpu::TextureView {
+        &self.material_ids_view
+    }
+
+    /// Copy the ids at a pixel into the readback buffer. Record this after the HDR pass that wrote
+    /// the ids, and call [`ObjectIdTargets::read`] once it's been submitted.
+    pub fn record_readback(&self, command_encoder: &mut wgpu::CommandEncoder, x: u32, y: u32) {
+        for (texture, offset) in [
+            (&self.model_matrix_ids, 0),
+            (
+                &self.material_ids,
+                wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as u64,
+            ),
+        ] {
+            command_encoder.copy_texture_to_buffer(
+                wgpu::ImageCopyTexture {
+                    texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d { x, y, z: 0 },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                wgpu::ImageCopyBuffer {
+                    buffer: &self.readback,
+                    layout: wgpu::ImageDataLayout {
+                        offset,
+                        bytes_per_row: Some(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT),
+                        rows_per_image: None,
+                    },
+                },
+                wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+    }
+
+    /**
+    Read the ids that [`ObjectIdTargets::record_readback`] copied.
+
+    Blocks until the GPU has finished the submitted work, so it should only be called when the user
+    has asked for a pick (e.g. on a click).
+    */
+    pub fn read(&self, device: &wgpu::Device) -> Option<Pick> {
+        let slice = self.readback.slice(..);
+        let (sender, receiver) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        device.poll(wgpu::Maintain::Wait);
+        if let Err(error) = receiver.recv().expect("map_async callback wasn't called") {
+            log::error!("failed to read object ids: {}", error);
+            return None;
+        }
+
+        let (model_matrix_id, material_id) = {
+            let data = slice.get_mapped_range();
+            let id_at = |offset: usize| -> u32 {
+                *bytemuck::from_bytes(&data[offset..offset + std::mem::size_of::<u32>()])
+            };
+            (id_at(0), id_at(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as usize))
+        };
+        self.readback.unmap();
+        Pick::from_ids(model_matrix_id, material_id)
+    }
+}
+
+#[test]
+fn test_pick_from_ids() {
+    assert_eq!(Pick::from_ids(NOTHING, NOTHING), None);
+
+    let model_matrix_id = ModelMatrixId::new(3, 1);
+    let material_id = MaterialId::new(5, 0);
+    assert_eq!(
+        Pick::from_ids(model_matrix_id.0, material_id.0),
+        Some(Pick {
+            model_matrix_id,
+            material_id,
+        })
+    );
+}
@@ -3,7 +3,16 @@ use std::ops::{Add, Sub};
 use crate::vector::Vec3;
 
 #[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    bytemuck::Pod,
+    bytemuck::Zeroable,
+    PartialEq,
+    serde::Serialize,
+    serde::Deserialize,
+)]
 pub struct Point3 {
     pub x: f32,
     pub y: f32,
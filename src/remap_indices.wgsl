@@ -0,0 +1,47 @@
+// A range of vertices that moved to `to..to + (from_end - from_start)`. See `gpu_buffer::Relocation`.
+struct Relocation {
+  from_start: u32,
+  from_end: u32,
+  to: u32,
+}
+
+@group(0) @binding(0)
+var<storage, read_write> indices: array<u32>;
+
+// Sorted, and non-overlapping.
+@group(0) @binding(1)
+var<storage, read> relocations: array<Relocation>;
+
+@group(0) @binding(2)
+var<uniform> index_count: u32;
+
+/* Each invocation moves one index to its vertex's new position. The dispatch has more than one row
+of workgroups when there are too many indices for a single row.
+*/
+@compute @workgroup_size(64)
+fn remap(
+  @builtin(global_invocation_id) global_id: vec3<u32>,
+  @builtin(num_workgroups) num_workgroups: vec3<u32>
+) {
+  let index = global_id.y * num_workgroups.x * 64u + global_id.x;
+  if index >= index_count {
+    return;
+  }
+
+  // The first relocation that ends after the vertex.
+  let vertex = indices[index];
+  var low = 0u;
+  var high = arrayLength(&relocations);
+  while low < high {
+    let middle = (low + high) / 2u;
+    if relocations[middle].from_end <= vertex {
+      low = middle + 1u;
+    } else {
+      high = middle;
+    }
+  }
+
+  if low < arrayLength(&relocations) && relocations[low].from_start <= vertex {
+    indices[index] = vertex - relocations[low].from_start + relocations[low].to;
+  }
+}
@@ -3,9 +3,13 @@ use crate::{
     gpu_buffer::GpuBuffer,
     gpu_flag::GpuFlag,
     gpu_variable::GpuVariable,
+    index_buffer::IndexBuffer,
+    instance::{Draws, Instance, InstanceBuffer},
+    joint_matrices::JointMatrices,
     light::{DirectionalLightGpu, PointLightGpu},
     material::Materials,
     model_matrices::ModelMatrices,
+    picking::{self, ObjectIdTargets},
     shadow_maps,
     vertex::Vertex,
     vertex_buffer::VertexBuffer,
@@ -19,6 +23,9 @@ pub struct RenderHdr {
     pub pipeline_layout: wgpu::PipelineLayout,
     pub shader_module: wgpu::ShaderModule,
     pub render_pipeline: wgpu::RenderPipeline,
+
+    /// Also writes to [`ObjectIdTargets`], for picking.
+    pub render_pipeline_object_ids: wgpu::RenderPipeline,
 }
 
 impl RenderHdr {
@@ -40,58 +47,78 @@ impl RenderHdr {
 
         let shader_module = device.create_shader_module(wgpu::include_wgsl!("render_hdr.wgsl"));
 
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("render_hdr_pipeline"),
-            layout: Some(&pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader_module,
-                entry_point: "vertex_main",
-                buffers: &[Vertex::LAYOUT],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader_module,
-                entry_point: "fragment_main",
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: render_target_format,
-                    // HDR render target format (Rgba32Float) doesn't support blending.
-                    blend: None,
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                unclipped_depth: false,
-                polygon_mode: wgpu::PolygonMode::Fill,
-                conservative: false,
-            },
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: depth_texture_format,
-                // If this is disabled then depth testing won't happen.
-                depth_write_enabled: true,
-                /*
-                WebGPU doesn't specify a Z direction for NDC:
-                <https://www.reddit.com/r/wgpu/comments/tilvas/is_your_wgpu_world_left_or_right_handed/iykwrp0/>
-
-                The Z direction is implied by the projection matrix, and the depth test needs to bet
-                configured to match. If the projection matrix makes model_matrices with high Z smaller (left-handed coordinates / "+Z in"),
-                then the closest fragment is the one with the smallest Z, which means we need to
-                clear to 1.0 (max Z / far plane) and use the `Less` comparison.
-
-                Conversely, if the projection matrix made model_matrices with low Z smaller (right-handed / "+Z out"),
-                then we'd need to clear to 0.0 (min Z / far plane) and use the `Greater` comparison.
-                */
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                // What's depth bias?
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multisample: wgpu::MultisampleState::default(),
-            // What's a multiview render pass?
-            multiview: None,
+        let create_render_pipeline =
+            |label, fragment_entry_point, targets: &[Option<wgpu::ColorTargetState>]| {
+                device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some(label),
+                    layout: Some(&pipeline_layout),
+                    vertex: wgpu::VertexState {
+                        module: &shader_module,
+                        entry_point: "vertex_main",
+                        buffers: &[Vertex::LAYOUT, Instance::LAYOUT],
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: &shader_module,
+                        entry_point: fragment_entry_point,
+                        targets,
+                    }),
+                    primitive: wgpu::PrimitiveState {
+                        topology: wgpu::PrimitiveTopology::TriangleList,
+                        strip_index_format: None,
+                        front_face: wgpu::FrontFace::Ccw,
+                        cull_mode: Some(wgpu::Face::Back),
+                        unclipped_depth: false,
+                        polygon_mode: wgpu::PolygonMode::Fill,
+                        conservative: false,
+                    },
+                    depth_stencil: Some(wgpu::DepthStencilState {
+                        format: depth_texture_format,
+                        // If this is disabled then depth testing won't happen.
+                        depth_write_enabled: true,
+                        /*
+                        WebGPU doesn't specify a Z direction for NDC:
+                        <https://www.reddit.com/r/wgpu/comments/tilvas/is_your_wgpu_world_left_or_right_handed/iykwrp0/>
+
+                        The Z direction is implied by the projection matrix, and the depth test needs to bet
+                        configured to match. If the projection matrix makes model_matrices with high Z smaller (left-handed coordinates / "+Z in"),
+                        then the closest fragment is the one with the smallest Z, which means we need to
+                        clear to 1.0 (max Z / far plane) and use the `Less` comparison.
+
+                        Conversely, if the projection matrix made model_matrices with low Z smaller (right-handed / "+Z out"),
+                        then we'd need to clear to 0.0 (min Z / far plane) and use the `Greater` comparison.
+                        */
+                        depth_compare: wgpu::CompareFunction::Less,
+                        stencil: wgpu::StencilState::default(),
+                        // What's depth bias?
+                        bias: wgpu::DepthBiasState::default(),
+                    }),
+                    multisample: wgpu::MultisampleState::default(),
+                    // What's a multiview render pass?
+                    multiview: None,
+                })
+            };
+
+        let hdr_target = Some(wgpu::ColorTargetState {
+            format: render_target_format,
+            // HDR render target format (Rgba32Float) doesn't support blending.
+            blend: None,
+            write_mask: wgpu::ColorWrites::ALL,
         });
+        let object_id_target = Some(wgpu::ColorTargetState {
+            format: picking::FORMAT,
+            blend: None,
+            write_mask: wgpu::ColorWrites::ALL,
+        });
+        let render_pipeline = create_render_pipeline(
+            "render_hdr_pipeline",
+            "fragment_main",
+            std::slice::from_ref(&hdr_target),
+        );
+        let render_pipeline_object_ids = create_render_pipeline(
+            "render_hdr_pipeline_object_ids",
+            "fragment_main_object_ids",
+            &[hdr_target, object_id_target.clone(), object_id_target],
+        );
 
         Self {
             bind_group_layout_0,
@@ -101,15 +128,28 @@ impl RenderHdr {
             pipeline_layout,
             shader_module,
             render_pipeline,
+            render_pipeline_object_ids,
         }
     }
 
+    pub fn set_bind_group_0(&mut self, device: &wgpu::Device, bind_group_0: BindGroup0) {
+        let (bind_group_layout_0, bind_group_0) = bind_group_0.create(device);
+        self.bind_group_layout_0 = bind_group_layout_0;
+        self.bind_group_0 = bind_group_0;
+    }
+
+    /// When `object_id_targets` is given, the drawn objects' ids are also written to it.
+    #[allow(clippy::too_many_arguments)]
     pub fn record(
         &self,
         command_encoder: &mut wgpu::CommandEncoder,
         hdr_render_target_view: &wgpu::TextureView,
         depth_texture_view: &wgpu::TextureView,
+        object_id_targets: Option<&ObjectIdTargets>,
         vertex_buffer: &VertexBuffer,
+        index_buffer: &IndexBuffer,
+        instance_buffer: &InstanceBuffer,
+        draws: &Draws,
     ) {
         /* What is an "attachment"?
 
@@ -128,16 +168,39 @@ impl RenderHdr {
 
         See also: <https://stackoverflow.com/questions/46384007/what-is-the-meaning-of-attachment-when-speaking-about-the-vulkan-api>
         */
-        let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            label: Some("render_hdr_pass"),
-            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: hdr_render_target_view,
+        let object_id_attachment = |view| {
+            Some(wgpu::RenderPassColorAttachment {
+                view,
                 resolve_target: None,
                 ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Load,
+                    load: wgpu::LoadOp::Clear(wgpu::Color {
+                        r: picking::NOTHING as f64,
+                        g: 0.0,
+                        b: 0.0,
+                        a: 0.0,
+                    }),
                     store: true,
                 },
-            })],
+            })
+        };
+        let mut color_attachments = vec![Some(wgpu::RenderPassColorAttachment {
+            view: hdr_render_target_view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Load,
+                store: true,
+            },
+        })];
+        if let Some(object_id_targets) = object_id_targets {
+            color_attachments.push(object_id_attachment(
+                object_id_targets.model_matrix_ids_view(),
+            ));
+            color_attachments.push(object_id_attachment(object_id_targets.material_ids_view()));
+        }
+
+        let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("render_hdr_pass"),
+            color_attachments: &color_attachments,
             depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
                 view: depth_texture_view,
                 depth_ops: Some(wgpu::Operations {
@@ -153,11 +216,16 @@ impl RenderHdr {
                 stencil_ops: None,
             }),
         });
-        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_pipeline(match object_id_targets {
+            Some(_) => &self.render_pipeline_object_ids,
+            None => &self.render_pipeline,
+        });
         render_pass.set_bind_group(0, &self.bind_group_0, &[]);
         render_pass.set_bind_group(1, &self.bind_group_1, &[]);
         render_pass.set_vertex_buffer(0, vertex_buffer.as_raw_slice());
-        render_pass.draw(0..vertex_buffer.len() as u32, 0..1);
+        render_pass.set_vertex_buffer(1, instance_buffer.as_raw_slice());
+        render_pass.set_index_buffer(index_buffer.as_raw_slice(), IndexBuffer::FORMAT);
+        draws.record(&mut render_pass);
     }
 }
 
@@ -173,6 +241,7 @@ pub struct BindGroup0<'a> {
     pub shadow_map_lights: &'a GpuBuffer<shadow_maps::Light>,
     pub sky_texture: &'a wgpu::TextureView,
     pub sky_texture_sampler: &'a wgpu::Sampler,
+    pub joint_matrices: &'a JointMatrices,
 }
 
 impl<'a> BindGroup0<'a> {
@@ -406,6 +475,29 @@ impl<'a> BindGroup0<'a> {
             },
         );
 
+        // @group(0) @binding(11)
+        // var<storage, read> joint_matrices: array<mat4x4<f32>>;
+        let joint_matrices = (
+            wgpu::BindGroupLayoutEntry {
+                binding: 11,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupEntry {
+                binding: 11,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: self.joint_matrices.as_raw_buffer(),
+                    offset: 0,
+                    size: None,
+                }),
+            },
+        );
+
         let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("render_hdr_bind_group_layout_0"),
             entries: &[
@@ -420,6 +512,7 @@ impl<'a> BindGroup0<'a> {
                 shadow_map_lights.0,
                 sky_texture.0,
                 sky_texture_sampler.0,
+                joint_matrices.0,
             ],
         });
 
@@ -438,6 +531,7 @@ impl<'a> BindGroup0<'a> {
                 shadow_map_lights.1,
                 sky_texture.1,
                 sky_texture_sampler.1,
+                joint_matrices.1,
             ],
         });
 
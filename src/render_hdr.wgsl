@@ -12,6 +12,9 @@ fn get_model_matrix(model_matrices: array<ModelMatrixData>, model_matrix_id: Mod
 }
 */
 
+// Ids of model matrices and materials hold a generation in their high bits. See `gpu_buffer::pack_id`.
+const ID_INDEX_MASK: u32 = 0xffffffu;
+
 struct Camera{
   eye: vec3<f32>,
   zfar: f32,
@@ -90,6 +93,9 @@ var sky_texture: texture_2d<f32>;
 @group(0) @binding(10)
 var sky_texture_sampler: sampler;
 
+@group(0) @binding(11)
+var<storage, read> joint_matrices: array<mat4x4<f32>>;
+
 @group(1) @binding(0)
 var<uniform> show_directional_shadow_map_coverage: u32; // bool
 
@@ -112,29 +118,79 @@ fn srgb_to_linear(srgb: vec4<f32>) -> vec4<f32> {
 
 struct VertexInput{
   @location(0) position: vec3<f32>,
-  @location(1) model_matrix_id: u32,
   @location(2) normal: vec3<f32>,
-  @location(3) material_id: u32
+  @location(3) material_id: u32,
+  @location(4) uv: vec2<f32>,
+  @location(5) tangent: vec4<f32>,
+  @location(6) joints: vec4<u32>,
+  @location(7) weights: vec4<f32>
+}
+
+// See `instance.rs`.
+struct InstanceInput{
+  @location(8) model_matrix_id: u32,
+  @location(9) material_id: u32,
+  @location(10) material_count: u32
+}
+
+fn is_skinned(weights: vec4<f32>) -> bool {
+  return any(weights != vec4<f32>(0.0));
+}
+
+/*
+Takes a vertex to world space. Skinned vertices are moved by a blend of their joints' matrices,
+which take them to their skeleton's coordinates, and then by their instance's model matrix like
+other vertices, so each instance of a skinned mesh is placed by its own model matrix.
+
+Also defined in `shadow_maps.wgsl`, identically.
+*/
+fn vertex_transform(input: VertexInput, instance: InstanceInput) -> mat4x4<f32> {
+  let model_matrix = model_matrices[instance.model_matrix_id & ID_INDEX_MASK];
+  if !is_skinned(input.weights) {
+    return model_matrix;
+  }
+  return model_matrix * (
+    input.weights.x * joint_matrices[input.joints.x & ID_INDEX_MASK] +
+    input.weights.y * joint_matrices[input.joints.y & ID_INDEX_MASK] +
+    input.weights.z * joint_matrices[input.joints.z & ID_INDEX_MASK] +
+    input.weights.w * joint_matrices[input.joints.w & ID_INDEX_MASK]
+  );
+}
+
+/* A vertex's material is an offset from its instance's material. The instance's materials share a
+generation (see `Materials::insert_many`), so adding to the id only changes its index.
+*/
+fn instance_material_id(input: VertexInput, instance: InstanceInput) -> u32 {
+  return instance.material_id + min(input.material_id, instance.material_count - 1u);
 }
 
 @vertex
-fn vertex_main(input: VertexInput) -> VertexOutput {
+fn vertex_main(input: VertexInput, instance: InstanceInput) -> VertexOutput {
   var output: VertexOutput;
 
-  let world_position = model_matrices[input.model_matrix_id] * vec4<f32>(input.position, 1.0);
+  let transform = vertex_transform(input, instance);
+  let world_position = transform * vec4<f32>(input.position, 1.0);
   output.world_position = world_position.xyz / world_position.w;
   output.position = camera.view_proj * world_position;
 
   // Nothing told me that I was forgetting to attach normals!
-  // The normal can only get passed through for translations.
-  output.normal = input.normal;
+  // The normal can only get passed through for translations. Joints rotate skinned vertices, so
+  // their normals are rotated too (which is right as long as the joints aren't scaled unevenly).
+  if is_skinned(input.weights) {
+    output.normal = normalize((transform * vec4<f32>(input.normal, 0.0)).xyz);
+  } else {
+    output.normal = input.normal;
+  }
+
+  output.model_matrix_id = instance.model_matrix_id;
+  output.material_id = instance_material_id(input, instance);
 
   if display_normals == 1u {
     output.albedo = vec4<f32>(output.normal, 1.0);
     output.roughness = 1.0;
     output.metallic = 0.0;
   } else {
-    let material = materials[input.material_id];
+    let material = materials[output.material_id & ID_INDEX_MASK];
     output.albedo = srgb_to_linear(material.color);
     output.roughness = material.roughness;
     output.metallic = material.metallic;
@@ -150,6 +206,8 @@ struct VertexOutput{
   @location(2) albedo: vec4<f32>,
   @location(3) roughness: f32,
   @location(4) metallic: f32,
+  @location(5) @interpolate(flat) model_matrix_id: u32,
+  @location(6) @interpolate(flat) material_id: u32,
 }
 
 const PI: f32 = 3.14159;
@@ -257,6 +315,30 @@ struct FragmentOutput{
 
 @fragment
 fn fragment_main(input: VertexOutput) -> FragmentOutput {
+  return shade(input);
+}
+
+struct FragmentOutputWithObjectIds{
+  @location(0) color: vec4<f32>,
+  @location(1) model_matrix_id: u32,
+  @location(2) material_id: u32,
+  @builtin(frag_depth) depth: f32
+}
+
+// Also writes the fragment's ids to the object id targets (see `picking.rs`).
+@fragment
+fn fragment_main_object_ids(input: VertexOutput) -> FragmentOutputWithObjectIds {
+  let shaded = shade(input);
+
+  var output: FragmentOutputWithObjectIds;
+  output.color = shaded.color;
+  output.depth = shaded.depth;
+  output.model_matrix_id = input.model_matrix_id;
+  output.material_id = input.material_id;
+  return output;
+}
+
+fn shade(input: VertexOutput) -> FragmentOutput {
   var output: FragmentOutput; 
   output.depth = log2(max(1e-6, 1.0 / input.position.w)) * (1.0 / log2(camera.zfar + 1.0));
   
@@ -277,9 +359,13 @@ fn fragment_main(input: VertexOutput) -> FragmentOutput {
 
     for (var i: u32 = 0u; i < arrayLength(&point_lights); i++) {
       let point_light = point_lights[i];
+      // Unused and removed slots are zeroed.
+      if point_light.luminous_power == 0.0 {
+        continue;
+      }
 
       // TODO: don't recalculate this for every fragment.
-      let point_light_position: vec4<f32> = model_matrices[point_light.model_matrix_id] * vec4<f32>(0.0, 0.0, 0.0, 1.0);
+      let point_light_position: vec4<f32> = model_matrices[point_light.model_matrix_id & ID_INDEX_MASK] * vec4<f32>(0.0, 0.0, 0.0, 1.0);
 
       // fragment to light
       let light_direction: vec3<f32> = normalize((point_light_position.xyz / point_light_position.w) - input.world_position); 
@@ -368,6 +454,10 @@ fn fragment_main(input: VertexOutput) -> FragmentOutput {
     
     for (var i: u32 = 0u; i < arrayLength(&directional_lights); i++) {
       let directional_light = directional_lights[i];
+      // Unused and removed slots are zeroed.
+      if directional_light.illuminance == 0.0 {
+        continue;
+      }
 
       let light_direction: vec3<f32> = -directional_light.direction; 
       
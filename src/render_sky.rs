@@ -89,6 +89,12 @@ impl RenderSky {
         }
     }
 
+    pub fn set_bind_group_0(&mut self, device: &wgpu::Device, bind_group_0: BindGroup0) {
+        let (bind_group_layout_0, bind_group_0) = bind_group_0.create(device);
+        self.bind_group_layout_0 = bind_group_layout_0;
+        self.bind_group_0 = bind_group_0;
+    }
+
     pub fn record(
         &self,
         command_encoder: &mut wgpu::CommandEncoder,
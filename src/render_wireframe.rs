@@ -43,10 +43,12 @@ pub struct RenderWireframe {
 }
 
 impl RenderWireframe {
+    /// Lines are drawn where `depth_compare` passes against the depth target, which isn't written to.
     pub fn new(
         device: &wgpu::Device,
         render_target_format: wgpu::TextureFormat,
         depth_format: wgpu::TextureFormat,
+        depth_compare: wgpu::CompareFunction,
         bind_group_0: BindGroup0,
     ) -> Self {
         let (bind_group_layout_0, bind_group_0) = bind_group_0.create(device);
@@ -88,7 +90,7 @@ impl RenderWireframe {
             depth_stencil: Some(wgpu::DepthStencilState {
                 format: depth_format,
                 depth_write_enabled: false,
-                depth_compare: wgpu::CompareFunction::Less,
+                depth_compare,
                 stencil: wgpu::StencilState::default(),
                 bias: wgpu::DepthBiasState::default(),
             }),
@@ -105,6 +107,12 @@ impl RenderWireframe {
         }
     }
 
+    pub fn set_bind_group_0(&mut self, device: &wgpu::Device, bind_group_0: BindGroup0) {
+        let (bind_group_layout_0, bind_group_0) = bind_group_0.create(device);
+        self.bind_group_layout_0 = bind_group_layout_0;
+        self.bind_group_0 = bind_group_0;
+    }
+
     pub fn record(
         &self,
         command_encoder: &mut wgpu::CommandEncoder,
@@ -12,6 +12,9 @@ var<uniform> camera: Camera;
 @group(0) @binding(1)
 var<storage, read> model_matrices: array<mat4x4<f32>>;
 
+// Ids of model matrices and materials hold a generation in their high bits. See `gpu_buffer::pack_id`.
+const ID_INDEX_MASK: u32 = 0xffffffu;
+
 struct VertexInput{
   @location(0) position: vec3<f32>,
   @location(1) model_matrix_id: u32,
@@ -21,7 +24,7 @@ struct VertexInput{
 fn vertex_main(input: VertexInput) -> @builtin(position) vec4<f32> {
   return
     camera.view_proj *
-    model_matrices[input.model_matrix_id] *
+    model_matrices[input.model_matrix_id & ID_INDEX_MASK] *
     vec4<f32>(input.position, 1.0);
 }
 
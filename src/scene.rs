@@ -0,0 +1,1458 @@
+/*! Scene files.
+
+A scene file is a [RON](https://github.com/ron-rs/ron) description ([`SceneDescription`]) of
+everything the viewer shows: the camera, the sky, materials, objects, lights and their animation. [`Scene::load`]
+reads a scene file and uploads it, and [`Scene::save`] writes it back with any changes that have
+been made in the viewer.
+
+File paths in a scene file (models and the HDRI) are relative to the working directory.
+
+```ron
+(
+    camera: (
+        eye: (x: 0.0, y: 0.0, z: 1.0),
+        direction: (x: 0.0, y: 0.0, z: -1.0),
+        up: (x: 0.0, y: 1.0, z: 0.0),
+        fovy: 45.0,
+        near: 0.1,
+        far: 100.0,
+    ),
+    sky: (hdri: Some("hdris/sky.hdr"), intensity: 80000.0),
+    materials: [
+        (name: "red", color: (r: 1.0, g: 0.0, b: 0.0, a: 1.0), roughness: 0.5, metallic: 0.0),
+    ],
+    objects: [
+        (
+            name: "monkey",
+            mesh: Model(path: "models/monkey.obj"),
+            material: Some("red"),
+            transform: (translation: (x: 0.0, y: 0.0, z: -10.0)),
+        ),
+    ],
+    point_lights: [],
+    directional_lights: [],
+)
+```
+*/
+
+use std::{collections::HashMap, fs, ops::Range, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    aabb::Aabb,
+    animation::{Animated, Animation},
+    asset_loader::{AssetId, AssetLoader},
+    camera::Camera,
+    color::Color,
+    error::{CapacityError, LoadError, SaveError},
+    frustum::Frustum,
+    gpu_buffer::{GpuBuffer, Relocation},
+    index_buffer::{IndexBuffer, IndexRange},
+    joint_matrices::JointMatrices,
+    light::{
+        fit_orthographic_projection_to_camera, DirectionalLight, DirectionalLightGpu, PointLight,
+        PointLightGpu, PointLightShadowMapFace, PointLightShadowMapFaces, ShadowMapLightIds,
+    },
+    load::{self, LoadOptions, LoadedModel, LoadedObject, Model},
+    load_gltf::{self, GltfScene, LoadedGltf, LoadedSkin},
+    lod,
+    material::{Material, MaterialId, Materials},
+    matrix::Matrix4,
+    mesh::Mesh,
+    model_matrices::{ModelMatrices, ModelMatrixId},
+    picking::Pick,
+    point::Point3,
+    render_wireframe,
+    scene_graph::{NodeId, SceneGraph},
+    shadow_map_atlas::ShadowMapAtlas,
+    shadow_maps, shape,
+    skin::SkeletonAnimation,
+    vector::Vec3,
+    vertex_buffer::VertexBuffer,
+    wireframe::{self, Wireframe},
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneDescription {
+    pub camera: CameraDescription,
+    pub sky: SkyDescription,
+
+    #[serde(default)]
+    pub materials: Vec<MaterialDescription>,
+
+    #[serde(default)]
+    pub objects: Vec<ObjectDescription>,
+
+    #[serde(default)]
+    pub point_lights: Vec<PointLightDescription>,
+
+    #[serde(default)]
+    pub directional_lights: Vec<DirectionalLightDescription>,
+
+    /// Keyframes for the objects, lights and camera. See [`animation`](crate::animation).
+    #[serde(default, skip_serializing_if = "Animation::is_empty")]
+    pub animation: Animation,
+}
+
+/// A [`Camera`], without its aspect ratio (which comes from the window).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CameraDescription {
+    pub eye: Point3,
+    pub direction: Vec3,
+    pub up: Vec3,
+    pub fovy: f32,
+    pub near: f32,
+    pub far: f32,
+}
+
+impl CameraDescription {
+    pub fn camera(&self, aspect: f32) -> Camera {
+        Camera {
+            eye: self.eye,
+            direction: self.direction.into(),
+            up: self.up.into(),
+            aspect,
+            fovy: self.fovy,
+            near: self.near,
+            far: self.far,
+        }
+    }
+}
+
+impl From<&Camera> for CameraDescription {
+    fn from(camera: &Camera) -> Self {
+        CameraDescription {
+            eye: camera.eye,
+            direction: camera.direction.into(),
+            up: camera.up.into(),
+            fovy: camera.fovy,
+            near: camera.near,
+            far: camera.far,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SkyDescription {
+    /// A Radiance HDR file. The sky is black when there isn't one.
+    #[serde(default)]
+    pub hdri: Option<String>,
+
+    /// Scales the HDRI's values, which are relative, to luminance in nits.
+    pub intensity: f32,
+}
+
+/// A [`Material`], named so that objects can refer to it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MaterialDescription {
+    pub name: String,
+    pub color: Color,
+    pub roughness: f32,
+    pub metallic: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ObjectDescription {
+    pub name: String,
+    pub mesh: MeshDescription,
+
+    /**
+    The name of one of the scene's materials.
+
+    Required for shapes. Models use the materials from their file's `.mtl` library when they don't
+    have one.
+    */
+    #[serde(default)]
+    pub material: Option<String>,
+
+    /// Relative to the object's parent, when it has one.
+    #[serde(default)]
+    pub transform: Transform,
+
+    /// Objects that move with this one.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<ObjectDescription>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum MeshDescription {
+    /// See [`shape::triangle`].
+    Triangle,
+
+    /// See [`shape::square`].
+    Square { side: f32 },
+
+    /// See [`shape::floor`].
+    Floor { side: f32 },
+
+    /// See [`shape::sphere`].
+    Sphere { radius: f32 },
+
+    /// A model file (any format that [`load::read_model`] supports, including mesh files, or a
+    /// glTF file), loaded in the background. See [`AssetLoader::load_model`].
+    Model {
+        path: String,
+
+        /// See [`LoadOptions::merge`].
+        #[serde(default)]
+        merge: bool,
+    },
+}
+
+/// Scale, then rotate, then translate.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Transform {
+    pub translation: Vec3,
+
+    /// Rotations around the X, Y and Z axes, in degrees. They're applied in that order.
+    pub rotation: Vec3,
+
+    pub scale: Vec3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform {
+            translation: Vec3::ZERO,
+            rotation: Vec3::ZERO,
+            scale: Vec3 {
+                x: 1.0,
+                y: 1.0,
+                z: 1.0,
+            },
+        }
+    }
+}
+
+impl Transform {
+    pub fn matrix(&self) -> Matrix4 {
+        let rotation = cgmath::Matrix4::from_angle_z(cgmath::Deg(self.rotation.z))
+            * cgmath::Matrix4::from_angle_y(cgmath::Deg(self.rotation.y))
+            * cgmath::Matrix4::from_angle_x(cgmath::Deg(self.rotation.x));
+        (cgmath::Matrix4::from_translation(self.translation.into())
+            * rotation
+            * cgmath::Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z))
+        .into()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PointLightDescription {
+    pub position: Point3,
+    pub color: Color,
+
+    /// Measured in lumens.
+    pub luminous_power: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DirectionalLightDescription {
+    /// The direction that the light travels in.
+    pub direction: Vec3,
+    pub color: Color,
+
+    /// Measured in lux.
+    pub illuminance: f32,
+}
+
+impl SceneDescription {
+    /**
+    Every object in the scene, including children, in depth-first order. Each object comes with the
+    index of its parent in the returned list.
+    */
+    pub fn objects(&self) -> Vec<(Option<usize>, &ObjectDescription)> {
+        let mut objects = Vec::new();
+        let mut stack: Vec<(Option<usize>, &ObjectDescription)> = self
+            .objects
+            .iter()
+            .rev()
+            .map(|object| (None, object))
+            .collect();
+        while let Some((parent, object)) = stack.pop() {
+            let index = objects.len();
+            objects.push((parent, object));
+            stack.extend(
+                object
+                    .children
+                    .iter()
+                    .rev()
+                    .map(|child| (Some(index), child)),
+            );
+        }
+        objects
+    }
+
+    /// The object at `index` in [`SceneDescription::objects`].
+    pub fn object_mut(&mut self, index: usize) -> Option<&mut ObjectDescription> {
+        fn find<'a>(
+            objects: &'a mut [ObjectDescription],
+            index: &mut usize,
+        ) -> Option<&'a mut ObjectDescription> {
+            for object in objects {
+                if *index == 0 {
+                    return Some(object);
+                }
+                *index -= 1;
+                if let Some(found) = find(&mut object.children, index) {
+                    return Some(found);
+                }
+            }
+            None
+        }
+
+        let mut index = index;
+        find(&mut self.objects, &mut index)
+    }
+
+    pub fn read(file_name: &str) -> Result<Self, LoadError> {
+        let contents = fs::read_to_string(file_name).map_err(|error| LoadError::Io {
+            path: PathBuf::from(file_name),
+            error,
+        })?;
+        ron::from_str(&contents).map_err(|error| LoadError::Scene {
+            path: PathBuf::from(file_name),
+            error,
+        })
+    }
+
+    pub fn write(&self, file_name: &str) -> Result<(), SaveError> {
+        let contents =
+            ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::new()).map_err(|error| {
+                SaveError::Scene {
+                    path: PathBuf::from(file_name),
+                    error,
+                }
+            })?;
+        fs::write(file_name, contents).map_err(|error| SaveError::Io {
+            path: PathBuf::from(file_name),
+            error,
+        })
+    }
+}
+
+/// A model file that's loaded by the [`AssetLoader`], and reloaded whenever the file changes.
+pub struct ModelAsset {
+    pub file_name: String,
+
+    /// The model's node in [`Scene::graph`]. The placeholder is attached to it until the model has
+    /// loaded.
+    pub node: NodeId,
+
+    pub options: LoadOptions,
+
+    /// The most recent request to load the file. Older requests' results are ignored.
+    pub request: AssetId,
+
+    /// Shown until the model has loaded for the first time.
+    pub placeholder: Option<Wireframe>,
+
+    /// Empty until the model has loaded, and for glTF files.
+    pub model: LoadedModel,
+
+    /**
+    A node under [`ModelAsset::node`] for each of the model's objects.
+
+    When a reload has fewer objects than the previous version, the leftover nodes are kept, without
+    bounds or model matrices.
+    */
+    pub object_nodes: Vec<NodeId>,
+
+    /**
+    The scene of a glTF file, once it's loaded, with its node hierarchy under [`ModelAsset::node`].
+    Its objects are in [`LoadedGltf::model`] rather than [`ModelAsset::model`].
+
+    The scene's lights aren't added, because the scene file's lights have their shadow maps set up
+    when it's loaded.
+    */
+    pub gltf: Option<LoadedGltf>,
+}
+
+impl ModelAsset {
+    /// The model's objects and what they use of the buffers, and the node of each object, whichever
+    /// kind of file it was loaded from.
+    pub fn loaded(&self) -> (&LoadedModel, &[NodeId]) {
+        match &self.gltf {
+            Some(gltf) => (&gltf.model, &gltf.object_nodes),
+            None => (&self.model, &self.object_nodes),
+        }
+    }
+
+    fn loaded_mut(&mut self) -> (&mut LoadedModel, &[NodeId]) {
+        match &mut self.gltf {
+            Some(gltf) => (&mut gltf.model, &gltf.object_nodes),
+            None => (&mut self.model, &self.object_nodes),
+        }
+    }
+}
+
+/// The GPU buffers that a scene is loaded into.
+pub struct SceneBuffers<'a> {
+    pub model_matrices: &'a mut ModelMatrices,
+    pub materials: &'a mut Materials,
+    pub vertex_buffer: &'a mut VertexBuffer,
+    pub index_buffer: &'a mut IndexBuffer,
+    pub point_lights: &'a mut GpuBuffer<PointLightGpu>,
+    pub directional_lights: &'a mut GpuBuffer<DirectionalLightGpu>,
+    pub shadow_map_lights: &'a mut GpuBuffer<shadow_maps::Light>,
+    pub shadow_map_atlas: &'a mut ShadowMapAtlas,
+
+    /// Directional lights' shadow projections are drawn here when debug wireframes are enabled.
+    pub wireframe_vertex_buffer: &'a mut GpuBuffer<render_wireframe::VertexInput>,
+}
+
+/// Where a light is on the GPU, so that changes to its description can be uploaded.
+struct UploadedLight<T> {
+    id: u32,
+    gpu: T,
+}
+
+/// A scene that's been loaded onto the GPU.
+pub struct Scene {
+    /**
+    The scene as it was described in its file, updated with any changes that have been made since
+    it was loaded. The animation's values aren't written to it (see [`Scene::animate`]).
+
+    Changes to materials and lights are uploaded with [`Scene::upload_materials`] and
+    [`Scene::upload_lights`]. The camera isn't kept up to date here, because it changes every
+    frame. It's passed to [`Scene::save`] instead.
+    */
+    pub description: SceneDescription,
+
+    /// The request to load the sky's HDRI.
+    pub hdri_request: Option<AssetId>,
+
+    /// Positions the scene's objects. Call [`Scene::update`] after changing it.
+    pub graph: SceneGraph,
+
+    /// The node of each of [`SceneDescription::objects`], in the same order.
+    pub object_nodes: Vec<NodeId>,
+
+    /// Shapes that only have a single level of detail.
+    pub static_objects: Vec<LoadedObject>,
+
+    /// Objects whose level of detail is chosen each frame.
+    pub lod_objects: Vec<LoadedObject>,
+
+    pub model_assets: Vec<ModelAsset>,
+    pub point_lights: Vec<PointLight>,
+    pub directional_lights: Vec<DirectionalLight>,
+
+    /// The node of each of [`Scene::lod_objects`], in the same order.
+    lod_object_nodes: Vec<NodeId>,
+
+    /**
+    The node of each of [`Scene::static_objects`], in the same order, with the object's bounds in
+    the node's coordinates.
+
+    The bounds aren't set on the nodes, because the flat shapes aren't shadow casters (see
+    [`shadow_caster_bounds`]).
+    */
+    static_object_nodes: Vec<(NodeId, Aabb)>,
+
+    /// [`SceneDescription::animation`], which [`Scene::animate`] applies.
+    animation: Animation,
+
+    /// The animation's values as of the last [`Scene::animate`], which override the description's.
+    animated: Animated,
+
+    /// The ids of [`SceneDescription::materials`], in the same order.
+    material_ids: Vec<MaterialId>,
+
+    point_lights_gpu: Vec<UploadedLight<PointLightGpu>>,
+    directional_lights_gpu: Vec<UploadedLight<DirectionalLightGpu>>,
+}
+
+impl Scene {
+    /**
+    Read a scene file and upload it.
+
+    The scene's models and HDRI are requested from `asset_loader`, and are uploaded by the caller
+    once they've loaded.
+    */
+    pub fn load(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        file_name: &str,
+        aspect: f32,
+        buffers: SceneBuffers,
+        asset_loader: &mut AssetLoader,
+    ) -> Result<Self, LoadError> {
+        let description = SceneDescription::read(file_name)?;
+
+        let invalid = |reason: String| LoadError::Invalid {
+            path: PathBuf::from(file_name),
+            reason,
+        };
+        let mut material_ids_by_name: HashMap<&str, MaterialId> = HashMap::new();
+        let mut material_ids = Vec::with_capacity(description.materials.len());
+        for material in description.materials.iter() {
+            if material_ids_by_name.contains_key(material.name.as_str()) {
+                return Err(invalid(format!(
+                    "material {:?} is defined more than once",
+                    material.name
+                )));
+            }
+            let material_id = buffers
+                .materials
+                .insert(
+                    device,
+                    queue,
+                    Material {
+                        color: material.color,
+                        roughness: material.roughness,
+                        metallic: material.metallic,
+                        _padding: [0, 0],
+                    },
+                )
+                .map_err(|error| LoadError::Capacity {
+                    path: PathBuf::from(file_name),
+                    error,
+                })?;
+            material_ids_by_name.insert(&material.name, material_id);
+            material_ids.push(material_id);
+        }
+
+        let objects = description.objects();
+        let mut object_material_ids = Vec::with_capacity(objects.len());
+        for (_, object) in objects {
+            let material_id = match &object.material {
+                Some(material) => match material_ids_by_name.get(material.as_str()) {
+                    Some(material_id) => Some(*material_id),
+                    None => {
+                        return Err(invalid(format!(
+                            "object {:?} uses undefined material {:?}",
+                            object.name, material
+                        )))
+                    }
+                },
+                None => None,
+            };
+            if material_id.is_none() && !matches!(object.mesh, MeshDescription::Model { .. }) {
+                return Err(invalid(format!(
+                    "object {:?} doesn't have a material",
+                    object.name
+                )));
+            }
+            object_material_ids.push(material_id);
+        }
+
+        description
+            .animation
+            .validate(&description)
+            .map_err(invalid)?;
+
+        Self::upload(
+            device,
+            queue,
+            description,
+            material_ids,
+            &object_material_ids,
+            aspect,
+            buffers,
+            asset_loader,
+        )
+        .map_err(|error| LoadError::Capacity {
+            path: PathBuf::from(file_name),
+            error,
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn upload(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        description: SceneDescription,
+        material_ids: Vec<MaterialId>,
+        object_material_ids: &[Option<MaterialId>],
+        aspect: f32,
+        buffers: SceneBuffers,
+        asset_loader: &mut AssetLoader,
+    ) -> Result<Self, CapacityError> {
+        let SceneBuffers {
+            model_matrices,
+            materials: _,
+            vertex_buffer,
+            index_buffer,
+            point_lights: point_lights_buffer,
+            directional_lights: directional_lights_buffer,
+            shadow_map_lights: shadow_map_lights_buffer,
+            shadow_map_atlas,
+            wireframe_vertex_buffer,
+        } = buffers;
+
+        let camera = description.camera.camera(aspect);
+
+        let mut graph = SceneGraph::new();
+        let mut object_nodes: Vec<NodeId> = Vec::new();
+        let mut static_objects = Vec::new();
+        let mut static_object_nodes = Vec::new();
+        let mut lod_objects = Vec::new();
+        let mut lod_object_nodes = Vec::new();
+        let mut model_assets = Vec::new();
+        // The levels of detail and bounds of each shape that's been uploaded.
+        let mut shape_meshes: Vec<(MeshDescription, Vec<IndexRange>, Aabb)> = Vec::new();
+        for ((parent, object), material_id) in
+            description.objects().into_iter().zip(object_material_ids)
+        {
+            let node = graph.add(
+                parent.map(|parent| object_nodes[parent]),
+                object.transform.matrix(),
+            );
+            object_nodes.push(node);
+
+            if let MeshDescription::Model { path, merge } = &object.mesh {
+                let options = LoadOptions {
+                    merge: *merge,
+                    material: *material_id,
+                    ..Default::default()
+                };
+                model_assets.push(ModelAsset {
+                    file_name: path.clone(),
+                    node,
+                    request: asset_loader.load_model(path, &options),
+                    options,
+                    placeholder: None,
+                    model: LoadedModel::default(),
+                    object_nodes: Vec::new(),
+                    gltf: None,
+                });
+                continue;
+            }
+
+            // Shapes always have a material (see `Scene::load`).
+            let material_id = material_id.unwrap();
+
+            // The matrix is filled in when the graph is updated.
+            let model_matrix_id = model_matrices.insert(device, queue, Matrix4::IDENTITY)?;
+            graph.set_model_matrix(node, Some(model_matrix_id));
+
+            // Objects with the same shape share its mesh, and are drawn as instances of it.
+            let (lods, aabb) = match shape_meshes.iter().find(|(mesh, ..)| *mesh == object.mesh) {
+                Some((_, lods, aabb)) => (lods.clone(), *aabb),
+                None => {
+                    let (lods, aabb) =
+                        upload_shape(device, queue, &object.mesh, vertex_buffer, index_buffer)?;
+                    shape_meshes.push((object.mesh.clone(), lods.clone(), aabb));
+                    (lods, aabb)
+                }
+            };
+            let loaded_object = LoadedObject {
+                name: object.name.clone(),
+                model_matrix_id,
+                material_id,
+                material_count: 1,
+                // Replaced by the world bounds once the graph is updated.
+                aabb,
+                lods,
+            };
+
+            if let MeshDescription::Sphere { .. } = object.mesh {
+                graph.set_bounds(node, Some(aabb));
+                lod_objects.push(loaded_object);
+                lod_object_nodes.push(node);
+            } else {
+                static_objects.push(loaded_object);
+                static_object_nodes.push((node, aabb));
+            }
+        }
+
+        graph.update(queue, model_matrices);
+        for (object, node) in lod_objects.iter_mut().zip(&lod_object_nodes) {
+            object.aabb = graph.world_bounds(*node).unwrap();
+        }
+        for (object, (node, aabb)) in static_objects.iter_mut().zip(&static_object_nodes) {
+            object.aabb = aabb.transform(graph.world_transform(*node));
+        }
+        let shadow_caster_bounds = shadow_caster_bounds(&graph);
+
+        let mut point_lights = Vec::with_capacity(description.point_lights.len());
+        let mut point_lights_gpu = Vec::with_capacity(description.point_lights.len());
+        for point_light in description.point_lights.iter() {
+            let position = point_light.position;
+            let model_matrix_id = model_matrices.insert(
+                device,
+                queue,
+                cgmath::Matrix4::from_translation(Vec3::from(position).into()).into(),
+            )?;
+
+            let shadow_projection = Matrix4::perspective(90.0, 1.0, 0.5, 15.0);
+
+            let mut create_shadow_map_face =
+                |up, face_direction| -> Result<PointLightShadowMapFace, CapacityError> {
+                    let shadow_map_atlas_entry = shadow_map_atlas.allocate();
+                    let shadow_view = Matrix4::look_to(position, face_direction, up);
+                    let shadow_map_light_gpu_id = shadow_map_lights_buffer.insert(
+                        device,
+                        queue,
+                        shadow_maps::Light {
+                            shadow_view,
+                            shadow_projection,
+                            shadow_map_atlas_position: shadow_map_atlas_entry.position().into(),
+                            shadow_map_atlas_size: [
+                                shadow_map_atlas_entry.size(),
+                                shadow_map_atlas_entry.size(),
+                            ],
+                            _padding: [0, 0, 0, 0, 0, 0, 0],
+                        },
+                    )?;
+                    Ok(PointLightShadowMapFace {
+                        shadow_map_light_gpu_id,
+                        shadow_map_atlas_entry,
+                        frustum: Frustum::new(shadow_projection * shadow_view),
+                    })
+                };
+
+            let x = create_shadow_map_face(Vec3::Y, Vec3::X)?;
+            let neg_x = create_shadow_map_face(Vec3::Y, -Vec3::X)?;
+            let y = create_shadow_map_face(Vec3::Z, Vec3::Y)?;
+            let neg_y = create_shadow_map_face(Vec3::Z, -Vec3::Y)?;
+            let z = create_shadow_map_face(Vec3::Y, Vec3::Z)?;
+            let neg_z = create_shadow_map_face(Vec3::Y, -Vec3::Z)?;
+            point_lights.push(PointLight {
+                shadow_map_faces: PointLightShadowMapFaces {
+                    x,
+                    neg_x,
+                    y,
+                    neg_y,
+                    z,
+                    neg_z,
+                },
+            });
+
+            let point_light_gpu = PointLightGpu {
+                model_matrix_id,
+                _padding0: [0, 0, 0],
+                color: point_light.color,
+                luminous_power: point_light.luminous_power,
+                shadow_map_light_ids: ShadowMapLightIds {
+                    x: x.shadow_map_light_gpu_id,
+                    neg_x: neg_x.shadow_map_light_gpu_id,
+                    y: y.shadow_map_light_gpu_id,
+                    neg_y: neg_y.shadow_map_light_gpu_id,
+                    z: z.shadow_map_light_gpu_id,
+                    neg_z: neg_z.shadow_map_light_gpu_id,
+                },
+            };
+            point_lights_gpu.push(UploadedLight {
+                id: point_lights_buffer.insert(device, queue, point_light_gpu)?,
+                gpu: point_light_gpu,
+            });
+        }
+
+        let mut directional_lights = Vec::with_capacity(description.directional_lights.len());
+        let mut directional_lights_gpu = Vec::with_capacity(description.directional_lights.len());
+        for directional_light in description.directional_lights.iter() {
+            let direction = directional_light.direction.normalize();
+            let shadow_map_atlas_entry = shadow_map_atlas.allocate();
+            let position = shadow_map_atlas_entry.position();
+            let size = shadow_map_atlas_entry.size();
+
+            let shadow_view = Matrix4::look_to(Point3::ZERO, direction, Vec3::Y);
+
+            let aabb = fit_orthographic_projection_to_camera(
+                [size, size],
+                &shadow_caster_bounds,
+                &camera,
+                shadow_view,
+            );
+            debug_assert!(aabb.valid(), "invalid aabb: {:?}", aabb);
+
+            // `ortho` takes positive near/far arguments but still assumes that far is towards -Z.
+            let shadow_projection = Matrix4::ortho(
+                aabb.min.x,
+                aabb.max.x,
+                aabb.min.y,
+                aabb.max.y,
+                -aabb.max.z,
+                -aabb.min.z,
+            );
+            let id = shadow_map_lights_buffer.insert(
+                device,
+                queue,
+                shadow_maps::Light {
+                    shadow_view,
+                    shadow_projection,
+                    shadow_map_atlas_position: position.into(),
+                    shadow_map_atlas_size: [size, size],
+                    _padding: [0, 0, 0, 0, 0, 0, 0],
+                },
+            )?;
+            let directional_light_gpu = DirectionalLightGpu {
+                color: directional_light.color,
+                direction,
+                illuminance: directional_light.illuminance,
+                shadow_map_light_id: id,
+            };
+            directional_lights_gpu.push(UploadedLight {
+                id: directional_lights_buffer.insert(device, queue, directional_light_gpu)?,
+                gpu: directional_light_gpu,
+            });
+
+            let shadow_view_inverse = shadow_view.inverse();
+
+            let wireframe = wireframe::add(
+                device,
+                queue,
+                model_matrices,
+                wireframe_vertex_buffer,
+                shadow_view_inverse,
+                aabb.as_cuboid().wireframe_mesh(),
+            )?;
+
+            directional_lights.push(DirectionalLight {
+                shadow_map_light_gpu_id: id,
+                shadow_map_atlas_entry,
+                shadow_view,
+                shadow_view_inverse,
+                frustum: Frustum::new(shadow_projection * shadow_view),
+                wireframe: Some(wireframe),
+            });
+        }
+
+        Ok(Scene {
+            hdri_request: description
+                .sky
+                .hdri
+                .as_ref()
+                .map(|hdri| asset_loader.load_hdri(hdri)),
+            animation: description.animation.clone(),
+            animated: Animated::default(),
+            description,
+            graph,
+            object_nodes,
+            static_objects,
+            lod_objects,
+            model_assets,
+            point_lights,
+            directional_lights,
+            lod_object_nodes,
+            static_object_nodes,
+            material_ids,
+            point_lights_gpu,
+            directional_lights_gpu,
+        })
+    }
+
+    /**
+    Upload a model that's been loaded for one of [`Scene::model_assets`], replacing the previous
+    version if there is one. See [`load::replace_model`].
+
+    When it fails, the previous version is removed, and the model isn't drawn until a later version
+    loads.
+    */
+    #[allow(clippy::too_many_arguments)]
+    pub fn replace_model(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        model_matrices: &mut ModelMatrices,
+        materials: &mut Materials,
+        vertex_buffer: &mut VertexBuffer,
+        index_buffer: &mut IndexBuffer,
+        model_asset_index: usize,
+        model: &Model,
+    ) -> Result<(), CapacityError> {
+        let model_asset = &mut self.model_assets[model_asset_index];
+
+        let previous = std::mem::take(&mut model_asset.model);
+        let loaded_model = match load::replace_model(
+            device,
+            queue,
+            model_matrices,
+            materials,
+            vertex_buffer,
+            index_buffer,
+            model,
+            &previous,
+            self.graph.world_transform(model_asset.node),
+            &model_asset.options,
+        ) {
+            Ok(loaded_model) => loaded_model,
+            Err(error) => {
+                // The previous version's data was removed, so its objects can't be drawn anymore.
+                for object in &previous.objects {
+                    if model_matrices.contains(object.model_matrix_id) {
+                        model_matrices.remove(queue, object.model_matrix_id);
+                    }
+                }
+                for node in &model_asset.object_nodes {
+                    self.graph.set_model_matrix(*node, None);
+                    self.graph.set_bounds(*node, None);
+                }
+                return Err(error);
+            }
+        };
+
+        let objects = &loaded_model.objects;
+        for (index, (object, model_object)) in objects.iter().zip(&model.objects).enumerate() {
+            let node = match model_asset.object_nodes.get(index) {
+                Some(node) => *node,
+                None => {
+                    let node = self.graph.add(Some(model_asset.node), Matrix4::IDENTITY);
+                    model_asset.object_nodes.push(node);
+                    node
+                }
+            };
+            self.graph
+                .set_model_matrix(node, Some(object.model_matrix_id));
+            self.graph.set_bounds(node, Some(model_object.aabb));
+        }
+        for node in model_asset.object_nodes.iter().skip(objects.len()) {
+            self.graph.set_model_matrix(*node, None);
+            self.graph.set_bounds(*node, None);
+        }
+        model_asset.model = loaded_model;
+        Ok(())
+    }
+
+    /**
+    Upload a glTF scene that's been read for one of [`Scene::model_assets`], replacing the previous
+    version if there is one. See [`load_gltf::insert_gltf`].
+
+    The previous version is removed first (see [`LoadedGltf::remove`]), so when this fails, the
+    model isn't drawn until a later version loads.
+    */
+    #[allow(clippy::too_many_arguments)]
+    pub fn replace_gltf(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        model_matrices: &mut ModelMatrices,
+        joint_matrices: &mut JointMatrices,
+        materials: &mut Materials,
+        vertex_buffer: &mut VertexBuffer,
+        index_buffer: &mut IndexBuffer,
+        model_asset_index: usize,
+        gltf_scene: &GltfScene,
+    ) -> Result<(), CapacityError> {
+        let model_asset = &mut self.model_assets[model_asset_index];
+        if let Some(previous) = model_asset.gltf.take() {
+            previous.remove(
+                queue,
+                model_matrices,
+                joint_matrices,
+                materials,
+                vertex_buffer,
+                index_buffer,
+                &mut self.graph,
+            );
+        }
+        model_asset.gltf = Some(load_gltf::insert_gltf(
+            device,
+            queue,
+            model_matrices,
+            joint_matrices,
+            materials,
+            vertex_buffer,
+            index_buffer,
+            &mut self.graph,
+            Some(model_asset.node),
+            gltf_scene,
+            &model_asset.options,
+        )?);
+        Ok(())
+    }
+
+    /**
+    Compact `vertex_buffer` and `index_buffer`, so that the space that [`Scene::replace_model`] and
+    [`Scene::replace_gltf`] freed can be used by larger models, and move the objects' index ranges along with their
+    indices.
+    */
+    pub fn compact(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        vertex_buffer: &mut VertexBuffer,
+        index_buffer: &mut IndexBuffer,
+    ) {
+        let vertex_relocations = vertex_buffer.compact(device, encoder);
+        let index_relocations = index_buffer.compact(device, encoder, &vertex_relocations);
+
+        let relocate = |relocations: &[Relocation], range: &mut Range<u64>| {
+            let start = Relocation::apply(relocations, range.start);
+            *range = start..start + (range.end - range.start);
+        };
+        for model_asset in &mut self.model_assets {
+            let (model, _) = model_asset.loaded_mut();
+            relocate(&vertex_relocations, &mut model.vertices);
+            relocate(&index_relocations, &mut model.indices);
+        }
+        if index_relocations.is_empty() {
+            return;
+        }
+        let objects = self
+            .static_objects
+            .iter_mut()
+            .chain(&mut self.lod_objects)
+            .chain(
+                self.model_assets
+                    .iter_mut()
+                    .flat_map(|model_asset| &mut model_asset.loaded_mut().0.objects),
+            );
+        for object in objects {
+            for lod in &mut object.lods {
+                lod.first_index =
+                    Relocation::apply(&index_relocations, lod.first_index as u64) as u32;
+            }
+        }
+    }
+
+    pub fn animation(&self) -> &Animation {
+        &self.animation
+    }
+
+    fn skins(&self) -> impl Iterator<Item = &LoadedSkin> {
+        self.model_assets
+            .iter()
+            .filter_map(|model_asset| model_asset.gltf.as_ref())
+            .flat_map(|gltf| &gltf.skins)
+    }
+
+    /// Whether anything in the scene is animated: the scene's own animation, or the skins of its
+    /// glTF models (see [`Scene::animate_skins`]).
+    pub fn is_animated(&self) -> bool {
+        !self.animation.is_empty() || self.skins().any(|skin| !skin.animations.is_empty())
+    }
+
+    /// The time of the last keyframe of the scene's animation, or of its skins' animations.
+    pub fn animation_duration(&self) -> f32 {
+        self.skins()
+            .filter_map(|skin| skin.animations.first())
+            .map(SkeletonAnimation::duration)
+            .fold(self.animation.duration(), f32::max)
+    }
+
+    /**
+    Pose the skins of the scene's glTF models at `time` (in seconds), and write their joint
+    matrices.
+
+    Each skin plays its first animation on the scene's clock. Skins without animations stay in
+    their rest pose. The bounds of the skinned meshes change on the next [`Scene::update`].
+    */
+    pub fn animate_skins(
+        &mut self,
+        queue: &wgpu::Queue,
+        joint_matrices: &mut JointMatrices,
+        time: f32,
+    ) {
+        let skins = self
+            .model_assets
+            .iter()
+            .filter_map(|model_asset| model_asset.gltf.as_ref())
+            .flat_map(|gltf| &gltf.skins);
+        for skin in skins {
+            if !skin.animations.is_empty() {
+                skin.update(queue, &mut self.graph, joint_matrices, Some(0), time);
+            }
+        }
+    }
+
+    /**
+    Set the scene's animated properties to their values at `time` (in seconds).
+
+    [`Scene::description`] isn't changed, so it can still be saved. Animated objects move on the
+    next [`Scene::update`]. Animated lights need to be uploaded with [`Scene::upload_lights`].
+    */
+    pub fn animate(&mut self, time: f32, camera: &mut Camera) -> &Animated {
+        self.animated = self.animation.apply(time, &self.description, camera);
+        let objects = self.description.objects();
+        for index in self.animated.objects() {
+            self.graph.set_local_transform(
+                self.object_nodes[index],
+                self.animated
+                    .transform(index, objects[index].1.transform)
+                    .matrix(),
+            );
+        }
+        &self.animated
+    }
+
+    /**
+    Update [`Scene::graph`], and the bounds of the objects that it positions.
+
+    Returns whether anything moved or changed size.
+    */
+    pub fn update(&mut self, queue: &wgpu::Queue, model_matrices: &mut ModelMatrices) -> bool {
+        if self.graph.update(queue, model_matrices).is_empty() {
+            return false;
+        }
+
+        let objects = self
+            .lod_objects
+            .iter_mut()
+            .zip(&self.lod_object_nodes)
+            .chain(self.model_assets.iter_mut().flat_map(|model_asset| {
+                let (model, object_nodes) = model_asset.loaded_mut();
+                model.objects.iter_mut().zip(object_nodes)
+            }));
+        for (object, node) in objects {
+            if let Some(bounds) = self.graph.world_bounds(*node) {
+                object.aabb = bounds;
+            }
+        }
+        for (object, (node, aabb)) in self
+            .static_objects
+            .iter_mut()
+            .zip(&self.static_object_nodes)
+        {
+            object.aabb = aabb.transform(self.graph.world_transform(*node));
+        }
+        true
+    }
+
+    /**
+    The index (in [`SceneDescription::objects`]) of the object that was picked.
+
+    When the pick is part of a model, the object is the one that loads the model.
+    */
+    pub fn picked_object(&self, pick: &Pick) -> Option<usize> {
+        let mut node = self.graph.node_with_model_matrix(pick.model_matrix_id);
+        while let Some(id) = node {
+            if let Some(index) = self.object_nodes.iter().position(|node| *node == id) {
+                return Some(index);
+            }
+            node = self.graph.parent(id);
+        }
+        None
+    }
+
+    /// The transform of one of [`SceneDescription::objects`], as it's currently animated.
+    pub fn object_transform(&self, index: usize) -> Transform {
+        let (_, object) = self.description.objects()[index];
+        self.animated.transform(index, object.transform)
+    }
+
+    /**
+    Move one of [`SceneDescription::objects`] relative to its parent.
+
+    The object's node is updated too, so its model matrices and the shadow casters' bounds change
+    on the next [`Scene::update`]. The description keeps its values of the object's animated
+    properties.
+    */
+    pub fn set_object_transform(&mut self, index: usize, transform: Transform) {
+        self.graph
+            .set_local_transform(self.object_nodes[index], transform.matrix());
+        let object = self
+            .description
+            .object_mut(index)
+            .expect("object index out of range");
+        object.transform = self.animated.unanimated(index, transform, object.transform);
+    }
+
+    /// Every object that's drawn: the shapes, and the objects of the models that have loaded.
+    pub fn loaded_objects(&self) -> impl Iterator<Item = &LoadedObject> {
+        self.static_objects.iter().chain(&self.lod_objects).chain(
+            self.model_assets
+                .iter()
+                .flat_map(|model_asset| model_asset.loaded().0.objects.iter()),
+        )
+    }
+
+    /// The object that uses the model matrix.
+    pub fn loaded_object(&self, model_matrix_id: ModelMatrixId) -> Option<&LoadedObject> {
+        self.loaded_objects()
+            .find(|object| object.model_matrix_id == model_matrix_id)
+    }
+
+    /// The index (in [`SceneDescription::materials`]) of the material. Materials that come from
+    /// model files aren't in the description.
+    pub fn material_index(&self, material_id: MaterialId) -> Option<usize> {
+        self.material_ids.iter().position(|id| *id == material_id)
+    }
+
+    /// The bounds that the directional lights' shadow maps are fitted to, as of the last update.
+    pub fn shadow_caster_bounds(&self) -> Aabb {
+        shadow_caster_bounds(&self.graph)
+    }
+
+    /// Upload [`SceneDescription::materials`] after they've been changed.
+    pub fn upload_materials(&self, queue: &wgpu::Queue, materials: &mut Materials) {
+        for (material, material_id) in self.description.materials.iter().zip(&self.material_ids) {
+            materials.update(
+                queue,
+                *material_id,
+                Material {
+                    color: material.color,
+                    roughness: material.roughness,
+                    metallic: material.metallic,
+                    _padding: [0, 0],
+                },
+            );
+        }
+    }
+
+    /**
+    Upload the colors and intensities of [`SceneDescription::point_lights`] and
+    [`SceneDescription::directional_lights`] after they've been changed, with the animated
+    intensities in place of the description's.
+
+    Lights can't be moved or pointed in a different direction after they're loaded, because their
+    shadow maps would need to be set up again.
+    */
+    pub fn upload_lights(
+        &self,
+        queue: &wgpu::Queue,
+        point_lights: &mut GpuBuffer<PointLightGpu>,
+        directional_lights: &mut GpuBuffer<DirectionalLightGpu>,
+    ) {
+        let animated = |values: &[(usize, f32)], index: usize, value: f32| {
+            values
+                .iter()
+                .rev()
+                .find(|(light, _)| *light == index)
+                .map_or(value, |(_, value)| *value)
+        };
+        for (index, (point_light, uploaded)) in self
+            .description
+            .point_lights
+            .iter()
+            .zip(&self.point_lights_gpu)
+            .enumerate()
+        {
+            point_lights.update(
+                queue,
+                uploaded.id,
+                PointLightGpu {
+                    color: point_light.color,
+                    luminous_power: animated(
+                        &self.animated.luminous_powers,
+                        index,
+                        point_light.luminous_power,
+                    ),
+                    ..uploaded.gpu
+                },
+            );
+        }
+        for (index, (directional_light, uploaded)) in self
+            .description
+            .directional_lights
+            .iter()
+            .zip(&self.directional_lights_gpu)
+            .enumerate()
+        {
+            directional_lights.update(
+                queue,
+                uploaded.id,
+                DirectionalLightGpu {
+                    color: directional_light.color,
+                    illuminance: animated(
+                        &self.animated.illuminances,
+                        index,
+                        directional_light.illuminance,
+                    ),
+                    ..uploaded.gpu
+                },
+            );
+        }
+    }
+
+    /// Write the scene to a scene file, with `camera` as the scene's camera.
+    pub fn save(&self, file_name: &str, camera: &Camera) -> Result<(), SaveError> {
+        SceneDescription {
+            camera: CameraDescription::from(camera),
+            ..self.description.clone()
+        }
+        .write(file_name)
+    }
+}
+
+/**
+Upload a shape's mesh, and return its levels of detail and its bounds.
+
+The mesh's vertices have the ids of a [`Model`]'s single object and material, because the shape's
+instances supply their model matrices and materials.
+*/
+fn upload_shape(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    mesh_description: &MeshDescription,
+    vertex_buffer: &mut VertexBuffer,
+    index_buffer: &mut IndexBuffer,
+) -> Result<(Vec<IndexRange>, Aabb), CapacityError> {
+    let (model_matrix_id, material_id) = (ModelMatrixId(0), MaterialId(0));
+    let mut mesh: Mesh = match *mesh_description {
+        MeshDescription::Triangle => shape::triangle(model_matrix_id, material_id),
+        MeshDescription::Square { side } => shape::square(model_matrix_id, material_id, side),
+        MeshDescription::Floor { side } => shape::floor(model_matrix_id, material_id, side),
+        MeshDescription::Sphere { radius } => shape::sphere(model_matrix_id, material_id, radius),
+        MeshDescription::Model { .. } => unreachable!("models aren't shapes"),
+    };
+
+    let mut lods = vec![IndexRange {
+        first_index: 0,
+        index_count: mesh.indices.len() as u32,
+    }];
+    // Only spheres are detailed enough to need simpler versions.
+    if let MeshDescription::Sphere { .. } = mesh_description {
+        lod::add_lods(
+            &mesh.vertices,
+            &mut mesh.indices,
+            &mut lods,
+            lod::DEFAULT_LOD_LEVELS,
+        );
+    }
+
+    let index_range = mesh.insert(device, queue, vertex_buffer, index_buffer)?;
+    let lods = lods
+        .iter()
+        .map(|lod| IndexRange {
+            first_index: index_range.first_index + lod.first_index,
+            index_count: lod.index_count,
+        })
+        .collect();
+    Ok((lods, mesh.aabb()))
+}
+
+/**
+The bounds of the scene's shadow casters.
+
+Only spheres and models have bounds. The flat shapes (including the floor) mostly receive shadows, and
+a large floor would make the directional lights' shadow maps cover a lot of empty space. The bounds
+always contain the origin.
+*/
+fn shadow_caster_bounds(graph: &SceneGraph) -> Aabb {
+    graph
+        .bounds()
+        .map_or(Aabb::EMPTY, |bounds| Aabb::EMPTY.union(bounds))
+}
+
+#[test]
+fn test_read_scene_description() {
+    let description = SceneDescription::read("scenes/default.ron").unwrap();
+    assert_eq!(description.objects.len(), 15);
+    for object in description.objects.iter() {
+        if let Some(material) = &object.material {
+            assert!(
+                description
+                    .materials
+                    .iter()
+                    .any(|description| &description.name == material),
+                "{:?}",
+                object
+            );
+        }
+    }
+
+    // Omitted fields get their defaults.
+    let object: ObjectDescription = ron::from_str(
+        r#"(name: "triangle", mesh: Triangle, material: Some("blue"), transform: (translation: (x: 1.0, y: 0.0, z: 0.0)))"#,
+    )
+    .unwrap();
+    assert_eq!(
+        object.transform,
+        Transform {
+            translation: Vec3::X,
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn test_transform_matrix() {
+    let transform = Transform {
+        translation: Vec3 {
+            x: 1.0,
+            y: 2.0,
+            z: 3.0,
+        },
+        rotation: Vec3 {
+            x: 0.0,
+            y: 90.0,
+            z: 0.0,
+        },
+        scale: Vec3 {
+            x: 2.0,
+            y: 2.0,
+            z: 2.0,
+        },
+    };
+    let point = Point3::from(
+        transform.matrix()
+            * Point3 {
+                x: 1.0,
+                y: 0.0,
+                z: 0.0,
+            }
+            .with_w(1.0),
+    );
+
+    // (1, 0, 0) is scaled to (2, 0, 0), rotated to (0, 0, -2), then translated.
+    assert!((point.x - 1.0).abs() < 1e-6, "{:?}", point);
+    assert!((point.y - 2.0).abs() < 1e-6, "{:?}", point);
+    assert!((point.z - 1.0).abs() < 1e-6, "{:?}", point);
+}
+
+#[test]
+fn test_scene_description_round_trip() {
+    let mut description = SceneDescription::read("scenes/default.ron").unwrap();
+
+    // Edits like the ones made in the viewer.
+    let mut camera = description.camera.camera(16.0 / 9.0);
+    camera.eye = Point3 {
+        x: 1.5,
+        y: 0.25,
+        z: -3.0,
+    };
+    camera.far = 42.0;
+    description.camera = CameraDescription::from(&camera);
+    description.sky.intensity = 12_345.6;
+    description.materials[0].roughness = 0.123;
+    description.point_lights[0].color = Color {
+        r: 0.1,
+        g: 0.2,
+        b: 0.3,
+        a: 1.0,
+    };
+    description.directional_lights[0].illuminance = 98_765.4;
+    description.objects[0].transform.rotation.y = 33.3;
+
+    let directory = std::env::temp_dir().join(format!("scene_{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    let path = directory.join("scene.ron");
+    let file_name = path.to_str().unwrap();
+
+    description.write(file_name).unwrap();
+    let read = SceneDescription::read(file_name).unwrap();
+    assert_eq!(read, description);
+
+    // Writing what was read produces the same file.
+    let written = fs::read_to_string(file_name).unwrap();
+    read.write(file_name).unwrap();
+    assert_eq!(fs::read_to_string(file_name).unwrap(), written);
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_camera_description() {
+    let description = SceneDescription::read("scenes/default.ron").unwrap().camera;
+    let camera = description.camera(2.0);
+    assert_eq!(camera.aspect, 2.0);
+    assert_eq!(CameraDescription::from(&camera), description);
+}
+
+#[test]
+fn test_object_mut() {
+    let mut description = SceneDescription::read("scenes/default.ron").unwrap();
+    let mut child = description.objects[1].clone();
+    child.name = String::from("child");
+    child.children.push(ObjectDescription {
+        name: String::from("grandchild"),
+        ..child.clone()
+    });
+    description.objects[0].children.push(child);
+
+    let names: Vec<String> = description
+        .objects()
+        .into_iter()
+        .map(|(_, object)| object.name.clone())
+        .collect();
+    for (index, name) in names.iter().enumerate() {
+        assert_eq!(&description.object_mut(index).unwrap().name, name);
+    }
+    assert_eq!(names[1], "child");
+    assert_eq!(names[2], "grandchild");
+    assert!(description.object_mut(names.len()).is_none());
+}
@@ -0,0 +1,369 @@
+/*! A hierarchy of transforms.
+
+Each node in a [`SceneGraph`] has a transform relative to its parent (its local transform), and its
+world transform is its parent's world transform followed by its local transform. A node that has a
+model matrix gets its world transform written to [`ModelMatrices`] by [`SceneGraph::update`], which
+only writes the matrices of the nodes whose world transforms changed since the last update.
+
+A node can also have bounds, in its own coordinates. A node's world bounds contain its own bounds and
+its children's world bounds, so a root's world bounds contain everything underneath it.
+*/
+
+use crate::{
+    aabb::Aabb,
+    matrix::Matrix4,
+    model_matrices::{ModelMatrices, ModelMatrixId},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(pub u32);
+
+struct Node {
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    local_transform: Matrix4,
+
+    /// As of the last update.
+    world_transform: Matrix4,
+
+    model_matrix_id: Option<ModelMatrixId>,
+
+    /// In the node's coordinates.
+    bounds: Option<Aabb>,
+
+    /// In world coordinates, as of the last update. `None` when neither the node nor its
+    /// descendants have bounds.
+    world_bounds: Option<Aabb>,
+
+    /// The node's world transform needs to be recalculated (and written to its model matrix).
+    transform_dirty: bool,
+
+    /// The node's world bounds need to be recalculated.
+    bounds_dirty: bool,
+}
+
+/// The outcome of resolving a [`SceneGraph`]'s dirty nodes.
+struct Resolved {
+    /// The model matrices whose nodes' world transforms changed.
+    model_matrices: Vec<(ModelMatrixId, Matrix4)>,
+
+    /// The nodes whose world transforms or world bounds changed.
+    changed: Vec<NodeId>,
+}
+
+/**
+A forest of nodes.
+
+Nodes are stored in the order they're added, and a node's parent must already exist when it's added,
+so parents always come before their children. Updates rely on this to visit parents before children
+(and children before parents) without walking the tree.
+*/
+#[derive(Default)]
+pub struct SceneGraph {
+    nodes: Vec<Node>,
+}
+
+impl SceneGraph {
+    pub fn new() -> Self {
+        SceneGraph { nodes: Vec::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Add a node with no model matrix and no bounds. Its world transform is calculated in the
+    /// next update.
+    pub fn add(&mut self, parent: Option<NodeId>, local_transform: Matrix4) -> NodeId {
+        let id = NodeId(self.nodes.len() as u32);
+        if let Some(parent) = parent {
+            self.node_mut(parent).children.push(id);
+        }
+        self.nodes.push(Node {
+            parent,
+            children: Vec::new(),
+            local_transform,
+            world_transform: local_transform,
+            model_matrix_id: None,
+            bounds: None,
+            world_bounds: None,
+            transform_dirty: true,
+            bounds_dirty: true,
+        });
+        id
+    }
+
+    fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id.0 as usize]
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        &mut self.nodes[id.0 as usize]
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.node(id).parent
+    }
+
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        &self.node(id).children
+    }
+
+    /// The nodes that don't have parents.
+    pub fn roots(&self) -> impl Iterator<Item = NodeId> + '_ {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| node.parent.is_none())
+            .map(|(index, _)| NodeId(index as u32))
+    }
+
+    pub fn local_transform(&self, id: NodeId) -> Matrix4 {
+        self.node(id).local_transform
+    }
+
+    pub fn set_local_transform(&mut self, id: NodeId, local_transform: Matrix4) {
+        let node = self.node_mut(id);
+        node.local_transform = local_transform;
+        node.transform_dirty = true;
+    }
+
+    /// As of the last update.
+    pub fn world_transform(&self, id: NodeId) -> Matrix4 {
+        self.node(id).world_transform
+    }
+
+    pub fn model_matrix(&self, id: NodeId) -> Option<ModelMatrixId> {
+        self.node(id).model_matrix_id
+    }
+
+    /// The first node that has the model matrix.
+    pub fn node_with_model_matrix(&self, model_matrix_id: ModelMatrixId) -> Option<NodeId> {
+        self.nodes
+            .iter()
+            .position(|node| node.model_matrix_id == Some(model_matrix_id))
+            .map(|index| NodeId(index as u32))
+    }
+
+    /// The node's world transform is written to the model matrix in the next update.
+    pub fn set_model_matrix(&mut self, id: NodeId, model_matrix_id: Option<ModelMatrixId>) {
+        let node = self.node_mut(id);
+        node.model_matrix_id = model_matrix_id;
+        node.transform_dirty = true;
+    }
+
+    /// Set the node's own bounds, in its own coordinates.
+    pub fn set_bounds(&mut self, id: NodeId, bounds: Option<Aabb>) {
+        let node = self.node_mut(id);
+        node.bounds = bounds;
+        node.bounds_dirty = true;
+    }
+
+    /// The bounds of the node and its descendants, in world coordinates, as of the last update.
+    pub fn world_bounds(&self, id: NodeId) -> Option<Aabb> {
+        self.node(id).world_bounds
+    }
+
+    /// The bounds of every node, in world coordinates, as of the last update.
+    pub fn bounds(&self) -> Option<Aabb> {
+        self.roots()
+            .filter_map(|root| self.world_bounds(root))
+            .reduce(Aabb::union)
+    }
+
+    /**
+    Recalculate the world transforms and world bounds that are out of date, and write the changed
+    world transforms to their model matrices.
+
+    Returns the nodes whose world transforms or world bounds changed.
+    */
+    pub fn update(
+        &mut self,
+        queue: &wgpu::Queue,
+        model_matrices: &mut ModelMatrices,
+    ) -> Vec<NodeId> {
+        let resolved = self.resolve();
+        for (model_matrix_id, world_transform) in resolved.model_matrices {
+            model_matrices.update(queue, model_matrix_id, world_transform);
+        }
+        resolved.changed
+    }
+
+    fn resolve(&mut self) -> Resolved {
+        let mut model_matrices = Vec::new();
+
+        // Parents before children.
+        let mut transform_changed = vec![false; self.nodes.len()];
+        for index in 0..self.nodes.len() {
+            let parent = self.nodes[index].parent;
+            let parent_changed = parent.is_some_and(|parent| transform_changed[parent.0 as usize]);
+            if !self.nodes[index].transform_dirty && !parent_changed {
+                continue;
+            }
+
+            let parent_world_transform = parent.map(|parent| self.world_transform(parent));
+            let node = &mut self.nodes[index];
+            node.world_transform = match parent_world_transform {
+                Some(parent_world_transform) => parent_world_transform * node.local_transform,
+                None => node.local_transform,
+            };
+            node.transform_dirty = false;
+            transform_changed[index] = true;
+
+            if let Some(model_matrix_id) = node.model_matrix_id {
+                model_matrices.push((model_matrix_id, node.world_transform));
+            }
+        }
+
+        /* Children before parents. A node's entry starts out as whether any of its children's world
+        bounds changed, and ends up as whether its own world bounds changed.
+        */
+        let mut bounds_changed = vec![false; self.nodes.len()];
+        for index in (0..self.nodes.len()).rev() {
+            if !transform_changed[index]
+                && !self.nodes[index].bounds_dirty
+                && !bounds_changed[index]
+            {
+                continue;
+            }
+
+            let node = &self.nodes[index];
+            let world_bounds = node
+                .bounds
+                .map(|bounds| bounds.transform(node.world_transform))
+                .into_iter()
+                .chain(
+                    node.children
+                        .iter()
+                        .filter_map(|child| self.world_bounds(*child)),
+                )
+                .reduce(Aabb::union);
+
+            let node = &mut self.nodes[index];
+            node.bounds_dirty = false;
+            bounds_changed[index] = world_bounds != node.world_bounds;
+            node.world_bounds = world_bounds;
+            if let (true, Some(parent)) = (bounds_changed[index], node.parent) {
+                bounds_changed[parent.0 as usize] = true;
+            }
+        }
+
+        Resolved {
+            model_matrices,
+            changed: (0..self.nodes.len())
+                .filter(|index| transform_changed[*index] || bounds_changed[*index])
+                .map(|index| NodeId(index as u32))
+                .collect(),
+        }
+    }
+}
+
+#[test]
+fn test_scene_graph_world_transforms() {
+    use crate::point::Point3;
+
+    let translation = |x: f32, y: f32, z: f32| -> Matrix4 {
+        cgmath::Matrix4::from_translation(cgmath::Vector3 { x, y, z }).into()
+    };
+
+    let origin = |graph: &SceneGraph, node| {
+        let point = Point3::from(graph.world_transform(node) * Point3::ZERO.with_w(1.0));
+        (point.x, point.y, point.z)
+    };
+
+    let mut graph = SceneGraph::new();
+    let base = graph.add(None, translation(1.0, 0.0, 0.0));
+    let arm = graph.add(Some(base), translation(0.0, 2.0, 0.0));
+    let hand = graph.add(Some(arm), translation(0.0, 0.0, 3.0));
+    let other = graph.add(None, translation(0.0, 0.0, -1.0));
+    graph.set_model_matrix(arm, Some(ModelMatrixId(7)));
+    graph.set_model_matrix(hand, Some(ModelMatrixId(8)));
+
+    // Every node is resolved the first time.
+    let resolved = graph.resolve();
+    assert_eq!(resolved.changed, [base, arm, hand, other]);
+    let written: Vec<u32> = resolved
+        .model_matrices
+        .iter()
+        .map(|(model_matrix_id, _)| model_matrix_id.0)
+        .collect();
+    assert_eq!(written, [7, 8]);
+    assert_eq!(origin(&graph, hand), (1.0, 2.0, 3.0));
+    assert_eq!(graph.roots().collect::<Vec<_>>(), [base, other]);
+    assert_eq!(graph.children(base), [arm]);
+    assert_eq!(graph.parent(hand), Some(arm));
+
+    // Nothing is dirty.
+    let resolved = graph.resolve();
+    assert!(resolved.changed.is_empty());
+    assert!(resolved.model_matrices.is_empty());
+
+    // Moving a node moves its descendants, and only their matrices are written.
+    graph.set_local_transform(arm, translation(0.0, 5.0, 0.0));
+    let resolved = graph.resolve();
+    assert_eq!(resolved.changed, [arm, hand]);
+    assert_eq!(resolved.model_matrices.len(), 2);
+    assert_eq!(origin(&graph, hand), (1.0, 5.0, 3.0));
+    assert_eq!(origin(&graph, base), (1.0, 0.0, 0.0));
+}
+
+#[test]
+fn test_scene_graph_bounds() {
+    use crate::point::Point3;
+
+    let translation = |x: f32, y: f32, z: f32| -> Matrix4 {
+        cgmath::Matrix4::from_translation(cgmath::Vector3 { x, y, z }).into()
+    };
+
+    let unit_box = Aabb {
+        min: Point3 {
+            x: -1.0,
+            y: -1.0,
+            z: -1.0,
+        },
+        max: Point3 {
+            x: 1.0,
+            y: 1.0,
+            z: 1.0,
+        },
+    };
+
+    let mut graph = SceneGraph::new();
+    let parent = graph.add(None, translation(10.0, 0.0, 0.0));
+    let child = graph.add(Some(parent), translation(0.0, 10.0, 0.0));
+    let empty = graph.add(None, Matrix4::IDENTITY);
+    graph.set_bounds(child, Some(unit_box));
+    graph.resolve();
+
+    // The parent doesn't have bounds of its own, so its world bounds are its child's.
+    let expected = unit_box.transform(translation(10.0, 10.0, 0.0));
+    assert_eq!(graph.world_bounds(child), Some(expected));
+    assert_eq!(graph.world_bounds(parent), Some(expected));
+    assert_eq!(graph.world_bounds(empty), None);
+    assert_eq!(graph.bounds(), Some(expected));
+
+    // Bounds propagate up when a child's bounds change...
+    graph.set_bounds(parent, Some(unit_box));
+    graph.resolve();
+    assert_eq!(
+        graph.world_bounds(parent),
+        Some(
+            unit_box
+                .transform(translation(10.0, 0.0, 0.0))
+                .union(expected)
+        )
+    );
+
+    // ...and when a child moves.
+    graph.set_local_transform(child, Matrix4::IDENTITY);
+    let resolved = graph.resolve();
+    assert_eq!(resolved.changed, [parent, child]);
+    assert_eq!(
+        graph.world_bounds(parent),
+        Some(unit_box.transform(translation(10.0, 0.0, 0.0)))
+    );
+}
@@ -4,7 +4,9 @@ use wgpu::include_wgsl;
 
 use crate::{
     gpu_buffer::GpuBuffer,
-    light::{DirectionalLight, PointLight},
+    index_buffer::IndexBuffer,
+    instance::{Draws, Instance, InstanceBuffer},
+    joint_matrices::JointMatrices,
     matrix::Matrix4,
     model_matrices::ModelMatrices,
     shadow_map_atlas::ShadowMapAtlasEntry,
@@ -24,6 +26,14 @@ pub struct Light {
     pub _padding: [u128; 7],
 }
 
+/// What to draw into one shadow map: a directional light's, or one face of a point light's. Each
+/// shadow map only draws the objects in its light's frustum.
+pub struct ShadowMapDraws<'a> {
+    pub shadow_map_light_gpu_id: u32,
+    pub shadow_map_atlas_entry: ShadowMapAtlasEntry,
+    pub draws: Draws<'a>,
+}
+
 pub struct ShadowMaps {
     pub bind_group_layout_0: wgpu::BindGroupLayout,
     pub bind_group_0: wgpu::BindGroup,
@@ -54,7 +64,7 @@ impl ShadowMaps {
             vertex: wgpu::VertexState {
                 module: &shader_module,
                 entry_point: "vertex_main",
-                buffers: &[Vertex::LAYOUT],
+                buffers: &[Vertex::LAYOUT, Instance::LAYOUT],
             },
             fragment: Some(wgpu::FragmentState {
                 module: &shader_module,
@@ -91,13 +101,20 @@ impl ShadowMaps {
         }
     }
 
+    pub fn set_bind_group_0(&mut self, device: &wgpu::Device, bind_group_0: BindGroup0) {
+        let (bind_group_layout_0, bind_group_0) = bind_group_0.create(device);
+        self.bind_group_layout_0 = bind_group_layout_0;
+        self.bind_group_0 = bind_group_0;
+    }
+
     pub fn record(
         &self,
         command_encoder: &mut wgpu::CommandEncoder,
         shadow_map_atlas: &wgpu::TextureView,
-        point_lights: &[PointLight],
-        directional_lights: &[DirectionalLight],
         vertex_buffer: &VertexBuffer,
+        index_buffer: &IndexBuffer,
+        instance_buffer: &InstanceBuffer,
+        shadow_maps: &[ShadowMapDraws],
     ) {
         let mut render_pass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("shadow_maps_pass"),
@@ -114,83 +131,19 @@ impl ShadowMaps {
 
         render_pass.set_pipeline(&self.render_pipeline);
         render_pass.set_vertex_buffer(0, vertex_buffer.as_raw_slice());
+        render_pass.set_vertex_buffer(1, instance_buffer.as_raw_slice());
+        render_pass.set_index_buffer(index_buffer.as_raw_slice(), IndexBuffer::FORMAT);
 
-        fn draw_shadow_map<'a>(
-            bind_group_0: &'a wgpu::BindGroup,
-            vertex_buffer: &VertexBuffer,
-            render_pass: &mut wgpu::RenderPass<'a>,
-            shadow_map_atlas_entry: &ShadowMapAtlasEntry,
-            shadow_map_light_gpu_id: u32,
-        ) {
-            let position = shadow_map_atlas_entry.position();
-            let size = shadow_map_atlas_entry.size();
+        for shadow_map in shadow_maps {
+            let position = shadow_map.shadow_map_atlas_entry.position();
+            let size = shadow_map.shadow_map_atlas_entry.size();
             render_pass.set_viewport(position.x, position.y, size, size, 0.0, 1.0);
             render_pass.set_bind_group(
                 0,
-                bind_group_0,
-                &[shadow_map_light_gpu_id * std::mem::size_of::<Light>() as u32],
-            );
-            render_pass.draw(0..vertex_buffer.len() as u32, 0..1);
-        }
-
-        for point_light in point_lights.iter() {
-            draw_shadow_map(
-                &self.bind_group_0,
-                vertex_buffer,
-                &mut render_pass,
-                &point_light.shadow_map_faces.x.shadow_map_atlas_entry,
-                point_light.shadow_map_faces.x.shadow_map_light_gpu_id,
-            );
-
-            draw_shadow_map(
-                &self.bind_group_0,
-                vertex_buffer,
-                &mut render_pass,
-                &point_light.shadow_map_faces.neg_x.shadow_map_atlas_entry,
-                point_light.shadow_map_faces.neg_x.shadow_map_light_gpu_id,
-            );
-
-            draw_shadow_map(
-                &self.bind_group_0,
-                vertex_buffer,
-                &mut render_pass,
-                &point_light.shadow_map_faces.y.shadow_map_atlas_entry,
-                point_light.shadow_map_faces.y.shadow_map_light_gpu_id,
-            );
-
-            draw_shadow_map(
-                &self.bind_group_0,
-                vertex_buffer,
-                &mut render_pass,
-                &point_light.shadow_map_faces.neg_y.shadow_map_atlas_entry,
-                point_light.shadow_map_faces.neg_y.shadow_map_light_gpu_id,
-            );
-
-            draw_shadow_map(
-                &self.bind_group_0,
-                vertex_buffer,
-                &mut render_pass,
-                &point_light.shadow_map_faces.z.shadow_map_atlas_entry,
-                point_light.shadow_map_faces.z.shadow_map_light_gpu_id,
-            );
-
-            draw_shadow_map(
-                &self.bind_group_0,
-                vertex_buffer,
-                &mut render_pass,
-                &point_light.shadow_map_faces.neg_z.shadow_map_atlas_entry,
-                point_light.shadow_map_faces.neg_z.shadow_map_light_gpu_id,
-            );
-        }
-
-        for directional_light in directional_lights.iter() {
-            draw_shadow_map(
                 &self.bind_group_0,
-                vertex_buffer,
-                &mut render_pass,
-                &directional_light.shadow_map_atlas_entry,
-                directional_light.shadow_map_light_gpu_id,
+                &[shadow_map.shadow_map_light_gpu_id * std::mem::size_of::<Light>() as u32],
             );
+            shadow_map.draws.record(&mut render_pass);
         }
     }
 }
@@ -198,6 +151,7 @@ impl ShadowMaps {
 pub struct BindGroup0<'a> {
     pub lights: &'a GpuBuffer<Light>,
     pub model_matrices: &'a ModelMatrices,
+    pub joint_matrices: &'a JointMatrices,
 }
 
 impl<'a> BindGroup0<'a> {
@@ -248,15 +202,38 @@ impl<'a> BindGroup0<'a> {
             },
         );
 
+        // @group(0) @binding(2)
+        // var<storage, read> joint_matrices: array<mat4x4<f32>>;
+        let joint_matrices = (
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: self.joint_matrices.as_raw_buffer(),
+                    offset: 0,
+                    size: None,
+                }),
+            },
+        );
+
         let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("shadow_maps_bind_group_layout_0"),
-            entries: &[directional_light.0, model_matrices.0],
+            entries: &[directional_light.0, model_matrices.0, joint_matrices.0],
         });
 
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("shadow_maps_bind_group_0"),
             layout: &layout,
-            entries: &[directional_light.1, model_matrices.1],
+            entries: &[directional_light.1, model_matrices.1, joint_matrices.1],
         });
 
         (layout, bind_group)
@@ -12,20 +12,58 @@ var<uniform> light: Light;
 @group(0) @binding(1)
 var<storage, read> model_matrices: array<mat4x4<f32>>;
 
+@group(0) @binding(2)
+var<storage, read> joint_matrices: array<mat4x4<f32>>;
+
+// Ids of model matrices and materials hold a generation in their high bits. See `gpu_buffer::pack_id`.
+const ID_INDEX_MASK: u32 = 0xffffffu;
+
 struct VertexInput{
   @location(0) position: vec3<f32>,
-  @location(1) model_matrix_id: u32,
   @location(2) normal: vec3<f32>,
-  @location(3) material_id: u32
+  @location(3) material_id: u32,
+  @location(4) uv: vec2<f32>,
+  @location(5) tangent: vec4<f32>,
+  @location(6) joints: vec4<u32>,
+  @location(7) weights: vec4<f32>
+}
+
+// See `instance.rs`.
+struct InstanceInput{
+  @location(8) model_matrix_id: u32,
+  @location(9) material_id: u32
+}
+
+fn is_skinned(weights: vec4<f32>) -> bool {
+  return any(weights != vec4<f32>(0.0));
+}
+
+/*
+Takes a vertex to world space. Skinned vertices are moved by a blend of their joints' matrices,
+which take them to their skeleton's coordinates, and then by their instance's model matrix like
+other vertices, so each instance of a skinned mesh is placed by its own model matrix.
+
+Also defined in `render_hdr.wgsl`, identically.
+*/
+fn vertex_transform(input: VertexInput, instance: InstanceInput) -> mat4x4<f32> {
+  let model_matrix = model_matrices[instance.model_matrix_id & ID_INDEX_MASK];
+  if !is_skinned(input.weights) {
+    return model_matrix;
+  }
+  return model_matrix * (
+    input.weights.x * joint_matrices[input.joints.x & ID_INDEX_MASK] +
+    input.weights.y * joint_matrices[input.joints.y & ID_INDEX_MASK] +
+    input.weights.z * joint_matrices[input.joints.z & ID_INDEX_MASK] +
+    input.weights.w * joint_matrices[input.joints.w & ID_INDEX_MASK]
+  );
 }
 
 @vertex
-fn vertex_main(input: VertexInput) -> @builtin(position) vec4<f32> {
-  let model_matrix = model_matrices[input.model_matrix_id];
+fn vertex_main(input: VertexInput, instance: InstanceInput) -> @builtin(position) vec4<f32> {
   return
     light.shadow_projection *
     light.shadow_view *
-    model_matrix *
+    vertex_transform(input, instance) *
     vec4<f32>(input.position, 1.0);
 }
 
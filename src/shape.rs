@@ -1,10 +1,14 @@
 use crate::{
-    material::MaterialId, model_matrices::ModelMatrixId, point::Point3, vector::Vec3,
+    material::MaterialId,
+    mesh::Mesh,
+    model_matrices::ModelMatrixId,
+    point::Point3,
+    vector::{Vec2, Vec3, Vec4},
     vertex::Vertex,
 };
 
-pub fn triangle(model_matrix_id: ModelMatrixId, material_id: MaterialId) -> Vec<Vertex> {
-    vec![
+pub fn triangle(model_matrix_id: ModelMatrixId, material_id: MaterialId) -> Mesh {
+    let vertices = vec![
         Vertex {
             position: Point3 {
                 x: 0.5,
@@ -18,6 +22,15 @@ pub fn triangle(model_matrix_id: ModelMatrixId, material_id: MaterialId) -> Vec<
                 z: 1.0,
             },
             material_id,
+            uv: Vec2 { x: 1.0, y: 1.0 },
+            tangent: Vec4 {
+                x: 1.0,
+                y: 0.0,
+                z: 0.0,
+                w: 1.0,
+            },
+            joints: [0; 4],
+            weights: Vec4::ZERO,
         },
         Vertex {
             position: Point3 {
@@ -32,6 +45,15 @@ pub fn triangle(model_matrix_id: ModelMatrixId, material_id: MaterialId) -> Vec<
                 z: 1.0,
             },
             material_id,
+            uv: Vec2 { x: 0.5, y: 0.0 },
+            tangent: Vec4 {
+                x: 1.0,
+                y: 0.0,
+                z: 0.0,
+                w: 1.0,
+            },
+            joints: [0; 4],
+            weights: Vec4::ZERO,
         },
         Vertex {
             position: Point3 {
@@ -46,13 +68,28 @@ pub fn triangle(model_matrix_id: ModelMatrixId, material_id: MaterialId) -> Vec<
                 z: 1.0,
             },
             material_id,
+            uv: Vec2 { x: 0.0, y: 1.0 },
+            tangent: Vec4 {
+                x: 1.0,
+                y: 0.0,
+                z: 0.0,
+                w: 1.0,
+            },
+            joints: [0; 4],
+            weights: Vec4::ZERO,
         },
-    ]
+    ];
+
+    Mesh {
+        vertices,
+        indices: vec![0, 1, 2],
+    }
 }
 
-pub fn square(model_matrix_id: ModelMatrixId, material_id: MaterialId, side: f32) -> Vec<Vertex> {
+pub fn square(model_matrix_id: ModelMatrixId, material_id: MaterialId, side: f32) -> Mesh {
     let side_over_2 = side / 2.0;
-    vec![
+    let vertices = vec![
+        // top right
         Vertex {
             position: Point3 {
                 x: side_over_2,
@@ -66,25 +103,21 @@ pub fn square(model_matrix_id: ModelMatrixId, material_id: MaterialId, side: f32
                 z: 1.0,
             },
             material_id,
-        },
-        Vertex {
-            position: Point3 {
-                x: -side_over_2,
-                y: side_over_2,
-                z: 0.0,
-            },
-            model_matrix_id,
-            normal: Vec3 {
-                x: 0.0,
+            uv: Vec2 { x: 1.0, y: 0.0 },
+            tangent: Vec4 {
+                x: 1.0,
                 y: 0.0,
-                z: 1.0,
+                z: 0.0,
+                w: 1.0,
             },
-            material_id,
+            joints: [0; 4],
+            weights: Vec4::ZERO,
         },
+        // top left
         Vertex {
             position: Point3 {
                 x: -side_over_2,
-                y: -side_over_2,
+                y: side_over_2,
                 z: 0.0,
             },
             model_matrix_id,
@@ -94,21 +127,17 @@ pub fn square(model_matrix_id: ModelMatrixId, material_id: MaterialId, side: f32
                 z: 1.0,
             },
             material_id,
-        },
-        Vertex {
-            position: Point3 {
-                x: side_over_2,
-                y: side_over_2,
-                z: 0.0,
-            },
-            model_matrix_id,
-            normal: Vec3 {
-                x: 0.0,
+            uv: Vec2 { x: 0.0, y: 0.0 },
+            tangent: Vec4 {
+                x: 1.0,
                 y: 0.0,
-                z: 1.0,
+                z: 0.0,
+                w: 1.0,
             },
-            material_id,
+            joints: [0; 4],
+            weights: Vec4::ZERO,
         },
+        // bottom left
         Vertex {
             position: Point3 {
                 x: -side_over_2,
@@ -122,7 +151,17 @@ pub fn square(model_matrix_id: ModelMatrixId, material_id: MaterialId, side: f32
                 z: 1.0,
             },
             material_id,
+            uv: Vec2 { x: 0.0, y: 1.0 },
+            tangent: Vec4 {
+                x: 1.0,
+                y: 0.0,
+                z: 0.0,
+                w: 1.0,
+            },
+            joints: [0; 4],
+            weights: Vec4::ZERO,
         },
+        // bottom right
         Vertex {
             position: Point3 {
                 x: side_over_2,
@@ -136,13 +175,28 @@ pub fn square(model_matrix_id: ModelMatrixId, material_id: MaterialId, side: f32
                 z: 1.0,
             },
             material_id,
+            uv: Vec2 { x: 1.0, y: 1.0 },
+            tangent: Vec4 {
+                x: 1.0,
+                y: 0.0,
+                z: 0.0,
+                w: 1.0,
+            },
+            joints: [0; 4],
+            weights: Vec4::ZERO,
         },
-    ]
+    ];
+
+    Mesh {
+        vertices,
+        indices: vec![0, 1, 2, 0, 2, 3],
+    }
 }
 
-pub fn floor(model_matrix_id: ModelMatrixId, material_id: MaterialId, side: f32) -> Vec<Vertex> {
+pub fn floor(model_matrix_id: ModelMatrixId, material_id: MaterialId, side: f32) -> Mesh {
     let side_over_2 = side / 2.0;
-    vec![
+    let vertices = vec![
+        // far right
         Vertex {
             position: Point3 {
                 x: side_over_2,
@@ -156,7 +210,17 @@ pub fn floor(model_matrix_id: ModelMatrixId, material_id: MaterialId, side: f32)
                 z: 0.0,
             },
             material_id,
+            uv: Vec2 { x: 1.0, y: 0.0 },
+            tangent: Vec4 {
+                x: 1.0,
+                y: 0.0,
+                z: 0.0,
+                w: 1.0,
+            },
+            joints: [0; 4],
+            weights: Vec4::ZERO,
         },
+        // near left
         Vertex {
             position: Point3 {
                 x: -side_over_2,
@@ -170,7 +234,17 @@ pub fn floor(model_matrix_id: ModelMatrixId, material_id: MaterialId, side: f32)
                 z: 0.0,
             },
             material_id,
+            uv: Vec2 { x: 0.0, y: 1.0 },
+            tangent: Vec4 {
+                x: 1.0,
+                y: 0.0,
+                z: 0.0,
+                w: 1.0,
+            },
+            joints: [0; 4],
+            weights: Vec4::ZERO,
         },
+        // near right
         Vertex {
             position: Point3 {
                 x: side_over_2,
@@ -184,21 +258,17 @@ pub fn floor(model_matrix_id: ModelMatrixId, material_id: MaterialId, side: f32)
                 z: 0.0,
             },
             material_id,
-        },
-        Vertex {
-            position: Point3 {
-                x: side_over_2,
+            uv: Vec2 { x: 1.0, y: 1.0 },
+            tangent: Vec4 {
+                x: 1.0,
                 y: 0.0,
-                z: -side_over_2,
-            },
-            model_matrix_id,
-            normal: Vec3 {
-                x: 0.0,
-                y: 1.0,
                 z: 0.0,
+                w: 1.0,
             },
-            material_id,
+            joints: [0; 4],
+            weights: Vec4::ZERO,
         },
+        // far left
         Vertex {
             position: Point3 {
                 x: -side_over_2,
@@ -212,235 +282,173 @@ pub fn floor(model_matrix_id: ModelMatrixId, material_id: MaterialId, side: f32)
                 z: 0.0,
             },
             material_id,
-        },
-        Vertex {
-            position: Point3 {
-                x: -side_over_2,
+            uv: Vec2 { x: 0.0, y: 0.0 },
+            tangent: Vec4 {
+                x: 1.0,
                 y: 0.0,
-                z: side_over_2,
-            },
-            model_matrix_id,
-            normal: Vec3 {
-                x: 0.0,
-                y: 1.0,
                 z: 0.0,
+                w: 1.0,
             },
-            material_id,
+            joints: [0; 4],
+            weights: Vec4::ZERO,
         },
-    ]
+    ];
+
+    Mesh {
+        vertices,
+        indices: vec![0, 1, 2, 0, 3, 1],
+    }
 }
 
-pub fn sphere(model_matrix_id: ModelMatrixId, material_id: MaterialId, radius: f32) -> Vec<Vertex> {
+pub fn sphere(model_matrix_id: ModelMatrixId, material_id: MaterialId, radius: f32) -> Mesh {
     let mut vertices = Vec::new();
+    let mut indices = Vec::new();
 
     // number of "longitude" lines
-    let meridians = 32;
+    let meridians: u32 = 32;
 
     // number of "latitude" lines
-    let parallels = 32;
+    let parallels: u32 = 32;
 
     let azimuth_per_meridian = std::f32::consts::TAU / meridians as f32;
     let elevation_per_parallel = std::f32::consts::PI / parallels as f32;
 
+    /* The texture is wrapped around the sphere: `u` follows the azimuth and `v` follows the
+    elevation (from the "north" pole to the "south" pole).
+
+    The tangent points in the direction of increasing azimuth.
+    */
+    let tangent = |azimuth: f32| Vec4 {
+        x: azimuth.cos(),
+        y: 0.0,
+        z: -azimuth.sin(),
+        w: 1.0,
+    };
+
     // origin = (0, 0, 0)
     // "north" pole = (0, radius, 0)
     // "south" pole = (0, -radius, 0)
+    //
+    // Each triangle that touches a pole has its own copy of the pole, so that the pole's `u` can be
+    // in the middle of the triangle.
+    let north_pole = vertices.len() as u32;
     for meridian in 0..meridians {
-        let ring_radius = radius * elevation_per_parallel.sin();
-
-        let azimuth = meridian as f32 * azimuth_per_meridian;
-        let next_azimuth = azimuth + azimuth_per_meridian;
-
-        let top = radius;
-        let bottom = radius * f32::cos(elevation_per_parallel);
-
-        let left_z = f32::cos(azimuth);
-        let left_x = f32::sin(azimuth);
-
-        let right_z = f32::cos(next_azimuth);
-        let right_x = f32::sin(next_azimuth);
-
-        let top = Point3 {
-            x: 0.0,
-            y: top,
-            z: 0.0,
-        };
-
-        let bottom_left = Point3 {
-            x: ring_radius * left_x,
-            y: bottom,
-            z: ring_radius * left_z,
-        };
-        let bottom_right = Point3 {
-            x: ring_radius * right_x,
-            y: bottom,
-            z: ring_radius * right_z,
-        };
-
+        let azimuth = (meridian as f32 + 0.5) * azimuth_per_meridian;
         vertices.push(Vertex {
-            position: top,
+            position: Point3 {
+                x: 0.0,
+                y: radius,
+                z: 0.0,
+            },
             model_matrix_id,
             normal: Vec3::Y,
             material_id,
-        });
-        vertices.push(Vertex {
-            position: bottom_left,
-            model_matrix_id,
-            normal: Vec3::from(bottom_left).normalize(),
-            material_id,
-        });
-        vertices.push(Vertex {
-            position: bottom_right,
-            model_matrix_id,
-            normal: Vec3::from(bottom_right).normalize(),
-            material_id,
+            uv: Vec2 {
+                x: azimuth / std::f32::consts::TAU,
+                y: 0.0,
+            },
+            tangent: tangent(azimuth),
+            joints: [0; 4],
+            weights: Vec4::ZERO,
         });
     }
 
-    for parallel in 1..(parallels - 1) {
+    // There's a ring of vertices on each parallel between the poles. The rings are shared by the
+    // triangles above and below them. The first and last vertices of a ring are in the same place,
+    // but the first has `u = 0` and the last has `u = 1`.
+    let first_ring = vertices.len() as u32;
+    for parallel in 1..parallels {
         let elevation = parallel as f32 * elevation_per_parallel;
-        let next_elevation = elevation + elevation_per_parallel;
-
         let ring_radius = radius * elevation.sin().abs();
-        let next_ring_radius = radius * next_elevation.sin().abs();
+        let y = radius * f32::cos(elevation);
 
-        for meridian in 0..meridians {
+        for meridian in 0..=meridians {
             let azimuth = meridian as f32 * azimuth_per_meridian;
-            let next_azimuth = azimuth + azimuth_per_meridian;
-
-            let top = radius * f32::cos(elevation);
-            let bottom = radius * f32::cos(next_elevation);
-
-            let left_z = f32::cos(azimuth);
-            let left_x = f32::sin(azimuth);
-
-            let right_z = f32::cos(next_azimuth);
-            let right_x = f32::sin(next_azimuth);
-
-            // top left = (azimuth, elevation)
-            let top_left = Point3 {
-                x: ring_radius * left_x,
-                y: top,
-                z: ring_radius * left_z,
-            };
-
-            // top right = (next_azimuth, elevation)
-            let top_right = Point3 {
-                x: ring_radius * right_x,
-                y: top,
-                z: ring_radius * right_z,
-            };
-
-            // bottom left = (azimuth, next_elevation)
-            let bottom_left = Point3 {
-                x: next_ring_radius * left_x,
-                y: bottom,
-                z: next_ring_radius * left_z,
-            };
 
-            // bottom right = (next_azimuth, next_elevation)
-            let bottom_right = Point3 {
-                x: next_ring_radius * right_x,
-                y: bottom,
-                z: next_ring_radius * right_z,
+            let position = Point3 {
+                x: ring_radius * f32::sin(azimuth),
+                y,
+                z: ring_radius * f32::cos(azimuth),
             };
-
-            // TR
-            vertices.push(Vertex {
-                position: top_right,
-                model_matrix_id,
-                normal: Vec3::from(top_right).normalize(),
-                material_id,
-            });
-            // BL
-            vertices.push(Vertex {
-                position: bottom_left,
-                model_matrix_id,
-                normal: Vec3::from(bottom_left).normalize(),
-                material_id,
-            });
-            // BR
-            vertices.push(Vertex {
-                position: bottom_right,
-                model_matrix_id,
-                normal: Vec3::from(bottom_right).normalize(),
-                material_id,
-            });
-
-            // TR
-            vertices.push(Vertex {
-                position: top_right,
-                model_matrix_id,
-                normal: Vec3::from(top_right).normalize(),
-                material_id,
-            });
-            // TL
-            vertices.push(Vertex {
-                position: top_left,
-                model_matrix_id,
-                normal: Vec3::from(top_left).normalize(),
-                material_id,
-            });
-            // BL
             vertices.push(Vertex {
-                position: bottom_left,
+                position,
                 model_matrix_id,
-                normal: Vec3::from(bottom_left).normalize(),
+                normal: Vec3::from(position).normalize(),
                 material_id,
+                uv: Vec2 {
+                    x: meridian as f32 / meridians as f32,
+                    y: parallel as f32 / parallels as f32,
+                },
+                tangent: tangent(azimuth),
+                joints: [0; 4],
+                weights: Vec4::ZERO,
             });
         }
     }
 
+    let south_pole = vertices.len() as u32;
+    for meridian in 0..meridians {
+        let azimuth = (meridian as f32 + 0.5) * azimuth_per_meridian;
+        vertices.push(Vertex {
+            position: Point3 {
+                x: 0.0,
+                y: -radius,
+                z: 0.0,
+            },
+            model_matrix_id,
+            normal: -Vec3::Y,
+            material_id,
+            uv: Vec2 {
+                x: azimuth / std::f32::consts::TAU,
+                y: 1.0,
+            },
+            tangent: tangent(azimuth),
+            joints: [0; 4],
+            weights: Vec4::ZERO,
+        });
+    }
+
+    // The index of the vertex at `(meridian, parallel)`, for `1 <= parallel < parallels`.
+    let ring_vertex = |meridian: u32, parallel: u32| -> u32 {
+        first_ring + (parallel - 1) * (meridians + 1) + meridian
+    };
+
     for meridian in 0..meridians {
-        let ring_radius = radius * f32::sin(elevation_per_parallel);
+        // top, bottom left, bottom right
+        indices.extend([
+            north_pole + meridian,
+            ring_vertex(meridian, 1),
+            ring_vertex(meridian + 1, 1),
+        ]);
+    }
 
-        let azimuth = meridian as f32 * azimuth_per_meridian;
-        let next_azimuth = azimuth + azimuth_per_meridian;
+    for parallel in 1..(parallels - 1) {
+        for meridian in 0..meridians {
+            // top left = (azimuth, elevation)
+            let top_left = ring_vertex(meridian, parallel);
 
-        let top = radius * f32::cos(std::f32::consts::PI - elevation_per_parallel);
-        let bottom = -radius;
+            // top right = (next_azimuth, elevation)
+            let top_right = ring_vertex(meridian + 1, parallel);
 
-        let left_z = f32::cos(azimuth);
-        let left_x = f32::sin(azimuth);
+            // bottom left = (azimuth, next_elevation)
+            let bottom_left = ring_vertex(meridian, parallel + 1);
 
-        let right_z = f32::cos(next_azimuth);
-        let right_x = f32::sin(next_azimuth);
+            // bottom right = (next_azimuth, next_elevation)
+            let bottom_right = ring_vertex(meridian + 1, parallel + 1);
 
-        let top_right = Point3 {
-            x: ring_radius * right_x,
-            y: top,
-            z: ring_radius * right_z,
-        };
-        let top_left = Point3 {
-            x: ring_radius * left_x,
-            y: bottom,
-            z: ring_radius * left_z,
-        };
-        let bottom = Point3 {
-            x: 0.0,
-            y: bottom,
-            z: 0.0,
-        };
+            indices.extend([top_right, bottom_left, bottom_right]);
+            indices.extend([top_right, top_left, bottom_left]);
+        }
+    }
 
-        vertices.push(Vertex {
-            position: top_right,
-            model_matrix_id,
-            normal: Vec3::from(top_right).normalize(),
-            material_id,
-        });
-        vertices.push(Vertex {
-            position: top_left,
-            model_matrix_id,
-            normal: Vec3::from(top_left).normalize(),
-            material_id,
-        });
-        vertices.push(Vertex {
-            position: bottom,
-            model_matrix_id,
-            normal: -Vec3::Y,
-            material_id,
-        });
+    for meridian in 0..meridians {
+        // top right, top left, bottom
+        indices.extend([
+            ring_vertex(meridian + 1, parallels - 1),
+            ring_vertex(meridian, parallels - 1),
+            south_pole + meridian,
+        ]);
     }
 
-    vertices
+    Mesh { vertices, indices }
 }
@@ -0,0 +1,372 @@
+/*! Mesh simplification using [quadric error metrics](https://www.cs.cmu.edu/~./garland/Papers/quadrics.pdf).
+
+Edges are collapsed one at a time, cheapest first. The cost of collapsing an edge is the sum of the
+squared distances from the vertex that's kept to the planes of the triangles around both of the
+edge's vertices.
+
+Only half-edge collapses are used: one of the edge's vertices is moved onto the other. No new
+vertices are created, so a simplified mesh's indices point into the original vertices and every
+level of detail can share one vertex buffer.
+
+Vertices with the same position are treated as one vertex when collapsing edges, so the mesh
+doesn't tear apart. Positions that are shared by several vertices (e.g. at UV seams, sharp edges or
+material boundaries), and positions on the mesh's open boundaries are never moved.
+*/
+
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{BinaryHeap, HashMap},
+};
+
+use crate::{point::Point3, vector::Vec3, vertex::Vertex};
+
+/// A symmetric 4x4 matrix that measures the sum of squared distances to a set of planes.
+#[derive(Debug, Clone, Copy, Default)]
+struct Quadric {
+    /// The upper triangle of the matrix, row by row: `aa ab ac ad bb bc bd cc cd dd`.
+    values: [f64; 10],
+}
+
+impl Quadric {
+    /// The plane `ax + by + cz + d = 0`, where `(a, b, c)` is a unit normal.
+    fn plane(normal: Vec3, point: Point3, weight: f64) -> Self {
+        let [a, b, c] = [normal.x as f64, normal.y as f64, normal.z as f64];
+        let d = -(a * point.x as f64 + b * point.y as f64 + c * point.z as f64);
+        let values = [
+            a * a,
+            a * b,
+            a * c,
+            a * d,
+            b * b,
+            b * c,
+            b * d,
+            c * c,
+            c * d,
+            d * d,
+        ];
+        Quadric {
+            values: values.map(|value| value * weight),
+        }
+    }
+
+    fn add(self, other: Self) -> Self {
+        let mut values = self.values;
+        for (value, other_value) in values.iter_mut().zip(other.values) {
+            *value += other_value;
+        }
+        Quadric { values }
+    }
+
+    /// The weighted sum of squared distances from `point` to the planes.
+    fn error(&self, point: Point3) -> f64 {
+        let [aa, ab, ac, ad, bb, bc, bd, cc, cd, dd] = self.values;
+        let [x, y, z] = [point.x as f64, point.y as f64, point.z as f64];
+        (aa * x * x + 2.0 * ab * x * y + 2.0 * ac * x * z + 2.0 * ad * x)
+            + (bb * y * y + 2.0 * bc * y * z + 2.0 * bd * y)
+            + (cc * z * z + 2.0 * cd * z)
+            + dd
+    }
+}
+
+/// Moving every vertex at position `from` onto position `to`.
+#[derive(Debug, Clone, Copy)]
+struct Collapse {
+    cost: f64,
+    from: u32,
+    to: u32,
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    // Ties are broken by position, so that simplification is deterministic.
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.cost
+            .total_cmp(&other.cost)
+            .then(self.from.cmp(&other.from))
+            .then(self.to.cmp(&other.to))
+    }
+}
+
+/**
+Simplify the triangles in `indices` until there are at most `target_index_count` indices, or until
+no more edges can be collapsed.
+
+The result's indices point into `vertices`.
+*/
+pub fn simplify(vertices: &[Vertex], indices: &[u32], target_index_count: usize) -> Vec<u32> {
+    /* Number the distinct positions that are used by the triangles. Edges are collapsed between
+    positions rather than vertices.
+    */
+    let mut position_ids: HashMap<[u32; 3], u32> = HashMap::new();
+    let mut positions: Vec<Point3> = Vec::new();
+    let mut vertex_positions: HashMap<u32, u32> = HashMap::new();
+    let mut position_vertex_counts: Vec<u32> = Vec::new();
+    for index in indices.iter() {
+        if vertex_positions.contains_key(index) {
+            continue;
+        }
+        let position = vertices[*index as usize].position;
+        let key = [position.x, position.y, position.z].map(f32::to_bits);
+        let position_id = *position_ids.entry(key).or_insert_with(|| {
+            positions.push(position);
+            position_vertex_counts.push(0);
+            (positions.len() - 1) as u32
+        });
+        position_vertex_counts[position_id as usize] += 1;
+        vertex_positions.insert(*index, position_id);
+    }
+    let position_of = |index: u32| vertex_positions[&index];
+
+    // Triangles that are already degenerate can't be collapsed, so they're dropped.
+    let mut triangles: Vec<[u32; 3]> = indices
+        .chunks(3)
+        .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+        .filter(|triangle| {
+            let [a, b, c] = triangle.map(position_of);
+            a != b && b != c && c != a
+        })
+        .collect();
+    let mut alive = vec![true; triangles.len()];
+    let mut alive_count = triangles.len();
+
+    let mut position_triangles: Vec<Vec<usize>> = vec![Vec::new(); positions.len()];
+    let mut edge_triangle_counts: HashMap<(u32, u32), u32> = HashMap::new();
+    let mut quadrics: Vec<Quadric> = vec![Quadric::default(); positions.len()];
+    for (triangle_index, triangle) in triangles.iter().enumerate() {
+        let [a, b, c] = triangle.map(position_of);
+        for (from, to) in [(a, b), (b, c), (c, a)] {
+            position_triangles[from as usize].push(triangle_index);
+            *edge_triangle_counts
+                .entry((from.min(to), from.max(to)))
+                .or_insert(0) += 1;
+        }
+
+        // The cross product's length is twice the triangle's area.
+        let normal = (positions[b as usize] - positions[a as usize])
+            .cross(positions[c as usize] - positions[a as usize]);
+        let double_area = normal.length();
+        if double_area > 0.0 {
+            let quadric = Quadric::plane(
+                normal.normalize(),
+                positions[a as usize],
+                double_area as f64 / 2.0,
+            );
+            for position in [a, b, c] {
+                quadrics[position as usize] = quadrics[position as usize].add(quadric);
+            }
+        }
+    }
+
+    let mut locked: Vec<bool> = position_vertex_counts
+        .iter()
+        .map(|vertex_count| *vertex_count > 1)
+        .collect();
+    for ((a, b), triangle_count) in edge_triangle_counts.iter() {
+        if *triangle_count == 1 {
+            locked[*a as usize] = true;
+            locked[*b as usize] = true;
+        }
+    }
+
+    let mut heap: BinaryHeap<Reverse<Collapse>> = BinaryHeap::new();
+    let push_collapses =
+        |heap: &mut BinaryHeap<Reverse<Collapse>>, quadrics: &[Quadric], a: u32, b: u32| {
+            for (from, to) in [(a, b), (b, a)] {
+                if !locked[from as usize] {
+                    let cost = quadrics[from as usize]
+                        .add(quadrics[to as usize])
+                        .error(positions[to as usize]);
+                    heap.push(Reverse(Collapse { cost, from, to }));
+                }
+            }
+        };
+    for triangle in triangles.iter() {
+        let [a, b, c] = triangle.map(position_of);
+        for (from, to) in [(a, b), (b, c), (c, a)] {
+            push_collapses(&mut heap, &quadrics, from, to);
+        }
+    }
+
+    let mut collapsed = vec![false; positions.len()];
+    let target_triangle_count = target_index_count / 3;
+    while alive_count > target_triangle_count {
+        let Some(Reverse(collapse)) = heap.pop() else {
+            break;
+        };
+        let Collapse { cost, from, to } = collapse;
+        if collapsed[from as usize] || collapsed[to as usize] {
+            continue;
+        }
+
+        /* Quadrics only grow as positions are merged, so a collapse's cost can only increase. A
+        collapse whose cost has increased since it was queued is queued again with its new cost.
+        */
+        let quadric = quadrics[from as usize].add(quadrics[to as usize]);
+        let current_cost = quadric.error(positions[to as usize]);
+        if current_cost > cost {
+            heap.push(Reverse(Collapse {
+                cost: current_cost,
+                from,
+                to,
+            }));
+            continue;
+        }
+
+        /* The edge has to still exist, and moving `from` onto `to` mustn't flip any of the
+        triangles that survive the collapse.
+        */
+        let mut target_vertex = None;
+        let mut flips = false;
+        for triangle_index in position_triangles[from as usize].iter() {
+            if !alive[*triangle_index] {
+                continue;
+            }
+            let triangle = triangles[*triangle_index];
+            let triangle_positions = triangle.map(position_of);
+            if let Some(corner) = triangle_positions
+                .iter()
+                .position(|position| *position == to)
+            {
+                target_vertex.get_or_insert(triangle[corner]);
+                continue;
+            }
+
+            let [a, b, c] = triangle_positions.map(|position| positions[position as usize]);
+            let normal = (b - a).cross(c - a);
+            let [a, b, c] = triangle_positions.map(|position| {
+                if position == from {
+                    positions[to as usize]
+                } else {
+                    positions[position as usize]
+                }
+            });
+            let new_normal = (b - a).cross(c - a);
+            if new_normal.dot(normal) <= 0.0 {
+                flips = true;
+                break;
+            }
+        }
+        let Some(target_vertex) = target_vertex else {
+            continue;
+        };
+        if flips {
+            continue;
+        }
+
+        let from_triangles = std::mem::take(&mut position_triangles[from as usize]);
+        for triangle_index in from_triangles {
+            if !alive[triangle_index] {
+                continue;
+            }
+            let triangle = &mut triangles[triangle_index];
+            if triangle.iter().any(|vertex| vertex_positions[vertex] == to) {
+                alive[triangle_index] = false;
+                alive_count -= 1;
+            } else {
+                for vertex in triangle.iter_mut() {
+                    if vertex_positions[vertex] == from {
+                        *vertex = target_vertex;
+                    }
+                }
+                position_triangles[to as usize].push(triangle_index);
+            }
+        }
+        collapsed[from as usize] = true;
+        quadrics[to as usize] = quadric;
+
+        position_triangles[to as usize].retain(|triangle_index| alive[*triangle_index]);
+        for triangle_index in position_triangles[to as usize].iter() {
+            for vertex in triangles[*triangle_index] {
+                let position = vertex_positions[&vertex];
+                if position != to {
+                    push_collapses(&mut heap, &quadrics, to, position);
+                }
+            }
+        }
+    }
+
+    triangles
+        .iter()
+        .zip(alive)
+        .filter(|(_, alive)| *alive)
+        .flat_map(|(triangle, _)| *triangle)
+        .collect()
+}
+
+#[test]
+fn test_simplify_grid() {
+    use crate::{material::MaterialId, model_matrices::ModelMatrixId, shape};
+
+    // A flat, 16x16 grid of squares, made from copies of a square.
+    let square = shape::square(ModelMatrixId(0), MaterialId(0), 1.0);
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    let mut vertex_ids: HashMap<(i32, i32), u32> = HashMap::new();
+    for x in 0..16 {
+        for y in 0..16 {
+            for index in square.indices.iter() {
+                let corner = square.vertices[*index as usize];
+                let key = (
+                    x + (corner.position.x + 0.5) as i32,
+                    y + (corner.position.y + 0.5) as i32,
+                );
+                let vertex = *vertex_ids.entry(key).or_insert_with(|| {
+                    vertices.push(Vertex {
+                        position: Point3 {
+                            x: key.0 as f32,
+                            y: key.1 as f32,
+                            z: 0.0,
+                        },
+                        ..corner
+                    });
+                    (vertices.len() - 1) as u32
+                });
+                indices.push(vertex);
+            }
+        }
+    }
+    assert_eq!(indices.len(), 16 * 16 * 6);
+
+    let simplified = simplify(&vertices, &indices, indices.len() / 4);
+    assert!(simplified.len() <= indices.len() / 4);
+
+    // The grid is flat, so it should keep its shape: every triangle faces up, and they add up to
+    // the same area.
+    let mut area = 0.0;
+    for triangle in simplified.chunks(3) {
+        let [a, b, c] = [0, 1, 2].map(|corner| vertices[triangle[corner] as usize].position);
+        let normal = (b - a).cross(c - a);
+        assert!(normal.z > 0.0);
+        area += normal.length() / 2.0;
+    }
+    assert!((area - 16.0 * 16.0).abs() < 1e-3, "{}", area);
+}
+
+#[test]
+fn test_simplify_sphere() {
+    use crate::{material::MaterialId, model_matrices::ModelMatrixId, shape};
+
+    let sphere = shape::sphere(ModelMatrixId(0), MaterialId(0), 1.0);
+    let simplified = simplify(&sphere.vertices, &sphere.indices, sphere.indices.len() / 2);
+    assert!(simplified.len() <= sphere.indices.len() / 2);
+
+    // Every remaining triangle should still face outwards.
+    for triangle in simplified.chunks(3) {
+        let [a, b, c] = [0, 1, 2].map(|corner| sphere.vertices[triangle[corner] as usize].position);
+        let normal = (b - a).cross(c - a);
+        assert!(normal.dot(Vec3::from(a)) > 0.0);
+    }
+}
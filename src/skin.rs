@@ -0,0 +1,446 @@
+/*! Skeletal animation of skinned meshes.
+
+A [`Skeleton`] is a hierarchy of [`Joint`]s. Each skinned vertex is attached to up to 4 joints (see
+[`Vertex::joints`](crate::vertex::Vertex::joints)), and follows them with the matrices from
+[`joint_palette`], which go in [`JointMatrices`](crate::joint_matrices::JointMatrices). The
+vertices are blended in the vertex shaders, so the meshes themselves never change.
+
+A [`SkeletonAnimation`] poses a skeleton at a point in time. Evaluating one only depends on the time,
+like [`Animation`](crate::animation::Animation).
+*/
+
+use crate::{
+    aabb::Aabb,
+    animation::Curve,
+    matrix::Matrix4,
+    point::Point3,
+    vector::{Vec3, Vec4},
+};
+
+/// Scale, then rotate, then translate.
+#[derive(Debug, Clone, Copy)]
+pub struct JointTransform {
+    pub translation: Vec3,
+
+    /// A unit quaternion, with the scalar part in `w`.
+    pub rotation: Vec4,
+
+    pub scale: Vec3,
+}
+
+impl Default for JointTransform {
+    fn default() -> Self {
+        JointTransform {
+            translation: Vec3::ZERO,
+            rotation: Vec4 {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+                w: 1.0,
+            },
+            scale: Vec3 {
+                x: 1.0,
+                y: 1.0,
+                z: 1.0,
+            },
+        }
+    }
+}
+
+impl JointTransform {
+    pub fn matrix(&self) -> Matrix4 {
+        let rotation = cgmath::Quaternion::new(
+            self.rotation.w,
+            self.rotation.x,
+            self.rotation.y,
+            self.rotation.z,
+        );
+        (cgmath::Matrix4::from_translation(self.translation.into())
+            * cgmath::Matrix4::from(rotation)
+            * cgmath::Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z))
+        .into()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Joint {
+    pub name: String,
+
+    /// The index of the joint's parent in [`Skeleton::joints`], or `None` for the skeleton's roots.
+    pub parent: Option<usize>,
+
+    /// Takes the joint's coordinates to its parent's coordinates when the joint isn't animated.
+    pub rest_transform: JointTransform,
+
+    /// Takes a vertex from the mesh's coordinates to the joint's coordinates in the pose that the
+    /// mesh was modelled in.
+    pub inverse_bind_matrix: Matrix4,
+}
+
+#[derive(Debug, Clone)]
+pub struct Skeleton {
+    /// Parents come before their children.
+    pub joints: Vec<Joint>,
+}
+
+impl Skeleton {
+    /// Every joint in its rest transform.
+    pub fn rest_pose(&self) -> Vec<JointTransform> {
+        self.joints
+            .iter()
+            .map(|joint| joint.rest_transform)
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum JointCurve {
+    Translation(Curve<Vec3>),
+
+    /// Unit quaternions, with the scalar part in `w`.
+    Rotation(Curve<Vec4>),
+
+    Scale(Curve<Vec3>),
+}
+
+#[derive(Debug, Clone)]
+pub struct JointTrack {
+    /// The index of the animated joint in [`Skeleton::joints`].
+    pub joint: usize,
+    pub curve: JointCurve,
+}
+
+#[derive(Debug, Clone)]
+pub struct SkeletonAnimation {
+    pub name: String,
+    pub tracks: Vec<JointTrack>,
+}
+
+impl SkeletonAnimation {
+    /// The time of the last keyframe, in seconds.
+    pub fn duration(&self) -> f32 {
+        self.tracks
+            .iter()
+            .map(|track| match &track.curve {
+                JointCurve::Translation(curve) | JointCurve::Scale(curve) => curve.duration(),
+                JointCurve::Rotation(curve) => curve.duration(),
+            })
+            .fold(0.0, f32::max)
+    }
+
+    /// The skeleton's joints at `time`. Joints and properties without tracks keep their rest
+    /// transforms.
+    pub fn pose(&self, skeleton: &Skeleton, time: f32) -> Vec<JointTransform> {
+        let mut pose = skeleton.rest_pose();
+        for track in &self.tracks {
+            let transform = &mut pose[track.joint];
+            match &track.curve {
+                JointCurve::Translation(curve) => {
+                    if let Some(translation) = curve.sample(time) {
+                        transform.translation = translation;
+                    }
+                }
+                JointCurve::Rotation(curve) => {
+                    if let Some(rotation) = curve.sample(time) {
+                        transform.rotation = normalize_quaternion(rotation);
+                    }
+                }
+                JointCurve::Scale(curve) => {
+                    if let Some(scale) = curve.sample(time) {
+                        transform.scale = scale;
+                    }
+                }
+            }
+        }
+        pose
+    }
+}
+
+fn normalize_quaternion(rotation: Vec4) -> Vec4 {
+    let length = (rotation.x * rotation.x
+        + rotation.y * rotation.y
+        + rotation.z * rotation.z
+        + rotation.w * rotation.w)
+        .sqrt();
+    if length == 0.0 {
+        return JointTransform::default().rotation;
+    }
+    Vec4 {
+        x: rotation.x / length,
+        y: rotation.y / length,
+        z: rotation.z / length,
+        w: rotation.w / length,
+    }
+}
+
+/**
+The skinning matrix of each of the skeleton's joints, in the order of [`Skeleton::joints`].
+
+`pose` has a transform for each joint (see [`SkeletonAnimation::pose`]), and `root_transform`
+takes the skeleton's coordinates to world space. A skinning matrix takes a vertex from the mesh's
+coordinates to world space, following its joint from the pose that the mesh was modelled in to
+`pose`.
+*/
+pub fn joint_palette(
+    skeleton: &Skeleton,
+    pose: &[JointTransform],
+    root_transform: Matrix4,
+) -> Vec<Matrix4> {
+    assert_eq!(
+        pose.len(),
+        skeleton.joints.len(),
+        "the pose doesn't match the skeleton"
+    );
+
+    let mut world_transforms: Vec<Matrix4> = Vec::with_capacity(pose.len());
+    for (joint, transform) in skeleton.joints.iter().zip(pose) {
+        let parent_transform = match joint.parent {
+            Some(parent) => world_transforms[parent],
+            None => root_transform,
+        };
+        world_transforms.push(parent_transform * transform.matrix());
+    }
+
+    world_transforms
+        .iter()
+        .zip(&skeleton.joints)
+        .map(|(world_transform, joint)| *world_transform * joint.inverse_bind_matrix)
+        .collect()
+}
+
+/**
+The bounds of a skinned mesh's vertices, grouped by the joints that move them, so that the mesh's
+bounds in any pose can be found without going through its vertices (see [`SkinBounds::posed`]).
+*/
+#[derive(Debug, Clone, Default)]
+pub struct SkinBounds {
+    /// The bounds of the vertices that each joint moves, in the mesh's coordinates, in the order of
+    /// [`Skeleton::joints`]. `None` for joints that don't move any.
+    pub joints: Vec<Option<Aabb>>,
+
+    /// The bounds of the mesh's vertices that aren't skinned, which don't move with the pose.
+    pub unskinned: Option<Aabb>,
+}
+
+impl SkinBounds {
+    pub fn new(joint_count: usize) -> Self {
+        Self {
+            joints: vec![None; joint_count],
+            unskinned: None,
+        }
+    }
+
+    /// Add a vertex, with the indices of its joints in [`Skeleton::joints`] and their weights.
+    pub fn add(&mut self, position: Point3, joints: [usize; 4], weights: Vec4) {
+        let weights = [weights.x, weights.y, weights.z, weights.w];
+        let union = |bounds: Option<Aabb>| {
+            Some(bounds.map_or(Aabb::point(position), |bounds| {
+                bounds.union(Aabb::point(position))
+            }))
+        };
+        if weights.iter().all(|weight| *weight == 0.0) {
+            self.unskinned = union(self.unskinned);
+        }
+        for (joint, weight) in joints.into_iter().zip(weights) {
+            if weight > 0.0 {
+                self.joints[joint] = union(self.joints[joint]);
+            }
+        }
+    }
+
+    /**
+    The mesh's bounds in the pose that `palette` is for (see [`joint_palette`]).
+
+    A skinned vertex is a weighted average of where each of its joints would take it, so it's inside
+    the bounds of those places.
+    */
+    pub fn posed(&self, palette: &[Matrix4]) -> Option<Aabb> {
+        self.joints
+            .iter()
+            .zip(palette)
+            .filter_map(|(bounds, matrix)| Some(bounds.as_ref()?.transform(*matrix)))
+            .chain(self.unskinned)
+            .reduce(Aabb::union)
+    }
+}
+
+#[test]
+fn test_joint_palette() {
+    use crate::animation::Keyframe;
+
+    // A two-joint arm pointing up the Y axis, with the elbow 1 unit above the shoulder.
+    let elbow_position = Vec3 {
+        x: 0.0,
+        y: 1.0,
+        z: 0.0,
+    };
+    let skeleton = Skeleton {
+        joints: vec![
+            Joint {
+                name: String::from("shoulder"),
+                parent: None,
+                rest_transform: JointTransform::default(),
+                inverse_bind_matrix: Matrix4::IDENTITY,
+            },
+            Joint {
+                name: String::from("elbow"),
+                parent: Some(0),
+                rest_transform: JointTransform {
+                    translation: elbow_position,
+                    ..JointTransform::default()
+                },
+                inverse_bind_matrix: cgmath::Matrix4::from_translation((-elbow_position).into())
+                    .into(),
+            },
+        ],
+    };
+    let hand = Point3 {
+        x: 0.0,
+        y: 2.0,
+        z: 0.0,
+    };
+    let skin = |palette: &[Matrix4], joint: usize| Point3::from(palette[joint] * hand.with_w(1.0));
+    let assert_near = |actual: Point3, expected: (f32, f32, f32)| {
+        assert!(
+            (actual.x - expected.0).abs() < 1e-5
+                && (actual.y - expected.1).abs() < 1e-5
+                && (actual.z - expected.2).abs() < 1e-5,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    };
+
+    // In the rest pose, the vertices stay where they were modelled.
+    let palette = joint_palette(&skeleton, &skeleton.rest_pose(), Matrix4::IDENTITY);
+    assert_near(skin(&palette, 1), (0.0, 2.0, 0.0));
+
+    // Rotating the shoulder 90° around Z swings the arm's vertices round to the -X axis.
+    let animation = SkeletonAnimation {
+        name: String::from("swing"),
+        tracks: vec![JointTrack {
+            joint: 0,
+            curve: JointCurve::Rotation(Curve {
+                interpolation: Default::default(),
+                keyframes: vec![
+                    Keyframe {
+                        time: 0.0,
+                        value: JointTransform::default().rotation,
+                    },
+                    Keyframe {
+                        time: 1.0,
+                        value: Vec4 {
+                            x: 0.0,
+                            y: 0.0,
+                            z: std::f32::consts::FRAC_1_SQRT_2,
+                            w: std::f32::consts::FRAC_1_SQRT_2,
+                        },
+                    },
+                ],
+            }),
+        }],
+    };
+    assert_eq!(animation.duration(), 1.0);
+
+    let palette = joint_palette(
+        &skeleton,
+        &animation.pose(&skeleton, 1.0),
+        Matrix4::IDENTITY,
+    );
+    assert_near(skin(&palette, 0), (-2.0, 0.0, 0.0));
+    assert_near(skin(&palette, 1), (-2.0, 0.0, 0.0));
+
+    // The root transform moves the whole skeleton.
+    let palette = joint_palette(
+        &skeleton,
+        &animation.pose(&skeleton, 1.0),
+        cgmath::Matrix4::from_translation(cgmath::Vector3::new(0.0, 0.0, 3.0)).into(),
+    );
+    assert_near(skin(&palette, 1), (-2.0, 0.0, 3.0));
+
+    // Half way there, the normalised quaternion is a 45° turn.
+    let half = std::f32::consts::SQRT_2;
+    let palette = joint_palette(
+        &skeleton,
+        &animation.pose(&skeleton, 0.5),
+        Matrix4::IDENTITY,
+    );
+    assert_near(skin(&palette, 1), (-half, half, 0.0));
+}
+
+#[test]
+fn test_skin_bounds() {
+    // Two joints that start together, and a vertex that's moved by both.
+    let skeleton = Skeleton {
+        joints: vec![
+            Joint {
+                name: String::from("root"),
+                parent: None,
+                rest_transform: JointTransform::default(),
+                inverse_bind_matrix: Matrix4::IDENTITY,
+            },
+            Joint {
+                name: String::from("tip"),
+                parent: Some(0),
+                rest_transform: JointTransform::default(),
+                inverse_bind_matrix: Matrix4::IDENTITY,
+            },
+        ],
+    };
+    let mut bounds = SkinBounds::new(skeleton.joints.len());
+    let weights = Vec4 {
+        x: 0.5,
+        y: 0.5,
+        z: 0.0,
+        w: 0.0,
+    };
+    bounds.add(Point3::ZERO, [0, 1, 0, 0], weights);
+    bounds.add(
+        Point3 {
+            x: 1.0,
+            y: 0.0,
+            z: 0.0,
+        },
+        [0, 0, 0, 0],
+        Vec4 {
+            x: 1.0,
+            y: 0.0,
+            z: 0.0,
+            w: 0.0,
+        },
+    );
+    assert_eq!(bounds.unskinned, None);
+
+    let palette = joint_palette(&skeleton, &skeleton.rest_pose(), Matrix4::IDENTITY);
+    assert_eq!(
+        bounds.posed(&palette),
+        Some(Aabb {
+            min: Point3::ZERO,
+            max: Point3 {
+                x: 1.0,
+                y: 0.0,
+                z: 0.0,
+            },
+        })
+    );
+
+    // Moving the tip up stretches the bounds to where it takes the shared vertex.
+    let mut pose = skeleton.rest_pose();
+    pose[1].translation = Vec3 {
+        x: 0.0,
+        y: 2.0,
+        z: 0.0,
+    };
+    let palette = joint_palette(&skeleton, &pose, Matrix4::IDENTITY);
+    assert_eq!(
+        bounds.posed(&palette),
+        Some(Aabb {
+            min: Point3::ZERO,
+            max: Point3 {
+                x: 1.0,
+                y: 2.0,
+                z: 0.0,
+            },
+        })
+    );
+}
@@ -0,0 +1,203 @@
+/*! [MikkTSpace](http://www.mikktspace.com/) tangent generation.
+
+MikkTSpace is the tangent space that most tools (e.g. Blender, Substance) bake normal maps in, so
+using the same tangents when rendering makes baked normal maps look the way they did in the tool.
+
+MikkTSpace uses texture coordinates whose origin is at the bottom left, so the tangents are
+calculated with `v` flipped relative to [`Vertex::uv`]. This is the same convention as glTF.
+*/
+
+use std::collections::HashMap;
+
+use crate::{
+    mesh::Mesh,
+    vector::{Vec3, Vec4},
+    vertex::Vertex,
+};
+
+struct Geometry<'a> {
+    vertices: &'a [Vertex],
+    indices: &'a [u32],
+
+    /// The tangent at each of the triangles' corners.
+    tangents: Vec<Vec4>,
+}
+
+impl<'a> Geometry<'a> {
+    fn vertex(&self, face: usize, vert: usize) -> &Vertex {
+        &self.vertices[self.indices[3 * face + vert] as usize]
+    }
+}
+
+impl<'a> bevy_mikktspace::Geometry for Geometry<'a> {
+    fn num_faces(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        let position = self.vertex(face, vert).position;
+        [position.x, position.y, position.z]
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        let normal = self.vertex(face, vert).normal;
+        [normal.x, normal.y, normal.z]
+    }
+
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        let uv = self.vertex(face, vert).uv;
+        [uv.x, 1.0 - uv.y]
+    }
+
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        self.tangents[3 * face + vert] = Vec4 {
+            x: tangent[0],
+            y: tangent[1],
+            z: tangent[2],
+            w: tangent[3],
+        };
+    }
+}
+
+/**
+Calculate the mesh's tangents from its positions, normals and texture coordinates.
+
+The tangents at a vertex can differ between the triangles that share it (e.g. on a UV seam), so
+vertices are split when that happens.
+*/
+pub fn generate_tangents(mesh: &mut Mesh) {
+    let mut geometry = Geometry {
+        vertices: &mesh.vertices,
+        indices: &mesh.indices,
+        tangents: vec![Vec3::ZERO.with_w(1.0); mesh.indices.len()],
+    };
+
+    if !bevy_mikktspace::generate_tangents(&mut geometry) {
+        log::warn!("failed to generate tangents");
+        for vertex in mesh.vertices.iter_mut() {
+            vertex.tangent = perpendicular_tangent(vertex.normal);
+        }
+        return;
+    }
+    let tangents = geometry.tangents;
+
+    let mut vertices = Vec::with_capacity(mesh.vertices.len());
+    let mut split_vertices: HashMap<(u32, [u32; 4]), u32> = HashMap::new();
+    for (index, tangent) in mesh.indices.iter_mut().zip(tangents) {
+        let key = (
+            *index,
+            [tangent.x, tangent.y, tangent.z, tangent.w].map(f32::to_bits),
+        );
+        *index = *split_vertices.entry(key).or_insert_with(|| {
+            vertices.push(Vertex {
+                tangent,
+                ..mesh.vertices[*index as usize]
+            });
+            (vertices.len() - 1) as u32
+        });
+    }
+    mesh.vertices = vertices;
+}
+
+/**
+A tangent for a vertex that doesn't have texture coordinates.
+
+There's no direction of increasing `u` to follow, so this is just some unit vector that's
+perpendicular to `normal`.
+*/
+pub fn perpendicular_tangent(normal: Vec3) -> Vec4 {
+    let axis = if normal.x.abs() < 0.9 {
+        Vec3::X
+    } else {
+        Vec3::Y
+    };
+    let tangent = normal.cross(axis).cross(normal);
+    if tangent.length() > 0.0 {
+        tangent.normalize().with_w(1.0)
+    } else {
+        Vec3::X.with_w(1.0)
+    }
+}
+
+#[test]
+fn test_generate_tangents_matches_shapes() {
+    use crate::{material::MaterialId, model_matrices::ModelMatrixId, shape};
+
+    for expected in [
+        shape::triangle(ModelMatrixId(0), MaterialId(0)),
+        shape::square(ModelMatrixId(0), MaterialId(0), 2.0),
+        shape::floor(ModelMatrixId(0), MaterialId(0), 2.0),
+    ] {
+        let mut mesh = Mesh {
+            vertices: expected
+                .vertices
+                .iter()
+                .map(|vertex| Vertex {
+                    tangent: Vec3::ZERO.with_w(0.0),
+                    ..*vertex
+                })
+                .collect(),
+            indices: expected.indices.clone(),
+        };
+        generate_tangents(&mut mesh);
+
+        // Flat shapes don't have seams, so no vertices are split.
+        assert_eq!(mesh.vertices.len(), expected.vertices.len());
+        assert_eq!(mesh.indices, expected.indices);
+        for (vertex, expected_vertex) in mesh.vertices.iter().zip(expected.vertices.iter()) {
+            let (tangent, expected_tangent) = (vertex.tangent, expected_vertex.tangent);
+            assert!(
+                tangent.xyz().dot(expected_tangent.xyz()) > 0.9999,
+                "{:?}",
+                vertex
+            );
+            assert_eq!(tangent.w, expected_tangent.w);
+        }
+    }
+}
+
+#[test]
+fn test_generate_tangents_splits_mirrored_uvs() {
+    use crate::{material::MaterialId, model_matrices::ModelMatrixId, point::Point3, vector::Vec2};
+
+    /* Two triangles in the z = 0 plane that share the edge x = 0. The texture is mirrored across
+    the edge, so the shared vertices need a different tangent on each side.
+    */
+    let vertex = |x: f32, y: f32, u: f32| Vertex {
+        position: Point3 { x, y, z: 0.0 },
+        model_matrix_id: ModelMatrixId(0),
+        normal: Vec3::Z,
+        material_id: MaterialId(0),
+        uv: Vec2 { x: u, y: 1.0 - y },
+        tangent: Vec3::ZERO.with_w(0.0),
+        joints: [0; 4],
+        weights: Vec4::ZERO,
+    };
+    let mut mesh = Mesh {
+        vertices: vec![
+            vertex(0.0, 0.0, 0.0),
+            vertex(0.0, 1.0, 0.0),
+            vertex(-1.0, 0.0, 1.0),
+            vertex(1.0, 0.0, 1.0),
+        ],
+        indices: vec![0, 1, 2, 0, 3, 1],
+    };
+    generate_tangents(&mut mesh);
+
+    assert_eq!(mesh.vertices.len(), 6);
+    for triangle in mesh.indices.chunks(3) {
+        let is_left = triangle
+            .iter()
+            .any(|index| mesh.vertices[*index as usize].position.x < 0.0);
+        for index in triangle {
+            let tangent = mesh.vertices[*index as usize].tangent;
+            let expected_x = if is_left { -1.0 } else { 1.0 };
+            assert!((tangent.x - expected_x).abs() < 1e-4, "{:?}", tangent);
+            assert_eq!(tangent.w, if is_left { -1.0 } else { 1.0 });
+        }
+    }
+}
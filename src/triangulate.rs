@@ -0,0 +1,155 @@
+use crate::{point::Point3, vector::Vec3};
+
+/**
+Split a simple polygon into triangles using [ear clipping](https://en.wikipedia.org/wiki/Polygon_triangulation#Ear_clipping_method).
+
+The polygon can be convex or concave, and doesn't have to lie exactly on a plane. Returns indices
+into `polygon`. The triangles have the same winding order as the polygon.
+*/
+pub fn triangulate(polygon: &[Point3]) -> Vec<[usize; 3]> {
+    if polygon.len() < 3 {
+        return Vec::new();
+    }
+
+    let normal = polygon_normal(polygon);
+
+    let mut triangles = Vec::with_capacity(polygon.len() - 2);
+    let mut remaining: Vec<usize> = (0..polygon.len()).collect();
+
+    while remaining.len() > 3 {
+        let ear = (0..remaining.len())
+            .find(|&corner| is_ear(polygon, &remaining, normal, corner))
+            /* Only happens when the polygon is degenerate or self-intersecting. Clipping any
+            corner keeps the loop making progress, and still covers the polygon's vertices.
+            */
+            .unwrap_or(0);
+
+        let (previous, current, next) = corner_indices(&remaining, ear);
+        triangles.push([previous, current, next]);
+        remaining.remove(ear);
+    }
+
+    triangles.push([remaining[0], remaining[1], remaining[2]]);
+
+    triangles
+}
+
+/**
+The direction that the polygon faces, using [Newell's method](https://www.khronos.org/opengl/wiki/Calculating_a_Surface_Normal#Newell.27s_Method).
+
+The polygon's vertices are counter-clockwise when viewed from the direction of the normal. The
+normal isn't normalized.
+*/
+fn polygon_normal(polygon: &[Point3]) -> Vec3 {
+    let mut normal = Vec3::ZERO;
+    for (index, current) in polygon.iter().enumerate() {
+        let next = polygon[(index + 1) % polygon.len()];
+        normal += Vec3 {
+            x: (current.y - next.y) * (current.z + next.z),
+            y: (current.z - next.z) * (current.x + next.x),
+            z: (current.x - next.x) * (current.y + next.y),
+        };
+    }
+    normal
+}
+
+/// The polygon indices of the corner at `remaining[corner]` and its neighbours.
+fn corner_indices(remaining: &[usize], corner: usize) -> (usize, usize, usize) {
+    let previous = remaining[(corner + remaining.len() - 1) % remaining.len()];
+    let current = remaining[corner];
+    let next = remaining[(corner + 1) % remaining.len()];
+    (previous, current, next)
+}
+
+/**
+A corner is an "ear" when it's convex and no other vertex of the polygon is inside the triangle
+it forms with its neighbours. Cutting off an ear leaves a smaller simple polygon.
+*/
+fn is_ear(polygon: &[Point3], remaining: &[usize], normal: Vec3, corner: usize) -> bool {
+    let (previous, current, next) = corner_indices(remaining, corner);
+    let a = polygon[previous];
+    let b = polygon[current];
+    let c = polygon[next];
+
+    let is_convex = (b - a).cross(c - b).dot(normal) > 0.0;
+    if !is_convex {
+        return false;
+    }
+
+    !remaining.iter().any(|&index| {
+        index != previous
+            && index != current
+            && index != next
+            && is_inside_triangle(polygon[index], a, b, c, normal)
+    })
+}
+
+/// Points on the triangle's edges count as inside.
+fn is_inside_triangle(point: Point3, a: Point3, b: Point3, c: Point3, normal: Vec3) -> bool {
+    [(a, b), (b, c), (c, a)]
+        .into_iter()
+        .all(|(from, to)| (to - from).cross(point - from).dot(normal) >= 0.0)
+}
+
+#[test]
+fn test_triangulate_concave() {
+    /*
+    An L shape in the XY plane, counter-clockwise when viewed from +Z:
+
+    5--4
+    |  |
+    |  3--2
+    |     |
+    0-----1
+    */
+    let polygon = [
+        Point3 {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        },
+        Point3 {
+            x: 2.0,
+            y: 0.0,
+            z: 0.0,
+        },
+        Point3 {
+            x: 2.0,
+            y: 1.0,
+            z: 0.0,
+        },
+        Point3 {
+            x: 1.0,
+            y: 1.0,
+            z: 0.0,
+        },
+        Point3 {
+            x: 1.0,
+            y: 2.0,
+            z: 0.0,
+        },
+        Point3 {
+            x: 0.0,
+            y: 2.0,
+            z: 0.0,
+        },
+    ];
+
+    let triangles = triangulate(&polygon);
+    assert_eq!(triangles.len(), polygon.len() - 2);
+
+    let mut area = 0.0;
+    for [a, b, c] in triangles {
+        let a = polygon[a];
+        let b = polygon[b];
+        let c = polygon[c];
+
+        // Every triangle faces the same way as the polygon.
+        let normal = (b - a).cross(c - a);
+        assert!(normal.z > 0.0, "{:?}", (a, b, c));
+        area += normal.length() / 2.0;
+    }
+
+    // Triangles that poked outside the concave corner would add to the area.
+    assert_eq!(area, 3.0);
+}
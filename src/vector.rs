@@ -3,7 +3,16 @@ use cgmath::InnerSpace;
 use crate::point::Point3;
 
 #[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    bytemuck::Pod,
+    bytemuck::Zeroable,
+    PartialEq,
+    serde::Serialize,
+    serde::Deserialize,
+)]
 pub struct Vec3 {
     pub x: f32,
     pub y: f32,
@@ -57,6 +66,15 @@ impl Vec3 {
             z: self.z / length,
         }
     }
+
+    pub fn with_w(self, w: f32) -> Vec4 {
+        Vec4 {
+            x: self.x,
+            y: self.y,
+            z: self.z,
+            w,
+        }
+    }
 }
 
 impl From<Vec3> for cgmath::Vector3<f32> {
@@ -128,12 +146,42 @@ pub struct Vec2 {
     pub y: f32,
 }
 
+impl Vec2 {
+    pub const ZERO: Self = Vec2 { x: 0.0, y: 0.0 };
+}
+
 impl From<Vec2> for [f32; 2] {
     fn from(value: Vec2) -> Self {
         [value.x, value.y]
     }
 }
 
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Vec4 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+impl Vec4 {
+    pub const ZERO: Self = Vec4 {
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+        w: 0.0,
+    };
+
+    pub fn xyz(self) -> Vec3 {
+        Vec3 {
+            x: self.x,
+            y: self.y,
+            z: self.z,
+        }
+    }
+}
+
 impl From<Point3> for Vec3 {
     fn from(value: Point3) -> Self {
         Vec3 {
@@ -1,12 +1,54 @@
-use crate::{material::MaterialId, model_matrices::ModelMatrixId, point::Point3, vector::Vec3};
+use crate::{
+    material::MaterialId,
+    model_matrices::ModelMatrixId,
+    point::Point3,
+    vector::{Vec2, Vec3, Vec4},
+};
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Vertex {
     pub position: Point3,
+
+    /**
+    The object that the vertex belongs to, when it's part of a [`Model`](crate::load::Model).
+
+    It isn't used for drawing: meshes are positioned by the model matrices of their instances (see
+    [`Instance`](crate::instance::Instance)), so that a mesh can be drawn in many places.
+    */
     pub model_matrix_id: ModelMatrixId,
+
     pub normal: Vec3,
+
+    /**
+    The vertex's material, as an offset from its instance's material (see
+    [`Instance::material_count`](crate::instance::Instance::material_count)). For a vertex of a
+    [`Model`](crate::load::Model), it's the index of its material in the model's materials.
+    */
     pub material_id: MaterialId,
+
+    /// Texture coordinates. `(0, 0)` is the top left of a texture and `(1, 1)` is the bottom right.
+    pub uv: Vec2,
+
+    /**
+    A [MikkTSpace](http://www.mikktspace.com/) tangent (see [`crate::tangent`]).
+
+    `xyz` points in the direction of increasing `u`, and `w` is the sign of the bitangent:
+    `bitangent = w * cross(normal, xyz)`.
+    */
+    pub tangent: Vec4,
+
+    /// Indices in [`JointMatrices`](crate::joint_matrices::JointMatrices) of the joints that move
+    /// the vertex.
+    pub joints: [u32; 4],
+
+    /**
+    How much each of `joints` moves the vertex. They add up to 1 for skinned vertices.
+
+    Vertices whose weights are all 0 aren't skinned, and are positioned by their model matrix
+    alone. Skinned vertices are positioned by their model matrix after their joints.
+    */
+    pub weights: Vec4,
 }
 
 impl Vertex {
@@ -20,12 +62,6 @@ impl Vertex {
                 offset: 0,
                 shader_location: 0,
             },
-            // model_matrix_id: ModelMatrixId
-            wgpu::VertexAttribute {
-                format: wgpu::VertexFormat::Uint32,
-                offset: std::mem::size_of::<Point3>() as u64,
-                shader_location: 1,
-            },
             // normal: Vec3
             wgpu::VertexAttribute {
                 format: wgpu::VertexFormat::Float32x3,
@@ -41,6 +77,48 @@ impl Vertex {
                     + std::mem::size_of::<Vec3>() as u64,
                 shader_location: 3,
             },
+            // uv: Vec2
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x2,
+                offset: std::mem::size_of::<Point3>() as u64
+                    + std::mem::size_of::<ModelMatrixId>() as u64
+                    + std::mem::size_of::<Vec3>() as u64
+                    + std::mem::size_of::<MaterialId>() as u64,
+                shader_location: 4,
+            },
+            // tangent: Vec4
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x4,
+                offset: std::mem::size_of::<Point3>() as u64
+                    + std::mem::size_of::<ModelMatrixId>() as u64
+                    + std::mem::size_of::<Vec3>() as u64
+                    + std::mem::size_of::<MaterialId>() as u64
+                    + std::mem::size_of::<Vec2>() as u64,
+                shader_location: 5,
+            },
+            // joints: [u32; 4]
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Uint32x4,
+                offset: std::mem::size_of::<Point3>() as u64
+                    + std::mem::size_of::<ModelMatrixId>() as u64
+                    + std::mem::size_of::<Vec3>() as u64
+                    + std::mem::size_of::<MaterialId>() as u64
+                    + std::mem::size_of::<Vec2>() as u64
+                    + std::mem::size_of::<Vec4>() as u64,
+                shader_location: 6,
+            },
+            // weights: Vec4
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x4,
+                offset: std::mem::size_of::<Point3>() as u64
+                    + std::mem::size_of::<ModelMatrixId>() as u64
+                    + std::mem::size_of::<Vec3>() as u64
+                    + std::mem::size_of::<MaterialId>() as u64
+                    + std::mem::size_of::<Vec2>() as u64
+                    + std::mem::size_of::<Vec4>() as u64
+                    + std::mem::size_of::<[u32; 4]>() as u64,
+                shader_location: 7,
+            },
         ],
     };
 }
@@ -1,66 +1,179 @@
-use crate::vertex::Vertex;
+use std::ops::Range;
+
+use crate::{
+    error::CapacityError,
+    gpu_buffer::{self, Ranges, Relocation},
+    vertex::Vertex,
+};
+
+const USAGE: wgpu::BufferUsages = wgpu::BufferUsages::VERTEX
+    .union(wgpu::BufferUsages::COPY_DST)
+    .union(wgpu::BufferUsages::COPY_SRC);
 
 pub struct VertexBuffer {
     /// Handle to the underling GPU buffer.
     buffer: wgpu::Buffer,
 
-    /// Maximum number of vertices that can be stored.
+    /// Maximum number of vertices that can be stored before the buffer grows.
     capacity: u64,
 
-    /// Current number of stored vertices
-    size: u64,
+    /// Which vertices are stored.
+    ranges: Ranges,
 }
 
 impl VertexBuffer {
     pub fn new(device: &wgpu::Device, capacity: u64) -> Self {
-        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("VertexBuffer"),
-            size: capacity * std::mem::size_of::<Vertex>() as u64,
-            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
-
         Self {
-            buffer,
+            buffer: create_buffer(device, capacity),
             capacity,
-            size: 0,
+            ranges: Ranges::default(),
         }
     }
 
-    pub fn insert(&mut self, queue: &wgpu::Queue, vertex: Vertex) {
-        assert!(self.size + 1 < self.capacity);
+    /// Insert a vertex, and return its index.
+    pub fn insert(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        vertex: Vertex,
+    ) -> Result<u64, CapacityError> {
+        self.insert_many(device, queue, &[vertex])
+    }
+
+    /**
+    Insert vertices next to each other, and return the index of the first one.
+
+    The vertices go in the first removed range that they fit in, or after the last stored vertex.
+    When they don't fit after the last stored vertex, the buffer grows (see
+    [`GpuBuffer`](crate::gpu_buffer::GpuBuffer)), which fails when the device doesn't allow a
+    buffer that big.
+    */
+    pub fn insert_many(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        vertices: &[Vertex],
+    ) -> Result<u64, CapacityError> {
+        let vertices_len = vertices.len() as u64;
+        let first_vertex = match self.ranges.reuse(vertices_len) {
+            Some(first_vertex) => first_vertex,
+            None => {
+                let required = self.ranges.len() + vertices_len;
+                if required > self.capacity {
+                    self.grow(device, queue, required)?;
+                }
+                self.ranges.push(vertices_len)
+            }
+        };
         queue.write_buffer(
             &self.buffer,
-            self.size * std::mem::size_of::<Vertex>() as u64,
-            bytemuck::cast_slice(&[vertex]),
+            first_vertex * std::mem::size_of::<Vertex>() as u64,
+            bytemuck::cast_slice(vertices),
         );
-        self.size += 1;
+        Ok(first_vertex)
     }
 
-    pub fn insert_many(&mut self, queue: &wgpu::Queue, vertices: &[Vertex]) {
-        let vertices_len = vertices.len() as u64;
-        assert!(
-            self.size + vertices_len < self.capacity,
-            "vertex buffer exceeded allocated capacity of {} `Vertex`s",
-            self.capacity
+    /// Replace the underlying buffer with one that can hold at least `required` vertices.
+    fn grow(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        required: u64,
+    ) -> Result<(), CapacityError> {
+        let element_size = std::mem::size_of::<Vertex>() as u64;
+        let max = gpu_buffer::max_capacity(device, USAGE, element_size);
+        let capacity =
+            gpu_buffer::grown_capacity(self.capacity, required, max).ok_or(CapacityError {
+                buffer: String::from("VertexBuffer"),
+                requested: required - self.ranges.len(),
+                available: max.saturating_sub(self.ranges.len()),
+            })?;
+        log::debug!(
+            "Growing VertexBuffer from {} to {} vertices",
+            self.capacity,
+            capacity
         );
-        queue.write_buffer(
+
+        self.buffer = gpu_buffer::reallocate(
+            device,
+            queue,
+            "VertexBuffer",
+            USAGE,
             &self.buffer,
-            self.size * std::mem::size_of::<Vertex>() as u64,
-            bytemuck::cast_slice(vertices),
+            element_size,
+            self.ranges.len(),
+            capacity,
         );
-        self.size += vertices_len;
+        self.capacity = capacity;
+        Ok(())
     }
 
-    pub fn as_raw_slice(&self) -> wgpu::BufferSlice {
+    /**
+    Remove a range of vertices, so that later inserts can reuse it.
+
+    The vertices stay in the buffer until they're overwritten, so anything that still draws them
+    (e.g. indices that point at them) sees either the old vertices or the ones that replace them.
+    */
+    pub fn remove(&mut self, range: Range<u64>) {
+        self.ranges.remove(range);
+    }
+
+    /**
+    Move the stored vertices to the start of a new buffer, so that the space of removed vertices
+    can be used for larger inserts.
+
+    Returns the ranges that moved. Indices that point into a moved range need to be offset by
+    `to - from.start`, which [`IndexBuffer::compact`](crate::index_buffer::IndexBuffer::compact)
+    does, and render passes need the new [`VertexBuffer::as_raw_slice`].
+    */
+    pub fn compact(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+    ) -> Vec<Relocation> {
+        if !self.ranges.has_removed() {
+            return Vec::new();
+        }
+
+        let vertex_size = std::mem::size_of::<Vertex>() as u64;
+        let buffer = create_buffer(device, self.capacity);
+        let relocations = self.ranges.compact();
+        for relocation in &relocations {
+            encoder.copy_buffer_to_buffer(
+                &self.buffer,
+                relocation.from.start * vertex_size,
+                &buffer,
+                relocation.to * vertex_size,
+                (relocation.from.end - relocation.from.start) * vertex_size,
+            );
+        }
+
+        self.buffer = buffer;
+        relocations
+            .into_iter()
+            .filter(|relocation| relocation.from.start != relocation.to)
+            .collect()
+    }
+
+    pub fn as_raw_slice(&self) -> wgpu::BufferSlice<'_> {
         self.buffer.slice(..)
     }
 
+    /// One past the last stored vertex. Removed vertices before it are counted.
     pub fn len(&self) -> u64 {
-        self.size
+        self.ranges.len()
     }
 
     pub fn is_empty(&self) -> bool {
         self.len() == 0
     }
 }
+
+fn create_buffer(device: &wgpu::Device, capacity: u64) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("VertexBuffer"),
+        size: capacity * std::mem::size_of::<Vertex>() as u64,
+        usage: USAGE,
+        mapped_at_creation: false,
+    })
+}
@@ -1,4 +1,5 @@
 use crate::{
+    error::CapacityError,
     gpu_buffer::GpuBuffer,
     matrix::Matrix4,
     model_matrices::{ModelMatrices, ModelMatrixId},
@@ -9,38 +10,61 @@ use crate::{
 pub struct Wireframe {
     pub model_matrix_id: ModelMatrixId,
     pub vertex_buffer_offset: u32,
+    pub vertex_count: u32,
 }
 
 pub fn add<T: IntoIterator<Item = (Point3, Point3)>>(
+    device: &wgpu::Device,
     queue: &wgpu::Queue,
     model_matrices: &mut ModelMatrices,
     vertex_buffer: &mut GpuBuffer<render_wireframe::VertexInput>,
     model_matrix: Matrix4,
     lines: T,
-) -> Wireframe {
-    let model_matrix_id = model_matrices.insert(queue, model_matrix);
+) -> Result<Wireframe, CapacityError> {
+    let model_matrix_id = model_matrices.insert(device, queue, model_matrix)?;
 
-    let vertex_buffer_offset = vertex_buffer.len();
+    let vertices: Vec<render_wireframe::VertexInput> = lines
+        .into_iter()
+        .flat_map(|(from, to)| {
+            [
+                render_wireframe::VertexInput {
+                    position: from,
+                    model_matrix_id,
+                },
+                render_wireframe::VertexInput {
+                    position: to,
+                    model_matrix_id,
+                },
+            ]
+        })
+        .collect();
+    let vertex_buffer_offset = match vertex_buffer.insert_many(device, queue, &vertices) {
+        Ok(vertex_buffer_offset) => vertex_buffer_offset,
+        Err(error) => {
+            model_matrices.remove(queue, model_matrix_id);
+            return Err(error);
+        }
+    };
 
-    for (from, to) in lines {
-        vertex_buffer.insert(
-            queue,
-            render_wireframe::VertexInput {
-                position: from,
-                model_matrix_id,
-            },
-        );
-        vertex_buffer.insert(
-            queue,
-            render_wireframe::VertexInput {
-                position: to,
-                model_matrix_id,
-            },
-        );
-    }
-
-    Wireframe {
+    Ok(Wireframe {
         model_matrix_id,
         vertex_buffer_offset,
+        vertex_count: vertices.len() as u32,
+    })
+}
+
+/// Remove the wireframe's model matrix and vertices. The vertices are zeroed, so their lines
+/// collapse to a point until their slots are reused.
+pub fn remove(
+    queue: &wgpu::Queue,
+    model_matrices: &mut ModelMatrices,
+    vertex_buffer: &mut GpuBuffer<render_wireframe::VertexInput>,
+    wireframe: &Wireframe,
+) {
+    model_matrices.remove(queue, wireframe.model_matrix_id);
+    let vertices =
+        wireframe.vertex_buffer_offset..wireframe.vertex_buffer_offset + wireframe.vertex_count;
+    for index in vertices {
+        vertex_buffer.remove(queue, index);
     }
 }